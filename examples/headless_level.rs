@@ -0,0 +1,113 @@
+//Runs a whole level without window, audio or renderer. Useful for CI and balancing scripts
+//Usage: cargo run --release --example headless_level -- [--frames <max_frames>] [--seed <seed>] [--level <assets/levels/*.level.ron>] [--difficulty <easy|normal|hard|custom>]
+//Replay a recorded session as a regression test: cargo run --release --example headless_level -- --replay <file>
+
+use std::time::Duration;
+
+use bevy::{input::InputPlugin, prelude::*, time::TimeUpdateStrategy};
+use bevy_game::{
+    game_rng::GameRng,
+    campaign::Campaign,
+    difficulty::{Difficulty, DifficultyPreset},
+    director::Director,
+    level::LevelDescription,
+    replay::ReplayPlayback,
+    safe_area::SheepCounter,
+    storyteller::{FailReason, Score},
+    GameState, SimulationPlugin,
+};
+
+const FRAME_TIME: f32 = 1.0 / 60.0;
+
+fn main() {
+    let args = std::env::args().collect::<Vec<_>>();
+    let max_frames: usize = args
+        .iter()
+        .position(|arg| arg == "--frames")
+        .and_then(|idx| args.get(idx + 1))
+        .and_then(|arg| arg.parse().ok())
+        .unwrap_or(60 * 60 * 7);
+
+    //Without asset server the built-in level is used, unless level file is given
+    let level = args
+        .iter()
+        .position(|arg| arg == "--level")
+        .and_then(|idx| args.get(idx + 1))
+        .map(|path| {
+            let text = std::fs::read_to_string(path).expect("Failed to read level file");
+            LevelDescription::from_ron(&text).expect("Failed to parse level file")
+        })
+        .unwrap_or_default();
+
+    let difficulty = args
+        .iter()
+        .position(|arg| arg == "--difficulty")
+        .and_then(|idx| args.get(idx + 1))
+        .map(|name| DifficultyPreset::from_name(name).expect("Unknown difficulty"))
+        .unwrap_or_default();
+
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, InputPlugin, SimulationPlugin))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+            FRAME_TIME,
+        )));
+
+    app.insert_resource(Campaign::new(vec![level]));
+    app.insert_resource(Difficulty::load(difficulty));
+
+    //Without asset loading we can go straight from Loading to Playing
+    app.update();
+    app.world
+        .resource_mut::<NextState<GameState>>()
+        .set(GameState::Playing);
+
+    let mut frames = 0;
+    while frames < max_frames {
+        app.update();
+        frames += 1;
+        if *app.world.resource::<State<GameState>>().get() == GameState::Finish {
+            break;
+        }
+    }
+
+    let score = app.world.resource::<Score>().0;
+    let in_safe_area = app.world.resource::<SheepCounter>().count;
+    let fail = match app.world.get_resource::<FailReason>() {
+        Some(FailReason::SheepDied) => "sheep died".to_string(),
+        Some(FailReason::TaskFailed(reason)) => format!("task failed: {}", reason),
+        None => "none".to_string(),
+    };
+
+    let director = app.world.resource::<Director>();
+    let mean_tension = director.history.iter().map(|s| s.tension).sum::<f32>()
+        / director.history.len().max(1) as f32;
+    println!(
+        "tension: max {:.2} mean {:.2} over {} samples",
+        director.max_tension(),
+        mean_tension,
+        director.history.len()
+    );
+    assert!((0.0..=1.0).contains(&director.max_tension()));
+
+    println!(
+        "seed: {} frames: {} state: {:?} score: {:.1} sheep in safe area: {} fail reason: {}",
+        app.world.resource::<GameRng>().seed(),
+        frames,
+        app.world.resource::<State<GameState>>().get(),
+        score,
+        in_safe_area,
+        fail
+    );
+
+    assert!(score >= 0.0);
+
+    //Level must end exactly when the recorded one did, otherwise simulation diverged from the recording
+    if let Some(playback) = app.world.get_resource::<ReplayPlayback>() {
+        println!(
+            "replay: played {} of {} recorded frames",
+            playback.cursor,
+            playback.replay.frames.len()
+        );
+        assert_eq!(playback.cursor, playback.replay.frames.len());
+    }
+}
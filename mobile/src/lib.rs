@@ -1,6 +1,6 @@
 use bevy::prelude::*;
 use bevy::window::WindowMode;
-use bevy_game::GamePlugin; // ToDo: Replace bevy_game with your new crate name.
+use bevy_game::{touch::TouchControlsPlugin, GamePlugin}; // ToDo: Replace bevy_game with your new crate name.
 
 #[bevy_main]
 fn main() {
@@ -15,6 +15,7 @@ fn main() {
                 ..default()
             }),
             GamePlugin,
+            TouchControlsPlugin,
         ))
         .run()
 }
@@ -34,13 +34,6 @@ fn startup(
         ..default()
     });
 
-    commands.spawn(AudioBundle {
-        source: asset_server.load("audio/sheep.ogg"),
-        settings: PlaybackSettings {
-            mode: PlaybackMode::Loop,
-            ..Default::default()
-        },
-        ..default()
-    });
+    //Sheep are heard one by one, see bleating.rs
 }
 
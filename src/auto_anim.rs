@@ -8,6 +8,11 @@ pub trait AnimSet {
     fn get_index_range(&self) -> AnimRange;
     fn get_tile_count() -> usize;
 
+    //Multiplied with the tile colors, lets several sets share the same tiles
+    fn get_tint() -> Color {
+        Color::WHITE
+    }
+
     fn get_tile_name(idx : usize) -> String {
         //write in format tileXXX.png with exactly 3 digits
         format!("tile{:03}.png", idx)
@@ -55,6 +60,7 @@ fn init_storage<T : AnimSet + Send + Sync + 'static>(
     let mut ms = Vec::new();
     for i in 0..T::get_tile_count() {
         ms.push(materials.add(StandardMaterial {
+            base_color: T::get_tint(),
             base_color_texture: Some(asset_server.load(T::get_tile_path(i))),
             alpha_mode: AlphaMode::Blend,
             reflectance: 0.1,
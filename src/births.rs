@@ -0,0 +1,89 @@
+//Lambs are born at dawn. Every well-fed adult sheep which spends the night in a safe area
+//can give birth with BIRTH_CHANCE. Newborns join the flock which goes to the next night,
+//so a well kept flock grows during the campaign instead of only losing sheep to wolves
+
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    game_rng::GameRng,
+    safe_area::OutOfSafeArea,
+    sheep::{spawn_sheep, Sheep, StartSheepCount},
+    sheep_traits::{SheepTraits, LAMB_SPAWN_RADIUS},
+    storyteller::{FailReason, LevelClock, Score, ScoreBonus, Storyteller},
+    GameSet, GameState, SimSet,
+};
+
+//Sheep with less hunger is well-fed
+const WELL_FED_HUNGER: f32 = 0.3;
+const BIRTH_CHANCE: f32 = 0.05;
+
+pub struct BirthsPlugin;
+
+impl Plugin for BirthsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Births>()
+            .add_systems(OnEnter(GameState::Playing), reset_births)
+            .add_systems(
+                Update,
+                births_at_dawn
+                    .in_set(GameSet::Playing)
+                    .in_set(SimSet::Sheep),
+            );
+    }
+}
+
+/// Lambs born at the dawn of the current night
+#[derive(Resource, Default, Debug)]
+pub struct Births {
+    pub count: usize,
+    pub done: bool,
+}
+
+fn reset_births(mut births: ResMut<Births>) {
+    *births = Births::default();
+}
+
+fn births_at_dawn(
+    mut commands: Commands,
+    mut births: ResMut<Births>,
+    teller: Res<Storyteller>,
+    clock: Res<LevelClock>,
+    fail: Option<Res<FailReason>>,
+    mut rng: ResMut<GameRng>,
+    mut score: ResMut<Score>,
+    mut bonus: ResMut<ScoreBonus>,
+    mut start_sheep_count: ResMut<StartSheepCount>,
+    sheep: Query<(Entity, &Transform, &SheepTraits), (With<Sheep>, Without<OutOfSafeArea>)>,
+) {
+    if births.done || fail.is_some() || teller.get_level_time(&clock) < teller.level_duration {
+        return;
+    }
+    births.done = true;
+
+    let mothers: Vec<(Entity, Vec3)> = sheep
+        .iter()
+        .filter(|(_, _, traits)| !traits.is_lamb() && traits.hunger < WELL_FED_HUNGER)
+        .filter(|_| rng.gen_range(0.0..1.0) < BIRTH_CHANCE)
+        .map(|(e, t, _)| (e, t.translation))
+        .collect();
+
+    for (mother, pos) in mothers.iter() {
+        let angle = rng.gen_range(0.0..PI * 2.0);
+        let pos = *pos + Vec3::new(angle.cos(), 0.0, angle.sin()) * LAMB_SPAWN_RADIUS;
+        let traits = SheepTraits::newborn(&mut *rng);
+        spawn_sheep(&mut commands, Vec3::new(pos.x, 0.0, pos.z), traits, Some(*mother));
+    }
+
+    //Every newborn is worth a sheep which survived the whole night. Score is not updated
+    //after the level end, so the bonus goes to the score right away
+    let born = mothers.len();
+    let born_score = born as f32 / start_sheep_count.0 * clock.elapsed;
+    bonus.0 += born_score;
+    score.0 += born_score;
+    start_sheep_count.0 += born as f32;
+    births.count = born;
+    info!("{} lambs born at dawn", born);
+}
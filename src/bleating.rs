@@ -0,0 +1,177 @@
+//Spatial bleats of single sheep, so the player hears where the trouble is.
+//Bleat is triggered when a sheep gets scared, is hunted by a wolf or is far from the flock.
+//Calm sheep bleat sometimes too, it replaces the old global flock loop.
+//Only MAX_VOICES bleats play at once, more important ones win when there are more candidates.
+//Listener is on the dog, see player::attach_dog_visuals
+
+use bevy::{
+    audio::{PlaybackMode, Volume},
+    prelude::*,
+    utils::HashMap,
+};
+use rand::Rng;
+
+use crate::{
+    neighbours::NearestSheep,
+    sheep::{IsScared, Sheep},
+    wolf::UnderHunting,
+    GameSet, GameState, GameStuff, VisualsSet,
+};
+
+const BLEAT_PATH: &str = "audio/sheep.ogg";
+//Bleat is cut from the flock recording
+const BLEAT_TIME: f32 = 1.5;
+const MAX_VOICES: usize = 8;
+//Same sheep does not bleat again during this time
+const SHEEP_COOLDOWN: f32 = 4.0;
+//Sheep without a neighbour this close is separated from the flock
+const SEPARATED_DISTANCE: f32 = 6.0;
+//Separated and calm sheep are checked this often
+const CHECK_PERIOD: f32 = 1.0;
+//Random calm sheep which bleat per check
+const CALM_BLEATS: usize = 2;
+
+/// Audio distances are in level meters multiplied by this, see PresentationPlugin
+pub const AUDIO_SCALE: f32 = 0.2;
+
+pub struct BleatingPlugin;
+
+impl Plugin for BleatingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Bleating>()
+            .add_systems(OnEnter(GameState::Playing), reset_bleating)
+            .add_systems(
+                Update,
+                (bleat, stop_bleats)
+                    .chain()
+                    .in_set(VisualsSet)
+                    .in_set(GameSet::Playing),
+            );
+    }
+}
+
+//Lower value is more important
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum BleatReason {
+    Hunted,
+    Scared,
+    Separated,
+    Calm,
+}
+
+impl BleatReason {
+    fn volume(&self) -> f32 {
+        match self {
+            BleatReason::Hunted => 1.0,
+            BleatReason::Scared => 0.8,
+            BleatReason::Separated => 0.7,
+            BleatReason::Calm => 0.4,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct Bleating {
+    //Time of the last bleat of every sheep which has bleated recently
+    last_bleat: HashMap<Entity, f32>,
+    since_check: f32,
+}
+
+/// Playing bleat, despawned after BLEAT_TIME
+#[derive(Component)]
+pub struct Bleat {
+    time: f32,
+}
+
+fn reset_bleating(mut bleating: ResMut<Bleating>) {
+    *bleating = Bleating::default();
+}
+
+fn bleat(
+    mut commands: Commands,
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    mut bleating: ResMut<Bleating>,
+    voices: Query<(), With<Bleat>>,
+    hunted: Query<Entity, (With<Sheep>, Added<UnderHunting>)>,
+    scared: Query<Entity, (With<Sheep>, Added<IsScared>)>,
+    flock: Query<(Entity, &Transform, &NearestSheep), With<Sheep>>,
+) {
+    let now = time.elapsed_seconds();
+    bleating.last_bleat.retain(|_, last| now - *last < SHEEP_COOLDOWN);
+
+    let mut candidates: Vec<(BleatReason, Entity)> = Vec::new();
+    candidates.extend(hunted.iter().map(|e| (BleatReason::Hunted, e)));
+    candidates.extend(scared.iter().map(|e| (BleatReason::Scared, e)));
+
+    bleating.since_check += time.delta_seconds();
+    if bleating.since_check >= CHECK_PERIOD {
+        bleating.since_check = 0.0;
+        for (e, t, nearest) in flock.iter() {
+            let separated = nearest
+                .0
+                .get(1)
+                .map_or(true, |(pos, _)| pos.distance(t.translation) > SEPARATED_DISTANCE);
+            if separated {
+                candidates.push((BleatReason::Separated, e));
+            }
+        }
+
+        let count = flock.iter().count();
+        if count > 0 {
+            let mut rng = rand::thread_rng();
+            for _ in 0..CALM_BLEATS {
+                if let Some((e, _, _)) = flock.iter().nth(rng.gen_range(0..count)) {
+                    candidates.push((BleatReason::Calm, e));
+                }
+            }
+        }
+    }
+
+    let free = MAX_VOICES.saturating_sub(voices.iter().count());
+    if free == 0 || candidates.is_empty() {
+        return;
+    }
+    candidates.sort_by_key(|(reason, _)| *reason);
+
+    let mut rng = rand::thread_rng();
+    let mut started = 0;
+    for (reason, e) in candidates {
+        if started >= free {
+            break;
+        }
+        if bleating.last_bleat.contains_key(&e) {
+            continue;
+        }
+        let Ok((_, t, _)) = flock.get(e) else {
+            continue;
+        };
+        bleating.last_bleat.insert(e, now);
+        started += 1;
+
+        commands.spawn((
+            Bleat { time: BLEAT_TIME },
+            AudioBundle {
+                source: asset_server.load(BLEAT_PATH),
+                settings: PlaybackSettings {
+                    mode: PlaybackMode::Despawn,
+                    volume: Volume::new_relative(reason.volume()),
+                    speed: rng.gen_range(0.9..1.2),
+                    spatial: true,
+                    ..default()
+                },
+            },
+            SpatialBundle::from_transform(Transform::from_translation(t.translation)),
+            GameStuff,
+        ));
+    }
+}
+
+fn stop_bleats(mut commands: Commands, time: Res<Time>, mut bleats: Query<(Entity, &mut Bleat)>) {
+    for (e, mut bleat) in bleats.iter_mut() {
+        bleat.time -= time.delta_seconds();
+        if bleat.time <= 0.0 {
+            commands.entity(e).despawn_recursive();
+        }
+    }
+}
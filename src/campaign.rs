@@ -0,0 +1,181 @@
+//Campaign is an ordered list of nights (levels from levels.assets.ron)
+//Surviving flock with lambs born at dawn and score go to the next night. Lambs grow up night by night. Failed night is retried with the same flock
+
+use bevy::prelude::*;
+
+use crate::{
+    births::Births,
+    level::{CurrentLevel, LevelDescription},
+    sheep::Sheep,
+    sheep_traits::{SheepTraits, ADULT_AGE, NIGHT_AGE},
+    storyteller::{FailReason, Score},
+    GameState,
+};
+
+pub struct CampaignPlugin;
+
+impl Plugin for CampaignPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Campaign>()
+            .add_systems(OnEnter(GameState::Playing), start_night.in_set(CampaignSet))
+            .add_systems(OnEnter(GameState::Finish), finish_night.in_set(CampaignSet));
+    }
+}
+
+//Level setup systems must run after this set, it selects CurrentLevel. Finish screen reads the night result after it
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct CampaignSet;
+
+#[derive(Resource, Clone, Debug)]
+pub struct Campaign {
+    pub levels: Vec<LevelDescription>,
+    //Index of the night which will be played next
+    pub night: usize,
+    //Highest night player can start from
+    pub unlocked: usize,
+    //Sheep survived previous night. None on the first night, level flock size is used then
+    pub flock: Option<usize>,
+    //Ages of lambs among the flock, they are spawned next to their mothers
+    pub lamb_ages: Option<Vec<f32>>,
+    //Sum of scores of completed nights
+    pub score: f32,
+    pub last_result: Option<NightResult>,
+}
+
+#[derive(Clone, Debug)]
+pub struct NightResult {
+    pub night: usize,
+    pub score: f32,
+    pub total_score: f32,
+    pub flock: usize,
+    //Lambs born at dawn, already counted in flock
+    pub born: usize,
+    pub failed: bool,
+    pub campaign_complete: bool,
+}
+
+impl Default for Campaign {
+    fn default() -> Self {
+        Self::new(vec![LevelDescription::default()])
+    }
+}
+
+impl Campaign {
+    pub fn new(levels: Vec<LevelDescription>) -> Self {
+        Self {
+            levels,
+            night: 0,
+            unlocked: 0,
+            flock: None,
+            lamb_ages: None,
+            score: 0.0,
+            last_result: None,
+        }
+    }
+
+    /// Replace list of nights. Progress is kept, but clamped to the new list
+    pub fn set_levels(&mut self, levels: Vec<LevelDescription>) {
+        self.levels = levels;
+        self.night = self.night.min(self.levels.len());
+        self.unlocked = self.unlocked.min(self.levels.len() - 1);
+    }
+
+    /// Saved campaign which can be continued from the menu
+    pub fn in_progress(&self) -> bool {
+        self.night > 0 && self.night < self.night_count()
+    }
+
+    pub fn night_count(&self) -> usize {
+        self.levels.len()
+    }
+
+    /// Level of the next night with carried flock size
+    pub fn current_level(&self) -> LevelDescription {
+        let mut level = self.levels[self.night.min(self.levels.len() - 1)].clone();
+        if let Some(flock) = self.flock {
+            level.flock.count = flock;
+        }
+        if let Some(ages) = &self.lamb_ages {
+            level.flock.lamb_ages = Some(ages.clone());
+        }
+        level
+    }
+
+    /// Start over from the first night. Unlocked nights stay unlocked
+    pub fn restart(&mut self) {
+        self.select_night(0);
+    }
+
+    /// Start from any unlocked night with the flock size from the level
+    pub fn select_night(&mut self, night: usize) {
+        self.night = night.min(self.unlocked);
+        self.flock = None;
+        self.lamb_ages = None;
+        self.score = 0.0;
+        self.last_result = None;
+    }
+}
+
+pub(crate) fn start_night(
+    mut campaign: ResMut<Campaign>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut score: ResMut<Score>,
+) {
+    if campaign
+        .last_result
+        .as_ref()
+        .is_some_and(|result| result.campaign_complete)
+    {
+        campaign.restart();
+    }
+
+    let level = campaign.current_level();
+    info!(
+        "Night {}/{}: {} with {} sheep",
+        campaign.night + 1,
+        campaign.night_count(),
+        level.name,
+        level.flock.count
+    );
+
+    current_level.0 = level;
+    score.0 = 0.0;
+}
+
+fn finish_night(
+    mut campaign: ResMut<Campaign>,
+    score: Res<Score>,
+    fail: Option<Res<FailReason>>,
+    births: Res<Births>,
+    sheep: Query<&SheepTraits, With<Sheep>>,
+) {
+    let night = campaign.night;
+    let flock = sheep.iter().count();
+    //Lambs are a night older next time, grown up ones join the adults
+    let lamb_ages: Vec<f32> = sheep
+        .iter()
+        .filter(|traits| traits.is_lamb())
+        .map(|traits| traits.age + NIGHT_AGE)
+        .filter(|age| *age < ADULT_AGE)
+        .collect();
+    let failed = fail.is_some();
+
+    if !failed {
+        campaign.score += score.0;
+        campaign.flock = Some(flock);
+        campaign.lamb_ages = Some(lamb_ages);
+        campaign.night += 1;
+        campaign.unlocked = campaign.unlocked.max(campaign.night.min(campaign.night_count() - 1));
+    }
+
+    let campaign_complete = campaign.night >= campaign.night_count();
+    campaign.last_result = Some(NightResult {
+        night,
+        score: score.0,
+        total_score: campaign.score,
+        flock,
+        born: births.count,
+        failed,
+        campaign_complete,
+    });
+}
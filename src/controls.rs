@@ -0,0 +1,545 @@
+//Action mapping for the dog. Simulation reads ActionState only, never raw keys
+//Bindings for keyboard, mouse and gamepad are stored as "controls" in crate::storage and can be changed in the main menu
+
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{player::PlayerInputSet, storage, GameSet, GameState};
+
+const BINDINGS_NAME: &str = "controls";
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum ControlAction {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    Sprint,
+    Bark,
+    BigBark,
+    ToggleMovementStyle,
+}
+
+impl ControlAction {
+    pub const ALL: [ControlAction; 8] = [
+        ControlAction::MoveUp,
+        ControlAction::MoveDown,
+        ControlAction::MoveLeft,
+        ControlAction::MoveRight,
+        ControlAction::Sprint,
+        ControlAction::Bark,
+        ControlAction::BigBark,
+        ControlAction::ToggleMovementStyle,
+    ];
+
+    //Actions which are pressed or not. Movement goes to the move axis instead
+    pub const BUTTONS: [ControlAction; 4] = [
+        ControlAction::Sprint,
+        ControlAction::Bark,
+        ControlAction::BigBark,
+        ControlAction::ToggleMovementStyle,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ControlAction::MoveUp => "Move up",
+            ControlAction::MoveDown => "Move down",
+            ControlAction::MoveLeft => "Move left",
+            ControlAction::MoveRight => "Move right",
+            ControlAction::Sprint => "Sprint",
+            ControlAction::Bark => "Bark",
+            ControlAction::BigBark => "Big bark",
+            ControlAction::ToggleMovementStyle => "Mouse/keys movement",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButtonType),
+}
+
+impl Binding {
+    //Rebinding replaces the binding of the same device: keyboard, mouse and gamepad separately
+    fn same_device(&self, other: &Binding) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            Binding::Key(key) => format!("{:?}", key),
+            Binding::Mouse(button) => format!("Mouse {:?}", button),
+            Binding::Gamepad(button) => format!("Pad {:?}", button),
+        }
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Clone, Debug)]
+pub struct ControlBindings {
+    pub bindings: BTreeMap<ControlAction, Vec<Binding>>,
+    //Left stick moves the dog, values below the dead zone are ignored
+    pub stick_dead_zone: f32,
+}
+
+impl Default for ControlBindings {
+    fn default() -> Self {
+        use Binding::*;
+
+        let bindings = [
+            (ControlAction::MoveUp, vec![Key(KeyCode::W), Gamepad(GamepadButtonType::DPadUp)]),
+            (ControlAction::MoveDown, vec![Key(KeyCode::S), Gamepad(GamepadButtonType::DPadDown)]),
+            (ControlAction::MoveLeft, vec![Key(KeyCode::A), Gamepad(GamepadButtonType::DPadLeft)]),
+            (ControlAction::MoveRight, vec![Key(KeyCode::D), Gamepad(GamepadButtonType::DPadRight)]),
+            (ControlAction::Sprint, vec![Key(KeyCode::ShiftLeft), Gamepad(GamepadButtonType::LeftTrigger2)]),
+            (ControlAction::Bark, vec![Key(KeyCode::Space), Mouse(MouseButton::Right), Gamepad(GamepadButtonType::South)]),
+            (ControlAction::BigBark, vec![Key(KeyCode::ControlLeft), Gamepad(GamepadButtonType::West)]),
+            (ControlAction::ToggleMovementStyle, vec![Key(KeyCode::Tab), Gamepad(GamepadButtonType::Select)]),
+        ];
+
+        Self {
+            bindings: bindings.into_iter().collect(),
+            stick_dead_zone: 0.2,
+        }
+    }
+}
+
+impl ControlBindings {
+    pub fn get(&self, action: ControlAction) -> &[Binding] {
+        self.bindings.get(&action).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Replace binding of the same device for the action. One button triggers only one action,
+    /// so other actions lose this binding. Returns these actions
+    pub fn rebind(&mut self, action: ControlAction, binding: Binding) -> Vec<ControlAction> {
+        let mut unbound = Vec::new();
+        for (other_action, bindings) in self.bindings.iter_mut() {
+            if *other_action != action && bindings.contains(&binding) {
+                bindings.retain(|other| *other != binding);
+                unbound.push(*other_action);
+            }
+        }
+
+        let bindings = self.bindings.entry(action).or_default();
+        bindings.retain(|other| !other.same_device(&binding));
+        bindings.push(binding);
+        unbound
+    }
+
+    /// Load from disk or localStorage. Missing or broken file gives default bindings
+    pub fn load() -> Self {
+        let Some(text) = storage::read(BINDINGS_NAME) else {
+            return Self::default();
+        };
+
+        match ron::from_str::<Self>(&text) {
+            Ok(mut loaded) => {
+                //Actions added after the file was written keep default bindings
+                for (action, bindings) in Self::default().bindings {
+                    loaded.bindings.entry(action).or_insert(bindings);
+                }
+                loaded
+            }
+            Err(err) => {
+                warn!("Failed to read control bindings, using default ones: {}", err);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+            Ok(text) => {
+                if let Err(err) = storage::write(BINDINGS_NAME, &text) {
+                    error!("Failed to write control bindings: {}", err);
+                }
+            }
+            Err(err) => error!("Failed to serialize control bindings: {}", err),
+        }
+    }
+}
+
+/// Player actions of the current frame. Filled from devices or from a replay file
+#[derive(Resource, Default, Clone, Debug)]
+pub struct ActionState {
+    //x to the right, y forward. Length is at most 1, less for a half-tilted stick
+    pub move_axis: Vec2,
+    pressed: Vec<ControlAction>,
+    just_pressed: Vec<ControlAction>,
+    just_released: Vec<ControlAction>,
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: ControlAction) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: ControlAction) -> bool {
+        self.just_pressed.contains(&action)
+    }
+
+    pub fn just_released(&self, action: ControlAction) -> bool {
+        self.just_released.contains(&action)
+    }
+
+    /// Set state of all button actions for the new frame
+    pub fn update(&mut self, pressed: impl IntoIterator<Item = ControlAction>) {
+        let previous = std::mem::take(&mut self.pressed);
+        self.set(pressed, &previous);
+    }
+
+    /// Set state of all button actions against the given previous frame.
+    /// Replay compares with its own previous frame, not with the live input
+    pub fn set(
+        &mut self,
+        pressed: impl IntoIterator<Item = ControlAction>,
+        previous: &[ControlAction],
+    ) {
+        let pressed = pressed.into_iter().collect::<Vec<_>>();
+        self.just_pressed = pressed
+            .iter()
+            .filter(|action| !previous.contains(action))
+            .copied()
+            .collect();
+        self.just_released = previous
+            .iter()
+            .filter(|action| !pressed.contains(action))
+            .copied()
+            .collect();
+        self.pressed = pressed;
+    }
+}
+
+/// Input from on-screen controls (see crate::touch). Merged into ActionState with devices
+#[derive(Resource, Default, Clone, Debug)]
+pub struct VirtualInput {
+    pub move_axis: Vec2,
+    pub pressed: Vec<ControlAction>,
+}
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ControlBindings>()
+            .init_resource::<ActionState>()
+            .init_resource::<VirtualInput>()
+            .add_systems(PreUpdate, update_action_state.in_set(PlayerInputSet));
+    }
+}
+
+/// Persisted bindings and rebinding buttons in the main menu
+pub struct ControlsMenuPlugin;
+
+impl Plugin for ControlsMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ControlBindings::load())
+            .init_resource::<Rebinding>()
+            .init_resource::<RebindNotice>()
+            .add_systems(OnEnter(GameState::Menu), setup_controls_menu)
+            .add_systems(OnExit(GameState::Menu), cleanup_controls_menu)
+            .add_systems(
+                Update,
+                (capture_rebind, controls_button_system, controls_text)
+                    .chain()
+                    .in_set(GameSet::Menu),
+            );
+    }
+}
+
+fn binding_pressed(
+    binding: &Binding,
+    keys: &Input<KeyCode>,
+    mouse: &Input<MouseButton>,
+    gamepad_buttons: &Input<GamepadButton>,
+    gamepads: &Gamepads,
+) -> bool {
+    match binding {
+        Binding::Key(key) => keys.pressed(*key),
+        Binding::Mouse(button) => mouse.pressed(*button),
+        Binding::Gamepad(button) => gamepads
+            .iter()
+            .any(|gamepad| gamepad_buttons.pressed(GamepadButton::new(gamepad, *button))),
+    }
+}
+
+fn update_action_state(
+    mut actions: ResMut<ActionState>,
+    bindings: Res<ControlBindings>,
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    gamepads: Res<Gamepads>,
+    virtual_input: Res<VirtualInput>,
+) {
+    let pressed = |action: ControlAction| {
+        virtual_input.pressed.contains(&action)
+            || bindings.get(action).iter().any(|binding| {
+                binding_pressed(binding, &keys, &mouse, &gamepad_buttons, &gamepads)
+            })
+    };
+
+    let mut move_axis = virtual_input.move_axis;
+    if pressed(ControlAction::MoveUp) {
+        move_axis.y += 1.0;
+    }
+    if pressed(ControlAction::MoveDown) {
+        move_axis.y -= 1.0;
+    }
+    if pressed(ControlAction::MoveLeft) {
+        move_axis.x -= 1.0;
+    }
+    if pressed(ControlAction::MoveRight) {
+        move_axis.x += 1.0;
+    }
+
+    for gamepad in gamepads.iter() {
+        let stick = Vec2::new(
+            gamepad_axes
+                .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX))
+                .unwrap_or_default(),
+            gamepad_axes
+                .get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY))
+                .unwrap_or_default(),
+        );
+        if stick.length() > bindings.stick_dead_zone {
+            move_axis += stick;
+        }
+    }
+
+    actions.move_axis = move_axis.clamp_length_max(1.0);
+    let buttons = ControlAction::BUTTONS.into_iter().filter(|action| pressed(*action));
+    actions.update(buttons);
+}
+
+/// Action which waits for a new binding
+#[derive(Resource, Default)]
+struct Rebinding(Option<ControlAction>);
+
+/// Actions which lost their binding on the last rebind, shown under the menu title
+#[derive(Resource, Default)]
+struct RebindNotice(Option<String>);
+
+#[derive(Component)]
+struct ControlsMenu;
+
+#[derive(Component)]
+struct ControlsTitle;
+
+#[derive(Component)]
+struct RebindButton(ControlAction);
+
+fn setup_controls_menu(mut commands: Commands) {
+    let mut text_style = TextStyle::default();
+    text_style.font_size = 18.0;
+
+    commands
+        .spawn((
+            ControlsMenu,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(10.0),
+                    top: Val::Px(10.0),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(4.0),
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                ControlsTitle,
+                TextBundle::from_section("Controls (click to rebind)", text_style.clone()),
+            ));
+
+            for action in ControlAction::ALL {
+                parent
+                    .spawn((
+                        RebindButton(action),
+                        ButtonBundle {
+                            style: Style {
+                                padding: UiRect::axes(Val::Px(8.0), Val::Px(4.0)),
+                                border: UiRect::all(Val::Px(2.0)),
+                                ..default()
+                            },
+                            border_color: BorderColor(Color::WHITE),
+                            background_color: BackgroundColor(Color::BLACK),
+                            ..default()
+                        },
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section("", text_style.clone()));
+                    });
+            }
+        });
+}
+
+fn cleanup_controls_menu(
+    mut commands: Commands,
+    query: Query<Entity, With<ControlsMenu>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut notice: ResMut<RebindNotice>,
+) {
+    rebinding.0 = None;
+    notice.0 = None;
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+//Runs before the button system, so the click which starts rebinding is not taken as a new binding.
+//Left mouse button is kept for the menu and can't be bound
+fn capture_rebind(
+    mut rebinding: ResMut<Rebinding>,
+    mut notice: ResMut<RebindNotice>,
+    mut bindings: ResMut<ControlBindings>,
+    keys: Res<Input<KeyCode>>,
+    mouse: Res<Input<MouseButton>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+) {
+    let Some(action) = rebinding.0 else {
+        return;
+    };
+
+    if keys.just_pressed(KeyCode::Escape) {
+        rebinding.0 = None;
+        return;
+    }
+
+    let binding = keys
+        .get_just_pressed()
+        .next()
+        .map(|key| Binding::Key(*key))
+        .or_else(|| {
+            mouse
+                .get_just_pressed()
+                .find(|button| **button != MouseButton::Left)
+                .map(|button| Binding::Mouse(*button))
+        })
+        .or_else(|| {
+            gamepad_buttons
+                .get_just_pressed()
+                .next()
+                .map(|button| Binding::Gamepad(button.button_type))
+        });
+
+    if let Some(binding) = binding {
+        info!("Bind {} to {}", action.name(), binding.name());
+        let unbound = bindings.rebind(action, binding);
+        notice.0 = (!unbound.is_empty()).then(|| {
+            let names = unbound.iter().map(ControlAction::name).collect::<Vec<_>>();
+            format!("{} is no longer bound to {}", binding.name(), names.join(", "))
+        });
+        bindings.save();
+        rebinding.0 = None;
+    }
+}
+
+fn controls_button_system(
+    mut rebinding: ResMut<Rebinding>,
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &RebindButton),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, mut color, button) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                rebinding.0 = Some(button.0);
+            }
+            Interaction::Hovered => {
+                *color = BackgroundColor(Color::DARK_GRAY);
+            }
+            Interaction::None => {
+                *color = BackgroundColor(Color::BLACK);
+            }
+        }
+    }
+}
+
+fn controls_text(
+    bindings: Res<ControlBindings>,
+    rebinding: Res<Rebinding>,
+    notice: Res<RebindNotice>,
+    buttons: Query<(&RebindButton, &Children)>,
+    mut title: Query<&mut Text, With<ControlsTitle>>,
+    mut texts: Query<&mut Text, Without<ControlsTitle>>,
+) {
+    if notice.is_changed() {
+        if let Ok(mut title) = title.get_single_mut() {
+            title.sections[0].value = match &notice.0 {
+                Some(notice) => format!("Controls (click to rebind)\n{}", notice),
+                None => "Controls (click to rebind)".to_string(),
+            };
+        }
+    }
+
+    for (button, children) in buttons.iter() {
+        let value = if rebinding.0 == Some(button.0) {
+            format!("{}: press a key (Esc to cancel)", button.0.name())
+        } else {
+            let names = bindings
+                .get(button.0)
+                .iter()
+                .map(Binding::name)
+                .collect::<Vec<_>>();
+            format!("{}: {}", button.0.name(), names.join(", "))
+        };
+
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = value.clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebind_takes_the_binding_from_other_actions() {
+        let mut bindings = ControlBindings::default();
+        let unbound = bindings.rebind(ControlAction::BigBark, Binding::Key(KeyCode::Space));
+
+        assert_eq!(unbound, vec![ControlAction::Bark]);
+        assert!(bindings.get(ControlAction::BigBark).contains(&Binding::Key(KeyCode::Space)));
+        assert!(!bindings.get(ControlAction::BigBark).contains(&Binding::Key(KeyCode::ControlLeft)));
+        assert!(!bindings.get(ControlAction::Bark).contains(&Binding::Key(KeyCode::Space)));
+    }
+
+    #[test]
+    fn mouse_binding_keeps_the_keyboard_one() {
+        let mut bindings = ControlBindings::default();
+        let unbound = bindings.rebind(ControlAction::Sprint, Binding::Mouse(MouseButton::Middle));
+
+        assert!(unbound.is_empty());
+        assert_eq!(
+            bindings.get(ControlAction::Sprint),
+            &[
+                Binding::Key(KeyCode::ShiftLeft),
+                Binding::Gamepad(GamepadButtonType::LeftTrigger2),
+                Binding::Mouse(MouseButton::Middle),
+            ]
+        );
+
+        //Mouse button replaces only the mouse binding of the action
+        bindings.rebind(ControlAction::Bark, Binding::Mouse(MouseButton::Middle));
+        assert_eq!(
+            bindings.get(ControlAction::Bark),
+            &[
+                Binding::Key(KeyCode::Space),
+                Binding::Gamepad(GamepadButtonType::South),
+                Binding::Mouse(MouseButton::Middle),
+            ]
+        );
+        assert!(!bindings.get(ControlAction::Sprint).contains(&Binding::Mouse(MouseButton::Middle)));
+    }
+}
@@ -1,15 +1,23 @@
 use bevy::prelude::*;
 use rand::Rng;
 
-use crate::{GameSet, GameStuff};
+use crate::{GameSet, GameStuff, SimSet, VisualsSet};
 
 pub struct CorpsePlugin;
 
 impl Plugin for CorpsePlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (corpse_system, spawn_corpse_system).in_set(GameSet::Playing))
-            .add_event::<SpawnCorpse>()
-            .add_systems(Startup, setup_corpse_storage);
+        app.add_systems(Update, (corpse_system, spawn_corpse_system).chain().in_set(GameSet::Playing).in_set(SimSet::Corpse))
+            .add_event::<SpawnCorpse>();
+    }
+}
+
+pub struct CorpseVisualsPlugin;
+
+impl Plugin for CorpseVisualsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_corpse_storage)
+            .add_systems(Update, attach_corpse_visuals.in_set(VisualsSet));
     }
 }
 
@@ -56,22 +64,20 @@ fn setup_corpse_storage(
 
 #[derive(Event)]
 pub struct SpawnCorpse {
-    pub position : Vec3
+    pub position : Vec3,
+    //Wolf which has eaten the sheep
+    pub killer : Entity,
 }
 
 fn spawn_corpse_system(
     mut commands : Commands,
     mut event : EventReader<SpawnCorpse>,
-    storage : Res<CorpseStoage>
 ) {
-    for event in event.iter() {
+    for event in event.read() {
         commands.spawn((
-            PbrBundle {
-                mesh: storage.mesh.clone(),
-                material: storage.material.clone(),
-                transform: Transform::from_translation(event.position + Vec3::Y * rand::thread_rng().gen_range(0.01..=0.02)).with_scale(Vec3::new(2.0, 2.0, 2.0)),
-                ..default()
-            },
+            SpatialBundle::from_transform(
+                Transform::from_translation(event.position).with_scale(Vec3::new(2.0, 2.0, 2.0)),
+            ),
             Corpse {
                 time: 20.0
             },
@@ -79,4 +85,16 @@ fn spawn_corpse_system(
         ));
     }
     event.clear();
-}
\ No newline at end of file
+}
+
+fn attach_corpse_visuals(
+    mut commands : Commands,
+    mut corpses : Query<(Entity, &mut Transform), Added<Corpse>>,
+    storage : Res<CorpseStoage>
+) {
+    for (entity, mut transform) in corpses.iter_mut() {
+        //small random lift against z-fighting between corpses
+        transform.translation.y += rand::thread_rng().gen_range(0.01..=0.02);
+        commands.entity(entity).insert((storage.mesh.clone(), storage.material.clone()));
+    }
+}
@@ -1,13 +1,20 @@
 use bevy::prelude::*;
 
 use crate::{
+    director::Director,
+    neighbours::NeighbourStats,
     safe_area::SheepCounter,
     sheep::{Sheep, StartSheepCount},
+    storyteller::LevelClock,
     GameSet, GameState, GameStuff,
 };
 
 const FONT_SIZE: f32 = 24.0;
 
+//Debug fast-forward: [ slows the level down, ] speeds it up, Backspace goes back to normal speed
+const MIN_TIME_SCALE: f32 = 0.125;
+const MAX_TIME_SCALE: f32 = 8.0;
+
 pub struct DiagnosticPlugin;
 
 impl Plugin for DiagnosticPlugin {
@@ -20,12 +27,24 @@ impl Plugin for DiagnosticPlugin {
                 setup_counter,
                 setup_sheep_counter,
                 setup_alive_sheep_counter,
+                setup_time_scale_text,
+                setup_neighbours_text,
+                setup_tension_text,
             )
                 .chain(),
         )
         .add_systems(
             Update,
-            (fps_counting, sheep_counter_text, alive_sheep_counter).in_set(GameSet::Playing),
+            (
+                fps_counting,
+                sheep_counter_text,
+                alive_sheep_counter,
+                change_time_scale,
+                time_scale_text,
+                neighbours_text,
+                tension_text,
+            )
+                .in_set(GameSet::Playing),
         );
     }
 }
@@ -140,3 +159,89 @@ pub fn alive_sheep_counter(
         );
     }
 }
+
+#[derive(Component)]
+pub struct TimeScaleText;
+
+pub fn setup_time_scale_text(mut commands: Commands, panels: Query<Entity, With<DiagnosticPanel>>) {
+    let mut text_style = TextStyle::default();
+    text_style.font_size = FONT_SIZE;
+    let time_scale = commands
+        .spawn(TextBundle::from_section("Speed: ", text_style))
+        .insert(TimeScaleText)
+        .id();
+
+    if let Ok(panel) = panels.get_single() {
+        commands.entity(panel).add_child(time_scale);
+    }
+}
+
+fn change_time_scale(input: Res<Input<KeyCode>>, mut clock: ResMut<LevelClock>) {
+    if input.just_pressed(KeyCode::BracketRight) {
+        clock.scale = (clock.scale * 2.0).min(MAX_TIME_SCALE);
+    }
+    if input.just_pressed(KeyCode::BracketLeft) {
+        clock.scale = (clock.scale / 2.0).max(MIN_TIME_SCALE);
+    }
+    if input.just_pressed(KeyCode::Back) {
+        clock.scale = 1.0;
+    }
+}
+
+pub fn time_scale_text(mut query: Query<&mut Text, With<TimeScaleText>>, clock: Res<LevelClock>) {
+    for mut text in &mut query {
+        text.sections[0].value = format!("Speed: x{}", clock.scale);
+    }
+}
+
+#[derive(Component)]
+pub struct NeighboursText;
+
+pub fn setup_neighbours_text(mut commands: Commands, panels: Query<Entity, With<DiagnosticPanel>>) {
+    let mut text_style = TextStyle::default();
+    text_style.font_size = FONT_SIZE;
+    let neighbours = commands
+        .spawn(TextBundle::from_section("Neighbours age: ", text_style))
+        .insert(NeighboursText)
+        .id();
+
+    if let Ok(panel) = panels.get_single() {
+        commands.entity(panel).add_child(neighbours);
+    }
+}
+
+//Age of sheep neighbour lists in frames: mean / max
+pub fn neighbours_text(mut query: Query<&mut Text, With<NeighboursText>>, stats: Res<NeighbourStats>) {
+    for mut text in &mut query {
+        text.sections[0].value = format!(
+            "Neighbours age: {:.1}/{}",
+            stats.mean_age, stats.max_age
+        );
+    }
+}
+
+#[derive(Component)]
+pub struct TensionText;
+
+pub fn setup_tension_text(mut commands: Commands, panels: Query<Entity, With<DiagnosticPanel>>) {
+    let mut text_style = TextStyle::default();
+    text_style.font_size = FONT_SIZE;
+    let tension = commands
+        .spawn(TextBundle::from_section("Tension: ", text_style))
+        .insert(TensionText)
+        .id();
+
+    if let Ok(panel) = panels.get_single() {
+        commands.entity(panel).add_child(tension);
+    }
+}
+
+//Director tension / current stress, and its phase
+pub fn tension_text(mut query: Query<&mut Text, With<TensionText>>, director: Res<Director>) {
+    for mut text in &mut query {
+        text.sections[0].value = format!(
+            "Tension: {:.2}/{:.2} {:?}",
+            director.tension, director.stress, director.phase
+        );
+    }
+}
@@ -0,0 +1,215 @@
+//Difficulty presets for the storyteller, wolves and global tasks
+//Sheep escape waves follow the hardness model from difficult_prepare/difficult_plot.py
+//Custom parameters are kept as "difficulty" in crate::storage, so they can be edited by hand
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{player::DOG_SPEED, storage};
+
+//Hardness model: dog has to catch escaped sheep before they run away
+//Distance which escaped sheep have to run to leave the pasture
+const ESCAPE_DISTANCE: f32 = 20.0;
+//Scared sheep speed, same as in the model
+const ESCAPE_SPEED: f32 = DOG_SPEED * 0.5 * 0.2;
+//Hardness sensitivity, k_h in the model
+const HARDNESS_K: f32 = 1.5;
+//Sheep in the smallest wave, c in the model
+const BASE_WAVE_COUNT: f32 = 10.0;
+//Time the dog has to react to a wave, delta_t in the model
+const REACTION_TIME: f32 = 10.0;
+//Lowest hardness of the model
+const MIN_HARDNESS: f32 = 0.05;
+const MAX_BEAMS: usize = 6;
+
+const CUSTOM_NAME: &str = "difficulty";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum DifficultyPreset {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    //Parameters set by hand, for example by balancing scripts
+    Custom,
+}
+
+impl DifficultyPreset {
+    pub const ALL: [DifficultyPreset; 4] = [
+        DifficultyPreset::Easy,
+        DifficultyPreset::Normal,
+        DifficultyPreset::Hard,
+        DifficultyPreset::Custom,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DifficultyPreset::Easy => "Easy",
+            DifficultyPreset::Normal => "Normal",
+            DifficultyPreset::Hard => "Hard",
+            DifficultyPreset::Custom => "Custom",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|preset| preset.name().eq_ignore_ascii_case(name))
+    }
+
+    /// Next preset for the menu switch
+    pub fn next(&self) -> Self {
+        match self {
+            DifficultyPreset::Easy => DifficultyPreset::Normal,
+            DifficultyPreset::Normal => DifficultyPreset::Hard,
+            DifficultyPreset::Hard => DifficultyPreset::Custom,
+            DifficultyPreset::Custom => DifficultyPreset::Easy,
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Difficulty {
+    pub preset: DifficultyPreset,
+    //Hardness at the end of an episode, in (0, 1]. Drives the number of beams in sheep escape waves
+    pub hardness: f32,
+    //Part of the flock which escapes in a wave at the end of an episode
+    pub wave_sheep_part: f32,
+    pub max_wolves: usize,
+    //Seconds to light the torches in the torch problem
+    pub torch_mission_time: f32,
+    //Level is failed when alive part of the flock drops below this
+    pub fail_threshold: f32,
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Self::preset(DifficultyPreset::Normal)
+    }
+}
+
+impl Difficulty {
+    /// Parameters of the preset. Custom starts from Normal
+    pub fn preset(preset: DifficultyPreset) -> Self {
+        match preset {
+            DifficultyPreset::Easy => Self {
+                preset,
+                hardness: 0.4,
+                wave_sheep_part: 0.1,
+                max_wolves: 10,
+                torch_mission_time: 60.0,
+                fail_threshold: 0.3,
+            },
+            DifficultyPreset::Normal | DifficultyPreset::Custom => Self {
+                preset,
+                hardness: 0.7,
+                wave_sheep_part: 0.2,
+                max_wolves: 20,
+                torch_mission_time: 40.0,
+                fail_threshold: 0.5,
+            },
+            DifficultyPreset::Hard => Self {
+                preset,
+                hardness: 1.0,
+                wave_sheep_part: 0.3,
+                max_wolves: 30,
+                torch_mission_time: 30.0,
+                fail_threshold: 0.6,
+            },
+        }
+    }
+
+    /// Parameters of the preset, Custom ones are read from the storage
+    pub fn load(preset: DifficultyPreset) -> Self {
+        match preset {
+            DifficultyPreset::Custom => Self::custom(),
+            _ => Self::preset(preset),
+        }
+    }
+
+    /// Custom parameters from the storage. Normal ones are written there on the first use,
+    /// so the player has a file to edit
+    pub fn custom() -> Self {
+        if let Some(text) = storage::read(CUSTOM_NAME) {
+            match ron::from_str::<Self>(&text) {
+                Ok(difficulty) => {
+                    return Self {
+                        preset: DifficultyPreset::Custom,
+                        ..difficulty
+                    }
+                }
+                Err(err) => warn!("Failed to read custom difficulty, using Normal: {}", err),
+            }
+        }
+
+        let difficulty = Self::preset(DifficultyPreset::Custom);
+        match ron::ser::to_string_pretty(&difficulty, ron::ser::PrettyConfig::default()) {
+            Ok(text) => {
+                if let Err(err) = storage::write(CUSTOM_NAME, &text) {
+                    warn!("Failed to write custom difficulty: {}", err);
+                }
+            }
+            Err(err) => warn!("Failed to serialize custom difficulty: {}", err),
+        }
+        difficulty
+    }
+
+    /// Hardness at the given point of an episode, grows from 30% to 100% of the preset one
+    pub fn episode_hardness(&self, episode_time: f32) -> f32 {
+        (self.hardness * (0.3 + 0.7 * episode_time.clamp(0.0, 1.0))).max(MIN_HARDNESS)
+    }
+
+    /// Hardness on the axis of the model. Beam count changes mostly below 0.3 there,
+    /// squared preset hardness puts Easy and Normal into this range
+    fn wave_hardness(&self, episode_time: f32) -> f32 {
+        self.episode_hardness(episode_time).powi(2).max(MIN_HARDNESS)
+    }
+
+    /// Number of escape directions for a wave. Sheep run away in `ESCAPE_DISTANCE / ESCAPE_SPEED`,
+    /// dog needs up to a full circle to reach a beam, the rest of the time (plus reaction time) is split between beams
+    pub fn wave_beams(&self, episode_time: f32) -> usize {
+        let param_curve = sheep_escape_time()
+            - dog_travel_time()
+            - 1.0 / HARDNESS_K / self.wave_hardness(episode_time);
+
+        let beams = (param_curve + REACTION_TIME) / BASE_WAVE_COUNT.sqrt();
+        (beams.round().max(1.0) as usize).min(MAX_BEAMS)
+    }
+
+    /// Number of sheep which escape in a wave
+    pub fn wave_count(&self, sheep_count: f32, episode_time: f32) -> usize {
+        (sheep_count * episode_time * self.wave_sheep_part + BASE_WAVE_COUNT).max(BASE_WAVE_COUNT)
+            as usize
+    }
+}
+
+fn sheep_escape_time() -> f32 {
+    ESCAPE_DISTANCE / ESCAPE_SPEED
+}
+
+fn dog_travel_time() -> f32 {
+    2.0 * std::f32::consts::PI * ESCAPE_DISTANCE / DOG_SPEED
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timings_match_the_model() {
+        //"Sheep time" and "Dog max travel time" printed by difficult_plot.py
+        assert!((sheep_escape_time() - 16.0).abs() < 1e-3);
+        assert!((dog_travel_time() - 10.053).abs() < 1e-3);
+    }
+
+    #[test]
+    fn wave_beams_per_preset() {
+        let beams = |preset| {
+            let difficulty = Difficulty::preset(preset);
+            [0.0, 0.25, 0.5, 0.75, 1.0].map(|t| difficulty.wave_beams(t))
+        };
+        assert_eq!(beams(DifficultyPreset::Easy), [1, 1, 2, 3, 4]);
+        assert_eq!(beams(DifficultyPreset::Normal), [1, 3, 4, 4, 5]);
+        assert_eq!(beams(DifficultyPreset::Hard), [3, 4, 5, 5, 5]);
+    }
+}
@@ -0,0 +1,423 @@
+//AI director: storyteller picks tasks by the player's stress instead of a fixed delay.
+//Stress is measured every frame from recent wolf kills, sheep outside of safe areas,
+//tired dog and the distance between the dog and the trouble. Tension is the smoothed stress.
+//Director goes through three phases:
+//  - build-up: new tasks are started one after another until the tension reaches the peak
+//  - peak: no new tasks, the player deals with what is already running
+//  - relax: no new tasks until the tension falls down, so the player can catch a breath
+//Tasks are picked by weight among registered tasks which are not on cooldown.
+//Tension curve is kept in Director::history for the debug panel and balancing scripts
+
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, utils::HashMap};
+use rand::Rng;
+
+use crate::{
+    corpse::SpawnCorpse,
+    game_rng::GameRng,
+    global_task::{
+        sheep_escape::ShawshankRedemption, ActiveTasks, GlobalTask, TaskInfo, TaskRegistry,
+    },
+    player::{Dog, Stamina},
+    safe_area::OutOfSafeArea,
+    sheep::Sheep,
+    storyteller::{LevelClock, Storyteller},
+    sunday::{DayState, EpisodeTime},
+    test_level::LevelSize,
+    wolf::UnderHunting,
+    GameState,
+};
+
+//Seconds between samples of the tension curve
+const SAMPLE_PERIOD: f32 = 1.0;
+//Whole level with some margin
+const MAX_SAMPLES: usize = 10 * 60;
+
+pub struct DirectorPlugin;
+
+impl Plugin for DirectorPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<DirectorParams>()
+            .register_type::<DirectorPhase>()
+            .init_resource::<DirectorParams>()
+            .init_resource::<Director>()
+            .add_systems(OnEnter(GameState::Playing), reset_director);
+    }
+}
+
+#[derive(Resource, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Resource, Default)]
+pub struct DirectorParams {
+    //Stress weights, they sum up to 1
+    pub kill_weight: f32,
+    pub outside_weight: f32,
+    pub stamina_weight: f32,
+    pub distance_weight: f32,
+    //Recent kills count this many seconds, then fade out
+    pub kill_memory: f32,
+    //Kills during kill_memory which give the full kill stress
+    pub max_kills: f32,
+    //Time for the tension to follow the stress
+    pub smoothing: f32,
+    //Build-up switches to peak above this tension
+    pub peak_tension: f32,
+    pub peak_time: f32,
+    //Relax lasts at least relax_time and until the tension falls below relax_tension
+    pub relax_tension: f32,
+    pub relax_time: f32,
+    //Delay before the first task of a build-up and between tasks of the same build-up
+    pub first_task_delay: f32,
+    pub next_task_delay: f32,
+    pub max_active_tasks: usize,
+}
+
+impl Default for DirectorParams {
+    fn default() -> Self {
+        Self {
+            kill_weight: 0.4,
+            outside_weight: 0.3,
+            stamina_weight: 0.1,
+            distance_weight: 0.2,
+            kill_memory: 20.0,
+            max_kills: 3.0,
+            smoothing: 3.0,
+            peak_tension: 0.6,
+            peak_time: 15.0,
+            relax_tension: 0.25,
+            relax_time: 20.0,
+            first_task_delay: 10.0,
+            next_task_delay: 30.0,
+            max_active_tasks: 2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect, Default)]
+pub enum DirectorPhase {
+    #[default]
+    BuildUp,
+    Peak,
+    Relax,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TensionSample {
+    //Level time
+    pub time: f32,
+    pub stress: f32,
+    pub tension: f32,
+    pub phase: DirectorPhase,
+}
+
+#[derive(Resource, Debug)]
+pub struct Director {
+    pub phase: DirectorPhase,
+    pub phase_time: f32,
+    pub stress: f32,
+    pub tension: f32,
+    //Kills with fading weight, see DirectorParams::kill_memory
+    pub recent_kills: f32,
+    pub since_task: f32,
+    //Level time when the task was started last time
+    pub last_started: HashMap<GlobalTask, f32>,
+    pub history: VecDeque<TensionSample>,
+    since_sample: f32,
+}
+
+impl Default for Director {
+    fn default() -> Self {
+        Self {
+            phase: DirectorPhase::BuildUp,
+            phase_time: 0.0,
+            stress: 0.0,
+            tension: 0.0,
+            recent_kills: 0.0,
+            since_task: 0.0,
+            last_started: HashMap::default(),
+            history: VecDeque::new(),
+            since_sample: 0.0,
+        }
+    }
+}
+
+impl Director {
+    /// Highest tension of the level so far
+    pub fn max_tension(&self) -> f32 {
+        self.history
+            .iter()
+            .map(|s| s.tension)
+            .fold(self.tension, f32::max)
+    }
+
+    fn set_phase(&mut self, phase: DirectorPhase) {
+        info!("Director: {:?} -> {:?}, tension {:.2}", self.phase, phase, self.tension);
+        self.phase = phase;
+        self.phase_time = 0.0;
+    }
+
+    /// Advance phase timers and switch the phase by the tension. True if new tasks may be started
+    fn advance_phase(&mut self, params: &DirectorParams, dt: f32) -> bool {
+        self.phase_time += dt;
+        self.since_task += dt;
+
+        match self.phase {
+            DirectorPhase::BuildUp => {
+                if self.tension >= params.peak_tension {
+                    self.set_phase(DirectorPhase::Peak);
+                    return false;
+                }
+                true
+            }
+            DirectorPhase::Peak => {
+                if self.phase_time >= params.peak_time {
+                    self.set_phase(DirectorPhase::Relax);
+                }
+                false
+            }
+            DirectorPhase::Relax => {
+                if self.phase_time >= params.relax_time && self.tension < params.relax_tension {
+                    self.set_phase(DirectorPhase::BuildUp);
+                    self.since_task = 0.0;
+                }
+                false
+            }
+        }
+    }
+
+    fn on_cooldown(&self, info: &TaskInfo, now: f32) -> bool {
+        self.last_started
+            .get(&info.task)
+            .is_some_and(|last| now - last < info.cooldown)
+    }
+
+    fn started(&mut self, task: GlobalTask, now: f32) {
+        self.last_started.insert(task, now);
+        self.since_task = 0.0;
+    }
+}
+
+fn reset_director(mut director: ResMut<Director>) {
+    *director = Director::default();
+}
+
+pub(crate) fn update_tension(
+    time: Res<Time>,
+    clock: Res<LevelClock>,
+    params: Res<DirectorParams>,
+    level_size: Res<LevelSize>,
+    mut director: ResMut<Director>,
+    mut kills: EventReader<SpawnCorpse>,
+    dog: Query<(&Transform, &Stamina), With<Dog>>,
+    sheep: Query<Option<&OutOfSafeArea>, With<Sheep>>,
+    trouble: Query<&Transform, (With<Sheep>, Or<(With<UnderHunting>, With<ShawshankRedemption>)>)>,
+) {
+    let dt = time.delta_seconds();
+
+    director.recent_kills *= (-dt / params.kill_memory).exp();
+    director.recent_kills += kills.read().count() as f32;
+    let kill_stress = (director.recent_kills / params.max_kills).min(1.0);
+
+    let total = sheep.iter().count();
+    let outside_stress = if total > 0 {
+        sheep.iter().filter(|out| out.is_some()).count() as f32 / total as f32
+    } else {
+        0.0
+    };
+
+    let (stamina_stress, distance_stress) = match dog.get_single() {
+        Ok((dog_transform, stamina)) => {
+            //Trouble far from the dog is worse than trouble under its nose
+            let distance = trouble
+                .iter()
+                .map(|t| t.translation.distance(dog_transform.translation))
+                .fold(None, |nearest: Option<f32>, d| Some(nearest.map_or(d, |n| n.min(d))));
+            (
+                1.0 - stamina.value.clamp(0.0, 1.0),
+                distance.map_or(0.0, |d| (d / level_size.0).min(1.0)),
+            )
+        }
+        Err(_) => (0.0, 0.0),
+    };
+
+    director.stress = (params.kill_weight * kill_stress
+        + params.outside_weight * outside_stress
+        + params.stamina_weight * stamina_stress
+        + params.distance_weight * distance_stress)
+        .clamp(0.0, 1.0);
+    let follow = (dt / params.smoothing).min(1.0);
+    director.tension += (director.stress - director.tension) * follow;
+
+    director.since_sample += dt;
+    if director.since_sample >= SAMPLE_PERIOD {
+        director.since_sample = 0.0;
+        let sample = TensionSample {
+            time: clock.elapsed,
+            stress: director.stress,
+            tension: director.tension,
+            phase: director.phase,
+        };
+        director.history.push_back(sample);
+        if director.history.len() > MAX_SAMPLES {
+            director.history.pop_front();
+        }
+    }
+}
+
+pub(crate) fn direct_tasks(
+    time: Res<Time>,
+    clock: Res<LevelClock>,
+    params: Res<DirectorParams>,
+    mut director: ResMut<Director>,
+    mut teller: ResMut<Storyteller>,
+    mut tasks: ResMut<ActiveTasks>,
+    registry: Res<TaskRegistry>,
+    day_state: Res<State<DayState>>,
+    episode_time: Res<EpisodeTime>,
+    mut rng: ResMut<GameRng>,
+    dog: Query<(), With<Dog>>,
+) {
+    if dog.is_empty() {
+        return;
+    }
+
+    //Safe area change is guaranteed once a day, whatever the tension is
+    let day = *day_state.get();
+    if day == DayState::Day && episode_time.0 > 0.5 && !teller.change_safe_area_was_spanwed {
+        teller.change_safe_area_was_spanwed = true;
+        tasks.start(GlobalTask::ChangeSafeArea);
+        director.started(GlobalTask::ChangeSafeArea, clock.elapsed);
+    }
+
+    if !director.advance_phase(&params, time.delta_seconds()) {
+        return;
+    }
+
+    //Evening warning does not count as a problem
+    let running = tasks
+        .tasks
+        .iter()
+        .filter(|t| t.task != GlobalTask::EveningWarning)
+        .count();
+    if running >= params.max_active_tasks {
+        return;
+    }
+    let delay = if running == 0 {
+        params.first_task_delay
+    } else {
+        params.next_task_delay
+    };
+    if director.since_task < delay {
+        return;
+    }
+
+    let candidates = registry
+        .available(day)
+        .filter(|info| !tasks.is_active(info.task) && info.weight > 0.0)
+        .filter(|info| !director.on_cooldown(info, clock.elapsed))
+        .map(|info| (info.task, info.weight))
+        .collect::<Vec<_>>();
+
+    if let Some(task) = pick_weighted(&candidates, &mut *rng) {
+        tasks.start(task);
+        director.started(task, clock.elapsed);
+    }
+}
+
+fn pick_weighted(candidates: &[(GlobalTask, f32)], rng: &mut GameRng) -> Option<GlobalTask> {
+    let total: f32 = candidates.iter().map(|(_, w)| w).sum();
+    if total <= 0.0 {
+        return None;
+    }
+    let mut roll = rng.gen_range(0.0..total);
+    for (task, weight) in candidates {
+        if roll < *weight {
+            return Some(*task);
+        }
+        roll -= weight;
+    }
+    candidates.last().map(|(task, _)| *task)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(task: GlobalTask, cooldown: f32) -> TaskInfo {
+        TaskInfo {
+            task,
+            days: &[DayState::Day],
+            weight: 1.0,
+            cooldown,
+        }
+    }
+
+    #[test]
+    fn pick_weighted_follows_weights() {
+        let mut rng = GameRng::new(7);
+        let candidates = [(GlobalTask::SheepEscape, 3.0), (GlobalTask::WolfAttack, 1.0)];
+        let escapes = (0..4000)
+            .filter(|_| pick_weighted(&candidates, &mut rng) == Some(GlobalTask::SheepEscape))
+            .count();
+        assert!((2800..3200).contains(&escapes), "{escapes}");
+    }
+
+    #[test]
+    fn pick_weighted_never_picks_zero_weight() {
+        let mut rng = GameRng::new(7);
+        let candidates = [(GlobalTask::SheepEscape, 0.0), (GlobalTask::WolfAttack, 1.0)];
+        for _ in 0..100 {
+            assert_eq!(pick_weighted(&candidates, &mut rng), Some(GlobalTask::WolfAttack));
+        }
+    }
+
+    #[test]
+    fn pick_weighted_empty() {
+        let mut rng = GameRng::new(7);
+        assert_eq!(pick_weighted(&[], &mut rng), None);
+        assert_eq!(pick_weighted(&[(GlobalTask::WolfAttack, 0.0)], &mut rng), None);
+    }
+
+    #[test]
+    fn cooldown_filters_recent_tasks() {
+        let mut director = Director::default();
+        let wolves = info(GlobalTask::WolfAttack, 30.0);
+        assert!(!director.on_cooldown(&wolves, 0.0));
+
+        director.started(GlobalTask::WolfAttack, 10.0);
+        assert!(director.on_cooldown(&wolves, 39.0));
+        assert!(!director.on_cooldown(&wolves, 40.0));
+        assert!(!director.on_cooldown(&info(GlobalTask::SheepEscape, 30.0), 11.0));
+    }
+
+    #[test]
+    fn phases_follow_tension() {
+        let params = DirectorParams::default();
+        let mut director = Director::default();
+
+        director.tension = 0.1;
+        assert!(director.advance_phase(&params, 1.0));
+        assert_eq!(director.phase, DirectorPhase::BuildUp);
+
+        director.tension = params.peak_tension;
+        assert!(!director.advance_phase(&params, 1.0));
+        assert_eq!(director.phase, DirectorPhase::Peak);
+
+        //Peak lasts peak_time even if the tension falls
+        director.tension = 0.0;
+        assert!(!director.advance_phase(&params, params.peak_time / 2.0));
+        assert_eq!(director.phase, DirectorPhase::Peak);
+        assert!(!director.advance_phase(&params, params.peak_time));
+        assert_eq!(director.phase, DirectorPhase::Relax);
+
+        //Relax waits for both relax_time and low tension
+        director.tension = params.relax_tension;
+        assert!(!director.advance_phase(&params, params.relax_time));
+        assert_eq!(director.phase, DirectorPhase::Relax);
+        director.tension = 0.0;
+        assert!(!director.advance_phase(&params, 1.0));
+        assert_eq!(director.phase, DirectorPhase::BuildUp);
+        assert_eq!(director.since_task, 0.0);
+        assert!(director.advance_phase(&params, 1.0));
+    }
+}
@@ -1,6 +1,7 @@
 use bevy::prelude::*;
 
 use crate::{
+    campaign::{Campaign, CampaignSet},
     storyteller::{FailReason, Score},
     GameSet, GameState,
 };
@@ -9,7 +10,10 @@ pub struct FinishScreenPlugin;
 
 impl Plugin for FinishScreenPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::Finish), setup_finish_screen);
+        app.add_systems(
+            OnEnter(GameState::Finish),
+            setup_finish_screen.after(CampaignSet),
+        );
 
         app.add_systems(OnExit(GameState::Finish), cleanup_finish_screen);
         app.add_systems(Update, finish_screen_system.in_set(GameSet::Finish));
@@ -19,7 +23,19 @@ impl Plugin for FinishScreenPlugin {
 #[derive(Component)]
 struct FinishScreen;
 
-fn setup_finish_screen(mut commands: Commands, score: Res<Score>, fail: Option<Res<FailReason>>) {
+#[derive(Component)]
+enum FinishButton {
+    //Next night on success, same night again on fail
+    Continue,
+    Menu,
+}
+
+fn setup_finish_screen(
+    mut commands: Commands,
+    score: Res<Score>,
+    fail: Option<Res<FailReason>>,
+    campaign: Res<Campaign>,
+) {
     let mut text_style = TextStyle::default();
     text_style.font_size = 24.0;
 
@@ -44,29 +60,59 @@ fn setup_finish_screen(mut commands: Commands, score: Res<Score>, fail: Option<R
             format!("Good dog! \nYou get to live another day. \nYou did well enough. Your master will be waiting for you tomorrow.")
         };
 
+        let campaign_text = match &campaign.last_result {
+            Some(result) if result.campaign_complete => format!(
+                "\nAll {} nights are over. Total score: {:.1}",
+                campaign.night_count(),
+                result.total_score
+            ),
+            Some(result) => format!(
+                "\nNight {}/{}. Sheep left: {}. Lambs born: {}. Total score: {:.1}",
+                result.night + 1,
+                campaign.night_count(),
+                result.flock,
+                result.born,
+                result.total_score
+            ),
+            None => String::new(),
+        };
+
         parent.spawn(TextBundle::from_section(
-            format!("{} \nScore: {:.1}", text, score.0), 
+            format!("{} \nScore: {:.1}{}", text, score.0, campaign_text), 
             TextStyle::default()
         ));
 
-        parent.spawn(ButtonBundle {
-            style: Style {
-                width: Val::Px(150.0),
-                height: Val::Px(65.0),
-                justify_content: JustifyContent::Center,
-                align_items: AlignItems::Center,
-                border: UiRect::all(Val::Px(5.0)),
-                ..default()
-            },
-            background_color: Color::rgb(0.15, 0.15, 0.15).into(),
-            border_color: Color::WHITE.into(),
+        let continue_text = match &campaign.last_result {
+            Some(result) if result.campaign_complete => None,
+            Some(result) if result.failed => Some("Retry"),
+            _ => Some("Next night"),
+        };
+
+        if let Some(continue_text) = continue_text {
+            spawn_button(parent, FinishButton::Continue, continue_text, &text_style);
+        }
+        spawn_button(parent, FinishButton::Menu, "Menu", &text_style);
+    });
+}
+
+fn spawn_button(parent: &mut ChildBuilder, button: FinishButton, text: &str, text_style: &TextStyle) {
+    parent.spawn((button, ButtonBundle {
+        style: Style {
+            width: Val::Px(150.0),
+            height: Val::Px(65.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            border: UiRect::all(Val::Px(5.0)),
             ..default()
-        }).with_children(|parent| {
-            parent.spawn(TextBundle::from_section(
-                "Ok",
-                text_style.clone()
-            ));
-        });
+        },
+        background_color: Color::rgb(0.15, 0.15, 0.15).into(),
+        border_color: Color::WHITE.into(),
+        ..default()
+    })).with_children(|parent| {
+        parent.spawn(TextBundle::from_section(
+            text,
+            text_style.clone()
+        ));
     });
 }
 
@@ -79,15 +125,16 @@ fn cleanup_finish_screen(mut commands: Commands, query: Query<Entity, With<Finis
 fn finish_screen_system(
     mut next_state: ResMut<NextState<GameState>>,
     mut interaction_query: Query<
-        (&Interaction, &mut BackgroundColor),
+        (&Interaction, &mut BackgroundColor, &FinishButton),
         (Changed<Interaction>, With<Button>),
     >,
 ) {
-    for (interaction, mut color) in interaction_query.iter_mut() {
+    for (interaction, mut color, button) in interaction_query.iter_mut() {
         match *interaction {
-            Interaction::Pressed => {
-                next_state.set(GameState::Menu);
-            }
+            Interaction::Pressed => match button {
+                FinishButton::Continue => next_state.set(GameState::Playing),
+                FinishButton::Menu => next_state.set(GameState::Menu),
+            },
             Interaction::Hovered => {
                 *color = Color::rgb(0.25, 0.25, 0.25).into();
             }
@@ -0,0 +1,279 @@
+//Boids model for the flock. Every sheep starts from the velocity of its behaviour (SheepTargetVel)
+//and adds steering from its nearest neighbours, the dog and the pasture border:
+//  - separation: push away from neighbours closer than separation_radius, stronger when closer
+//  - alignment: match the mean velocity of neighbours
+//  - cohesion: move to the mean position of neighbours
+//  - dog avoidance: push away from the dog inside dog_radius, stronger when closer
+//  - boundary: pull back to the pasture when the sheep is outside of it
+//  - mother: lambs catch up with their mother when they are too far from her
+//  - grass: hungry sheep drift to richer grass of the pasture
+//Weights are speeds in m/s which the rule gives at full strength. Sum is limited by the sheep max speed
+
+use bevy::prelude::*;
+
+#[derive(Resource, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Resource, Default)]
+pub struct FlockingParams {
+    pub separation_radius: f32,
+    pub separation_weight: f32,
+    pub alignment_weight: f32,
+    //Cohesion reaches full strength at this distance to the neighbours center
+    pub cohesion_radius: f32,
+    pub cohesion_weight: f32,
+    pub dog_radius: f32,
+    pub dog_weight: f32,
+    //Boundary reaches full strength this far outside the pasture
+    pub boundary_margin: f32,
+    pub boundary_weight: f32,
+    //Lamb starts to follow the mother further than mother_distance, full strength at twice of it
+    pub mother_distance: f32,
+    pub mother_weight: f32,
+    //Drift of a fully hungry sheep to richer grass
+    pub grass_weight: f32,
+}
+
+impl Default for FlockingParams {
+    fn default() -> Self {
+        Self {
+            separation_radius: 1.0,
+            separation_weight: 3.0,
+            alignment_weight: 0.3,
+            cohesion_radius: 3.0,
+            cohesion_weight: 0.3,
+            dog_radius: 3.0,
+            dog_weight: 3.0,
+            boundary_margin: 5.0,
+            boundary_weight: 2.0,
+            mother_distance: 1.5,
+            mother_weight: 2.0,
+            grass_weight: 0.5,
+        }
+    }
+}
+
+/// Sheep as seen by the flocking rules
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Boid {
+    pub position: Vec3,
+    pub velocity: Vec3,
+}
+
+/// What a sheep reacts to besides its neighbours
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FlockSurroundings {
+    pub dog: Option<Vec3>,
+    //Half size of the pasture around the origin. None for sheep which are allowed to leave it
+    pub bounds: Option<f32>,
+    //Mother position for lambs
+    pub mother: Option<Vec3>,
+    //Multiplier of mother_weight, newborn lambs stay closer to the mother
+    pub follow: f32,
+    //Direction to richer grass scaled by hunger
+    pub grass: Vec3,
+}
+
+impl FlockingParams {
+    /// Target velocity of a sheep which wants to move with `desired` velocity
+    pub fn steer(
+        &self,
+        boid: &Boid,
+        desired: Vec3,
+        neighbours: &[Boid],
+        surroundings: &FlockSurroundings,
+        max_speed: f32,
+    ) -> Vec3 {
+        let mut result = desired
+            + self.separation(boid, neighbours) * self.separation_weight
+            + self.alignment(boid, neighbours) * self.alignment_weight
+            + self.cohesion(boid, neighbours) * self.cohesion_weight
+            + surroundings.grass * self.grass_weight;
+
+        if let Some(dog) = surroundings.dog {
+            result += self.dog_avoidance(boid, dog) * self.dog_weight;
+        }
+        if let Some(bounds) = surroundings.bounds {
+            result += self.boundary(boid, bounds) * self.boundary_weight;
+        }
+        if let Some(mother) = surroundings.mother {
+            result += self.follow_mother(boid, mother) * self.mother_weight * surroundings.follow;
+        }
+
+        result.y = 0.0;
+        result.clamp_length_max(max_speed)
+    }
+
+    /// Sum of pushes from close neighbours, each is 1 at zero distance and 0 at separation_radius
+    pub fn separation(&self, boid: &Boid, neighbours: &[Boid]) -> Vec3 {
+        neighbours
+            .iter()
+            .map(|n| {
+                let dp = boid.position - n.position;
+                let length = dp.length();
+                if length < self.separation_radius {
+                    dp.normalize_or_zero() * (1.0 - length / self.separation_radius)
+                } else {
+                    Vec3::ZERO
+                }
+            })
+            .sum()
+    }
+
+    /// Difference between the mean neighbour velocity and own one, relative to the neighbours speed
+    pub fn alignment(&self, boid: &Boid, neighbours: &[Boid]) -> Vec3 {
+        if neighbours.is_empty() {
+            return Vec3::ZERO;
+        }
+        let mean = neighbours.iter().map(|n| n.velocity).sum::<Vec3>() / neighbours.len() as f32;
+        let speed = mean.length().max(boid.velocity.length());
+        if speed <= f32::EPSILON {
+            return Vec3::ZERO;
+        }
+        (mean - boid.velocity) / speed
+    }
+
+    /// Direction to the neighbours center, full length at cohesion_radius
+    pub fn cohesion(&self, boid: &Boid, neighbours: &[Boid]) -> Vec3 {
+        if neighbours.is_empty() {
+            return Vec3::ZERO;
+        }
+        let center = neighbours.iter().map(|n| n.position).sum::<Vec3>() / neighbours.len() as f32;
+        ((center - boid.position) / self.cohesion_radius).clamp_length_max(1.0)
+    }
+
+    /// Push from the dog, 1 at zero distance and 0 at dog_radius
+    pub fn dog_avoidance(&self, boid: &Boid, dog: Vec3) -> Vec3 {
+        let dp = boid.position - dog;
+        let length = dp.length();
+        if length < self.dog_radius {
+            dp.normalize_or_zero() * (1.0 - length / self.dog_radius)
+        } else {
+            Vec3::ZERO
+        }
+    }
+
+    /// Pull back inside the square [-bounds, bounds] on the ground plane
+    pub fn boundary(&self, boid: &Boid, bounds: f32) -> Vec3 {
+        let outside = |v: f32| {
+            let d = (v.abs() - bounds).max(0.0) / self.boundary_margin;
+            -v.signum() * d.min(1.0)
+        };
+        Vec3::new(outside(boid.position.x), 0.0, outside(boid.position.z))
+    }
+
+    /// Pull to the mother, 0 at mother_distance and 1 at twice of it
+    pub fn follow_mother(&self, boid: &Boid, mother: Vec3) -> Vec3 {
+        let dp = mother - boid.position;
+        let length = dp.length();
+        let strength = ((length - self.mother_distance) / self.mother_distance).clamp(0.0, 1.0);
+        dp.normalize_or_zero() * strength
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_SPEED: f32 = 100.0;
+
+    //Only one rule at full weight, so the steering shows what this rule does
+    fn only(rule: impl FnOnce(&mut FlockingParams)) -> FlockingParams {
+        let mut params = FlockingParams {
+            separation_weight: 0.0,
+            alignment_weight: 0.0,
+            cohesion_weight: 0.0,
+            dog_weight: 0.0,
+            boundary_weight: 0.0,
+            mother_weight: 0.0,
+            grass_weight: 0.0,
+            ..default()
+        };
+        rule(&mut params);
+        params
+    }
+
+    fn at(x: f32, z: f32) -> Boid {
+        Boid {
+            position: Vec3::new(x, 0.0, z),
+            velocity: Vec3::ZERO,
+        }
+    }
+
+    fn moving(x: f32, z: f32, velocity: Vec3) -> Boid {
+        Boid {
+            velocity,
+            ..at(x, z)
+        }
+    }
+
+    #[test]
+    fn separation_pushes_apart() {
+        let params = only(|p| p.separation_weight = 1.0);
+        let neighbours = [at(0.5, 0.0), at(0.0, 0.5)];
+        let steer = params.steer(&at(0.0, 0.0), Vec3::ZERO, &neighbours, &default(), MAX_SPEED);
+        assert!(steer.x < 0.0 && steer.z < 0.0, "{steer}");
+
+        //Neighbours outside of separation_radius do not push
+        let far = [at(2.0, 0.0)];
+        let steer = params.steer(&at(0.0, 0.0), Vec3::ZERO, &far, &default(), MAX_SPEED);
+        assert_eq!(steer, Vec3::ZERO);
+    }
+
+    #[test]
+    fn alignment_matches_heading() {
+        let params = only(|p| p.alignment_weight = 1.0);
+        let heading = Vec3::new(0.0, 0.0, 1.0);
+        let boid = moving(0.0, 0.0, Vec3::X);
+        let neighbours = [moving(1.0, 0.0, heading), moving(-1.0, 0.0, heading), moving(0.0, 1.0, heading)];
+        let steer = params.steer(&boid, boid.velocity, &neighbours, &default(), MAX_SPEED);
+        assert!(steer.normalize().dot(heading) > 0.99, "{steer}");
+    }
+
+    #[test]
+    fn cohesion_pulls_to_centroid() {
+        let params = only(|p| p.cohesion_weight = 1.0);
+        let neighbours = [at(2.0, 0.0), at(2.0, 2.0), at(2.0, -2.0), at(4.0, 0.0)];
+        let steer = params.steer(&at(0.0, 0.0), Vec3::ZERO, &neighbours, &default(), MAX_SPEED);
+        assert!(steer.normalize().dot(Vec3::X) > 0.99, "{steer}");
+    }
+
+    #[test]
+    fn dog_avoidance_points_away() {
+        let params = only(|p| p.dog_weight = 1.0);
+        let surroundings = FlockSurroundings {
+            dog: Some(Vec3::new(-1.0, 0.0, -1.0)),
+            ..default()
+        };
+        let steer = params.steer(&at(0.0, 0.0), Vec3::ZERO, &[], &surroundings, MAX_SPEED);
+        assert!(steer.normalize().dot(Vec3::new(1.0, 0.0, 1.0).normalize()) > 0.99, "{steer}");
+
+        //Dog further than dog_radius is ignored
+        let steer = params.steer(&at(10.0, 10.0), Vec3::ZERO, &[], &surroundings, MAX_SPEED);
+        assert_eq!(steer, Vec3::ZERO);
+    }
+
+    #[test]
+    fn boundary_pulls_back_inside() {
+        let params = only(|p| p.boundary_weight = 1.0);
+        let surroundings = FlockSurroundings {
+            bounds: Some(10.0),
+            ..default()
+        };
+        let steer = params.steer(&at(12.0, -13.0), Vec3::ZERO, &[], &surroundings, MAX_SPEED);
+        assert!(steer.x < 0.0 && steer.z > 0.0, "{steer}");
+
+        let steer = params.steer(&at(5.0, -5.0), Vec3::ZERO, &[], &surroundings, MAX_SPEED);
+        assert_eq!(steer, Vec3::ZERO);
+    }
+
+    #[test]
+    fn result_is_clamped_to_max_speed() {
+        let params = FlockingParams::default();
+        let surroundings = FlockSurroundings {
+            dog: Some(Vec3::new(0.1, 0.0, 0.0)),
+            ..default()
+        };
+        let neighbours = [at(-0.1, 0.0), at(-0.1, 0.1)];
+        let steer = params.steer(&at(0.0, 0.0), Vec3::new(0.0, 0.0, 5.0), &neighbours, &surroundings, 2.0);
+        assert!((steer.length() - 2.0).abs() < 1e-4, "{steer}");
+    }
+}
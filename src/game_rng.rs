@@ -0,0 +1,92 @@
+//Single source of randomness for the simulation. Same seed + same input = same level
+
+use bevy::prelude::*;
+use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
+
+use crate::GameState;
+
+pub const SEED_ENV: &str = "SHEEP_SEED";
+
+pub struct GameRngPlugin;
+
+impl Plugin for GameRngPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<GameRng>() {
+            app.insert_resource(GameRng::new(seed_from_env().unwrap_or_else(random_seed)));
+        }
+
+        app.add_systems(OnEnter(GameState::Playing), reset_rng.in_set(RngResetSet));
+    }
+}
+
+//Systems which spawn level stuff with GameRng must run after this set
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct RngResetSet;
+
+#[derive(Resource)]
+pub struct GameRng {
+    seed: u64,
+    rng: StdRng,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Restart random sequence from the seed
+    pub fn reset(&mut self) {
+        self.rng = StdRng::seed_from_u64(self.seed);
+    }
+
+    /// Separate generator derived from the seed. For cosmetic randomness, which must not shift the simulation sequence
+    pub fn fork(&self, salt: u64) -> StdRng {
+        StdRng::seed_from_u64(self.seed ^ salt)
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+/// Reads seed from `--seed <u64>` command line argument or SHEEP_SEED env variable
+pub fn seed_from_env() -> Option<u64> {
+    let mut args = std::env::args();
+    while let Some(arg) = args.next() {
+        if arg == "--seed" {
+            return args.next().and_then(|s| s.parse().ok());
+        }
+    }
+
+    std::env::var(SEED_ENV).ok().and_then(|s| s.parse().ok())
+}
+
+fn random_seed() -> u64 {
+    rand::thread_rng().gen()
+}
+
+fn reset_rng(mut rng: ResMut<GameRng>) {
+    info!("Level seed: {}", rng.seed());
+    rng.reset();
+}
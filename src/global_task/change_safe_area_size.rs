@@ -1,15 +1,23 @@
-use bevy::prelude::*;
+use bevy::{ecs::schedule::SystemConfigs, prelude::*};
 use rand::Rng;
 
-use crate::{storyteller::{Storyteller, GlobalTask}, safe_area::{SafeArea, LandSafeArea}, test_level::LevelSize, GameSet, level_ui::TaskText, GameStuff};
+use crate::{storyteller::Storyteller, safe_area::{SafeArea, LandSafeArea}, sunday::DayState, test_level::LevelSize, GameStuff, game_rng::GameRng};
 
-pub struct ChangeSafeAreaSizePlugin;
+use super::{ActiveTasks, GlobalTask, GlobalTaskDef};
 
-impl Plugin for ChangeSafeAreaSizePlugin {
-    fn build(&self, app: &mut App) {
-        app.add_systems(
-            OnEnter(GlobalTask::ChangeSafeArea), (start_change_safe_area, apply_deferred).chain())
-            .add_systems(Update, change_area_system.run_if(in_state(GlobalTask::ChangeSafeArea)).in_set(GameSet::Playing));
+pub struct ChangeSafeAreaTask;
+
+//Started by the storyteller once per level, never picked at random
+impl GlobalTaskDef for ChangeSafeAreaTask {
+    const TASK: GlobalTask = GlobalTask::ChangeSafeArea;
+    const DAYS: &'static [DayState] = &[];
+
+    fn start() -> SystemConfigs {
+        start_change_safe_area.into_configs()
+    }
+
+    fn update() -> SystemConfigs {
+        change_area_system.into_configs()
     }
 }
 
@@ -33,9 +41,9 @@ fn start_change_safe_area(
     mut commands: Commands,
     mut teller : ResMut<Storyteller>,
     mut areas: Query<(Entity, &mut SafeArea, &LandSafeArea)>,
-    leve_size : Res<LevelSize>
+    leve_size : Res<LevelSize>,
+    mut rng : ResMut<GameRng>
 ) {
-    let mut rng = rand::thread_rng();
     let pos = rng.gen_range(8..=20) as f32;
 
     for (entity, mut area, land) in areas.iter_mut() {
@@ -90,21 +98,15 @@ fn change_area_system(
     mut commands: Commands,
     mut change: Query<(Entity, &mut ChangeSafeArea)>,
     time : Res<Time>,
-    mut global_task : ResMut<NextState<GlobalTask>>,
-    mut text : Query<&mut Text, With<TaskText>>
+    mut tasks : ResMut<ActiveTasks>
 ) {
 
     if change.is_empty() {
-        global_task.set(GlobalTask::None);
-        for mut t in text.iter_mut() {
-            t.sections[0].value = "".to_string();
-        }
+        tasks.succeed(GlobalTask::ChangeSafeArea);
         return;
     }
 
-    for mut t in text.iter_mut() {
-        t.sections[0].value = "The wind has changed. Sheep safe zones are changing!".to_string();
-    }
+    tasks.set_objective(GlobalTask::ChangeSafeArea, "The wind has changed. Sheep safe zones are changing!");
 
     for (entity, mut change) in change.iter_mut() {
         change.time += time.delta_seconds();
@@ -1 +1,127 @@
+//Herding: a green pen appears on the pasture and the dog has to drive a part of the flock into it
+//before the deadline. Pen is a SafeArea marked with TargetPen, sheep inside it are counted
+//by safe_area::count_sheeps. Pen is not a shelter: sheep don't walk into it by themselves and wolves
+//hunt there as outside. Completed task gives a score bonus, missed deadline fails the night
 
+use std::f32::consts::PI;
+
+use bevy::{ecs::schedule::SystemConfigs, prelude::*};
+use rand::Rng;
+
+use crate::{
+    difficulty::Difficulty,
+    safe_area::{SafeArea, SheepCounter, TargetPen},
+    sheep::Sheep,
+    storyteller::ScoreBonus,
+    sunday::{DayState, EpisodeTime},
+    test_level::LevelSize,
+    GameStuff, game_rng::GameRng,
+};
+
+use super::{ActiveTasks, GlobalTask, GlobalTaskDef};
+
+const TIME_LIMIT: f32 = 90.0;
+//Part of the flock which has to be in the pen, grows with hardness
+const MIN_PEN_PART: f32 = 0.15;
+const MAX_PEN_PART: f32 = 0.35;
+//Ground taken by one sheep in a tight flock, square meters
+const SHEEP_AREA: f32 = 1.5;
+const MIN_PEN_RADIUS: f32 = 3.0;
+//Pen center is placed this far from the pasture center, in level sizes
+const PEN_DISTANCE: std::ops::Range<f32> = 0.3..0.6;
+//Bonus for a completed task, seconds left are added on top
+const COLLECT_SCORE: f32 = 30.0;
+
+pub struct CollectSheepInAreaTask;
+
+impl GlobalTaskDef for CollectSheepInAreaTask {
+    const TASK: GlobalTask = GlobalTask::CollectSheepInArea;
+    const DAYS: &'static [DayState] = &[DayState::Day];
+    const COOLDOWN: f32 = 60.0;
+
+    fn start() -> SystemConfigs {
+        spawn_pen.into_configs()
+    }
+
+    fn update() -> SystemConfigs {
+        collect_system.into_configs()
+    }
+
+    fn finish() -> Option<SystemConfigs> {
+        Some(remove_pen.into_configs())
+    }
+}
+
+#[derive(Resource)]
+pub struct CollectStatus {
+    pub needed: u32,
+}
+
+fn spawn_pen(
+    mut commands: Commands,
+    mut rng: ResMut<GameRng>,
+    level_size: Res<LevelSize>,
+    difficulty: Res<Difficulty>,
+    episode_time: Res<EpisodeTime>,
+    sheep: Query<(), With<Sheep>>,
+    mut tasks: ResMut<ActiveTasks>,
+) {
+    let hardness = difficulty.episode_hardness(episode_time.0).min(1.0);
+    let part = MIN_PEN_PART + (MAX_PEN_PART - MIN_PEN_PART) * hardness;
+    let needed = ((sheep.iter().count() as f32 * part).ceil() as u32).max(1);
+
+    //Pen fits the needed sheep with some free space
+    let radius = (needed as f32 * SHEEP_AREA * 1.5 / PI).sqrt().max(MIN_PEN_RADIUS);
+    let angle = rng.gen_range(0.0..PI * 2.0);
+    let distance = rng.gen_range(PEN_DISTANCE) * level_size.0;
+
+    commands.spawn((
+        SafeArea::Circle {
+            pos: Vec2::new(angle.cos(), angle.sin()) * distance,
+            radius,
+        },
+        TargetPen,
+        GameStuff,
+    ));
+    commands.insert_resource(CollectStatus { needed });
+    tasks.set_deadline(
+        GlobalTask::CollectSheepInArea,
+        TIME_LIMIT,
+        "The shepherd waited at the pen, but the sheep never came. Lazy mutt.",
+    );
+
+    info!("Collect {} sheep in a pen with radius {:.1}", needed, radius);
+}
+
+fn remove_pen(mut commands: Commands, pens: Query<Entity, With<TargetPen>>) {
+    for pen in pens.iter() {
+        commands.entity(pen).despawn_recursive();
+    }
+}
+
+fn collect_system(
+    counter: Res<SheepCounter>,
+    status: Res<CollectStatus>,
+    mut bonus: ResMut<ScoreBonus>,
+    mut tasks: ResMut<ActiveTasks>,
+) {
+    let time_left = tasks
+        .get(GlobalTask::CollectSheepInArea)
+        .and_then(|t| t.deadline)
+        .unwrap_or(0.0);
+
+    if counter.in_pen >= status.needed {
+        bonus.0 += COLLECT_SCORE + time_left;
+        info!("Pen is full with {:.0} seconds left", time_left);
+        tasks.succeed(GlobalTask::CollectSheepInArea);
+        return;
+    }
+
+    tasks.set_objective(
+        GlobalTask::CollectSheepInArea,
+        format!(
+            "Herd the sheep into the green pen! {}/{} sheep",
+            counter.in_pen, status.needed
+        ),
+    );
+}
@@ -1,29 +1,42 @@
-pub use bevy::prelude::*;
+use bevy::{ecs::schedule::SystemConfigs, prelude::*};
 
-use crate::{level_ui::TaskText, sunday::DayState, storyteller::GlobalTask, GameSet};
+use crate::{sunday::DayState, GameSet};
 
-pub struct EveningWarningPlugin;
+use super::{ActiveTasks, GlobalTask, GlobalTaskDef};
 
-impl Plugin for EveningWarningPlugin {
-    fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update, write_warning_message.run_if(in_state(GlobalTask::None)).run_if(in_state(DayState::Evening)).in_set(GameSet::Playing)
-        ).add_systems(OnExit(DayState::Evening), clear_message.in_set(GameSet::Playing));
+//Not a real problem, just a reminder which lives through the evening next to other tasks
+pub struct EveningWarningTask;
+
+impl GlobalTaskDef for EveningWarningTask {
+    const TASK: GlobalTask = GlobalTask::EveningWarning;
+    const DAYS: &'static [DayState] = &[];
+
+    fn build(app: &mut App) {
+        app.add_systems(OnEnter(DayState::Evening), start_warning.in_set(GameSet::Playing));
+    }
+
+    fn start() -> SystemConfigs {
+        write_warning_message.into_configs()
     }
-}
 
-fn write_warning_message(
-    mut texts : Query<&mut Text, With<TaskText>>
-) {
-    for mut text in texts.iter_mut() {
-        text.sections[0].value = "The night is coming! Safe zones are disappearing!\nGather the sheep near the torches!".to_string();
+    fn update() -> SystemConfigs {
+        wait_for_night.into_configs()
     }
 }
 
-fn clear_message(
-    mut texts : Query<&mut Text, With<TaskText>>
-) {
-    for mut text in texts.iter_mut() {
-        text.sections[0].value = "".to_string();
+fn start_warning(mut tasks: ResMut<ActiveTasks>) {
+    tasks.start(GlobalTask::EveningWarning);
+}
+
+fn write_warning_message(mut tasks: ResMut<ActiveTasks>) {
+    tasks.set_objective(
+        GlobalTask::EveningWarning,
+        "The night is coming! Safe zones are disappearing!\nGather the sheep near the torches!",
+    );
+}
+
+fn wait_for_night(mut tasks: ResMut<ActiveTasks>, day_state: Res<State<DayState>>) {
+    if *day_state.get() != DayState::Evening {
+        tasks.succeed(GlobalTask::EveningWarning);
     }
-}
\ No newline at end of file
+}
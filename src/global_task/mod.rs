@@ -1,3 +1,11 @@
+//Global tasks are problems the storyteller throws at the player: escaping sheep, torches going out,
+//wolf attacks and so on. Every task implements GlobalTaskDef and is registered with add_global_task.
+//Several tasks can run at once, each of them lives in ActiveTasks with its own objective and deadline:
+//  - start: runs once in the frame the task is started, can cancel the task if it makes no sense now
+//  - update: runs every frame while the task is running, reports success or fail to ActiveTasks
+//  - finish: runs once after the outcome, removes what the task has spawned
+//Outcomes are handled in one place (resolve_tasks): failed task ends the level with its reason
+
 pub mod collect_sheep_in_area;
 pub mod sheep_escape;
 pub mod torch_blinking;
@@ -5,17 +13,303 @@ pub mod wolf_attack;
 pub mod change_safe_area_size;
 pub mod evening_warning;
 
-use bevy::prelude::*;
+use bevy::{ecs::schedule::SystemConfigs, prelude::*};
+
+use crate::{
+    storyteller::FailReason,
+    sunday::DayState,
+    GameSet, GameState, SimSet,
+};
 
 pub struct GlobalTaskPlugin;
 
 impl Plugin for GlobalTaskPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins((
-            sheep_escape::SheepEscapePlugin,
-            torch_blinking::TorchBlinkingPlugin,
-            change_safe_area_size::ChangeSafeAreaSizePlugin,
-            evening_warning::EveningWarningPlugin,
-        ));
+        app.init_resource::<ActiveTasks>()
+            .init_resource::<TaskRegistry>()
+            .configure_sets(
+                Update,
+                (
+                    TaskSet::Start,
+                    TaskSet::Update,
+                    TaskSet::Resolve,
+                    TaskSet::Finish,
+                    TaskSet::Cleanup,
+                )
+                    .chain()
+                    .in_set(GameSet::Playing)
+                    .in_set(SimSet::GlobalTask),
+            )
+            .add_systems(OnEnter(GameState::Playing), reset_tasks)
+            .add_systems(
+                Update,
+                (
+                    (apply_deferred, resolve_tasks).chain().in_set(TaskSet::Resolve),
+                    (apply_deferred, cleanup_tasks).chain().in_set(TaskSet::Cleanup),
+                ),
+            );
+
+        app.add_global_task::<sheep_escape::SheepEscapeTask>()
+            .add_global_task::<torch_blinking::TorchProblemTask>()
+            .add_global_task::<wolf_attack::WolfAttackTask>()
+            .add_global_task::<collect_sheep_in_area::CollectSheepInAreaTask>()
+            .add_global_task::<change_safe_area_size::ChangeSafeAreaTask>()
+            .add_global_task::<evening_warning::EveningWarningTask>();
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum GlobalTask {
+    SheepEscape,
+    WolfAttack,
+    CollectSheepInArea,
+    TorchProblem,
+    ChangeSafeArea,
+    EveningWarning,
+}
+
+/// Order of task hooks inside SimSet::GlobalTask
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub enum TaskSet {
+    Start,
+    Update,
+    Resolve,
+    Finish,
+    Cleanup,
+}
+
+/// Global task definition. Hooks are systems which run only for this task, see add_global_task
+pub trait GlobalTaskDef: Send + Sync + 'static {
+    const TASK: GlobalTask;
+    /// Day parts when the storyteller may pick the task. Empty for tasks started by other means
+    const DAYS: &'static [DayState];
+    /// Chance to be picked relative to other available tasks, see director::direct_tasks
+    const WEIGHT: f32 = 1.0;
+    /// Seconds after the start before the storyteller picks the task again
+    const COOLDOWN: f32 = 30.0;
+
+    /// Resources and systems which are not hooks
+    fn build(_app: &mut App) {}
+    fn start() -> SystemConfigs;
+    fn update() -> SystemConfigs;
+    fn finish() -> Option<SystemConfigs> {
+        None
+    }
+}
+
+pub trait AddGlobalTask {
+    fn add_global_task<T: GlobalTaskDef>(&mut self) -> &mut Self;
+}
+
+impl AddGlobalTask for App {
+    fn add_global_task<T: GlobalTaskDef>(&mut self) -> &mut Self {
+        T::build(self);
+        self.world
+            .resource_mut::<TaskRegistry>()
+            .tasks
+            .push(TaskInfo {
+                task: T::TASK,
+                days: T::DAYS,
+                weight: T::WEIGHT,
+                cooldown: T::COOLDOWN,
+            });
+
+        self.add_systems(
+            Update,
+            (
+                T::start()
+                    .run_if(task_in_phase(T::TASK, TaskPhase::Starting))
+                    .in_set(TaskSet::Start),
+                T::update()
+                    .run_if(task_in_phase(T::TASK, TaskPhase::Running))
+                    .in_set(TaskSet::Update),
+            ),
+        );
+        if let Some(finish) = T::finish() {
+            self.add_systems(
+                Update,
+                finish
+                    .run_if(task_in_phase(T::TASK, TaskPhase::Finished))
+                    .in_set(TaskSet::Finish),
+            );
+        }
+        self
+    }
+}
+
+pub struct TaskInfo {
+    pub task: GlobalTask,
+    pub days: &'static [DayState],
+    pub weight: f32,
+    pub cooldown: f32,
+}
+
+/// All registered tasks in the registration order
+#[derive(Resource, Default)]
+pub struct TaskRegistry {
+    pub tasks: Vec<TaskInfo>,
+}
+
+impl TaskRegistry {
+    pub fn available(&self, day: DayState) -> impl Iterator<Item = &TaskInfo> {
+        self.tasks.iter().filter(move |info| info.days.contains(&day))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaskPhase {
+    Starting,
+    Running,
+    Finished,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TaskOutcome {
+    Success,
+    Failed(String),
+    //Task could not start or was stopped without a result
+    Cancelled,
+}
+
+#[derive(Debug)]
+pub struct ActiveTask {
+    pub task: GlobalTask,
+    pub phase: TaskPhase,
+    pub outcome: Option<TaskOutcome>,
+    /// Line shown to the player, see level_ui::show_tasks
+    pub objective: String,
+    /// Seconds left, task fails with deadline_fail when it runs out
+    pub deadline: Option<f32>,
+    pub deadline_fail: String,
+}
+
+/// Tasks which are running now, in the start order
+#[derive(Resource, Default, Debug)]
+pub struct ActiveTasks {
+    pub tasks: Vec<ActiveTask>,
+}
+
+impl ActiveTasks {
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    pub fn is_active(&self, task: GlobalTask) -> bool {
+        self.get(task).is_some()
+    }
+
+    pub fn get(&self, task: GlobalTask) -> Option<&ActiveTask> {
+        self.tasks.iter().find(|t| t.task == task)
+    }
+
+    pub fn get_mut(&mut self, task: GlobalTask) -> Option<&mut ActiveTask> {
+        self.tasks.iter_mut().find(|t| t.task == task)
+    }
+
+    pub fn phase(&self, task: GlobalTask) -> Option<TaskPhase> {
+        self.get(task).map(|t| t.phase)
+    }
+
+    /// Returns false if the task is already running, the same task never runs twice
+    pub fn start(&mut self, task: GlobalTask) -> bool {
+        if self.is_active(task) {
+            return false;
+        }
+        info!("Start task {:?}", task);
+        self.tasks.push(ActiveTask {
+            task,
+            phase: TaskPhase::Starting,
+            outcome: None,
+            objective: String::new(),
+            deadline: None,
+            deadline_fail: String::new(),
+        });
+        true
+    }
+
+    pub fn set_objective(&mut self, task: GlobalTask, objective: impl Into<String>) {
+        if let Some(t) = self.get_mut(task) {
+            t.objective = objective.into();
+        }
+    }
+
+    pub fn set_deadline(&mut self, task: GlobalTask, time: f32, fail: impl Into<String>) {
+        if let Some(t) = self.get_mut(task) {
+            t.deadline = Some(time);
+            t.deadline_fail = fail.into();
+        }
+    }
+
+    /// First outcome wins, later ones in the same frame are ignored
+    pub fn finish(&mut self, task: GlobalTask, outcome: TaskOutcome) {
+        if let Some(t) = self.get_mut(task) {
+            if t.phase != TaskPhase::Finished {
+                t.phase = TaskPhase::Finished;
+                t.outcome = Some(outcome);
+            }
+        }
+    }
+
+    pub fn succeed(&mut self, task: GlobalTask) {
+        self.finish(task, TaskOutcome::Success);
+    }
+
+    pub fn fail(&mut self, task: GlobalTask, reason: impl Into<String>) {
+        self.finish(task, TaskOutcome::Failed(reason.into()));
+    }
+
+    pub fn cancel(&mut self, task: GlobalTask) {
+        self.finish(task, TaskOutcome::Cancelled);
+    }
+}
+
+pub fn task_in_phase(
+    task: GlobalTask,
+    phase: TaskPhase,
+) -> impl FnMut(Res<ActiveTasks>) -> bool + Clone {
+    move |tasks: Res<ActiveTasks>| tasks.phase(task) == Some(phase)
+}
+
+fn reset_tasks(mut tasks: ResMut<ActiveTasks>) {
+    tasks.tasks.clear();
+}
+
+fn resolve_tasks(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut tasks: ResMut<ActiveTasks>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    for task in tasks.tasks.iter_mut() {
+        if task.phase != TaskPhase::Running {
+            continue;
+        }
+        if let Some(deadline) = task.deadline.as_mut() {
+            *deadline -= time.delta_seconds();
+            if *deadline <= 0.0 {
+                task.phase = TaskPhase::Finished;
+                task.outcome = Some(TaskOutcome::Failed(task.deadline_fail.clone()));
+            }
+        }
+    }
+
+    for task in tasks.tasks.iter().filter(|t| t.phase == TaskPhase::Finished) {
+        info!("Task {:?} finished: {:?}", task.task, task.outcome);
+        if let Some(TaskOutcome::Failed(reason)) = &task.outcome {
+            commands.insert_resource(FailReason::TaskFailed(reason.clone()));
+            game_state.set(GameState::Finish);
+        }
+    }
+}
+
+//Finish hooks have already run, started tasks get their update from the next frame
+fn cleanup_tasks(mut tasks: ResMut<ActiveTasks>) {
+    tasks.tasks.retain(|t| t.phase != TaskPhase::Finished);
+    for task in tasks.tasks.iter_mut() {
+        task.phase = TaskPhase::Running;
     }
 }
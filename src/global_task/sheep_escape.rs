@@ -1,31 +1,37 @@
-use bevy::prelude::*;
+use bevy::{ecs::schedule::SystemConfigs, prelude::*};
 use rand::Rng;
 
 use crate::{
-    level_ui::TaskText,
+    difficulty::Difficulty,
     player::Dog,
-    sheep::{Decision, GoTo, IdleFeeding, IsScared, Sheep},
-    storyteller::{FailReason, GlobalTask, Storyteller},
+    sheep::{EscapeTarget, GoTo, IsScared, Sheep, ESCAPE},
+    sheep_behaviour::SheepTransition,
+    storyteller::{LevelClock, Storyteller},
     sunday::{DayState, EpisodeTime},
     test_level::LevelSize,
-    GameSet, GameState,
+    game_rng::GameRng,
 };
 
-pub struct SheepEscapePlugin;
-
-impl Plugin for SheepEscapePlugin {
-    fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GlobalTask::SheepEscape), generate_new_wave)
-            .add_systems(
-                Update,
-                (apply_deferred, check_wave_finish, wave_executor)
-                    .chain()
-                    .run_if(in_state(GlobalTask::SheepEscape))
-                    .in_set(GameSet::Playing),
-            )
-            .init_resource::<NextWave>()
+use super::{ActiveTasks, GlobalTask, GlobalTaskDef};
+
+pub struct SheepEscapeTask;
+
+impl GlobalTaskDef for SheepEscapeTask {
+    const TASK: GlobalTask = GlobalTask::SheepEscape;
+    const DAYS: &'static [DayState] = &[DayState::Day, DayState::Night];
+
+    fn build(app: &mut App) {
+        app.init_resource::<NextWave>()
             .init_resource::<SheepWaveStatus>();
     }
+
+    fn start() -> SystemConfigs {
+        generate_new_wave.into_configs()
+    }
+
+    fn update() -> SystemConfigs {
+        (check_wave_finish, wave_executor).chain()
+    }
 }
 
 #[derive(Resource, Default)]
@@ -38,6 +44,7 @@ pub struct ShawshankRedemption;
 pub struct SheepWave {
     pub count: usize,
     pub beams: usize,
+    //Level time when the wave starts
     pub time: f32,
 }
 
@@ -48,14 +55,11 @@ pub struct SheepWaveStatus {
 }
 
 fn check_wave_finish(
-    mut commands: Commands,
     escapers: Query<Entity, (With<ShawshankRedemption>, With<Sheep>)>,
     sheep: Query<&Sheep>,
-    mut global_task: ResMut<NextState<GlobalTask>>,
+    mut tasks: ResMut<ActiveTasks>,
     mut sheep_wave_status: ResMut<SheepWaveStatus>,
-    mut game_state: ResMut<NextState<GameState>>,
     next_wave: Res<NextWave>,
-    mut info_texts: Query<&mut Text, With<TaskText>>,
 ) {
     let loose_limit = (sheep_wave_status.start_count / 2).max(10);
     if escapers.is_empty() && next_wave.0.is_none() && sheep_wave_status.start_count != 0 {
@@ -68,73 +72,67 @@ fn check_wave_finish(
         info!("WAVE FINISHED");
 
         if sheep_wave_status.start_count - alived_sheep > loose_limit {
-            commands.insert_resource(FailReason::TaskFailed(
-                "Half the runway flock has been eaten. That's giving bad dog, don't you think?".to_string(),
-            ));
-            game_state.set(GameState::Finish);
-            global_task.set(GlobalTask::None);
+            tasks.fail(
+                GlobalTask::SheepEscape,
+                "Half the runway flock has been eaten. That's giving bad dog, don't you think?",
+            );
         } else {
-            global_task.set(GlobalTask::None);
+            tasks.succeed(GlobalTask::SheepEscape);
         }
 
         sheep_wave_status.start_count = 0;
         sheep_wave_status.sheep.clear();
-
-        for mut t in info_texts.iter_mut() {
-            t.sections[0].value = "".to_string();
-        }
     } else if next_wave.0.is_some() {
-        for mut t in info_texts.iter_mut() {
-            t.sections[0].value = "Your flock is getting restless, wait for it".to_string();
-        }
+        tasks.set_objective(
+            GlobalTask::SheepEscape,
+            "Your flock is getting restless, wait for it",
+        );
     } else if sheep_wave_status.start_count != 0 {
-        for mut t in info_texts.iter_mut() {
-            t.sections[0].value = format!(
+        tasks.set_objective(
+            GlobalTask::SheepEscape,
+            format!(
                 "{} sheep are trying to escape! Stop them! Dont lose more than {}",
                 escapers.iter().count(),
                 loose_limit
-            );
-        }
+            ),
+        );
     }
 }
 
 fn generate_new_wave(
-    time: Res<Time>,
+    clock: Res<LevelClock>,
     mut next_wave: ResMut<NextWave>,
     teller: ResMut<Storyteller>,
     day_state: Res<State<DayState>>,
     episode_time: Res<EpisodeTime>,
+    difficulty: Res<Difficulty>,
     sheep: Query<(Entity, &Transform), (With<Sheep>, Without<IsScared>, Without<GoTo>)>,
 ) {
-    let level_time = time.elapsed_seconds() - teller.level_start_time;
+    let level_time = teller.get_level_time(&clock);
 
     let episode_time = episode_time.0;
 
     if *day_state == DayState::Day {
         let sheep_count = sheep.iter().count() as f32;
-        let c = (sheep_count * episode_time * 0.2 + 10.0).max(10.0);
         let mut dt = 5.0 - 1.0 * episode_time;
-        let n = 1.0 + 3.0 * episode_time;
 
         if level_time < 5.0 {
             dt = 2.0;
         }
 
         next_wave.0 = Some(SheepWave {
-            count: c as usize,
-            beams: n.round() as usize,
-            time: time.elapsed_seconds() + dt,
+            count: difficulty.wave_count(sheep_count, episode_time),
+            beams: difficulty.wave_beams(episode_time),
+            time: clock.elapsed + dt,
         });
     } else if *day_state == DayState::Night {
         let sheep_count = sheep.iter().count() as f32;
-        let c = (sheep_count * episode_time * 0.2 + 10.0).max(10.0);
         let dt = 5.0 - 1.0 * episode_time;
-        let n = 1.0 + 3.0 * episode_time;
 
         next_wave.0 = Some(SheepWave {
-            count: c as usize,
-            beams: n.round() as usize,
-            time: time.elapsed_seconds() + dt,
+            count: difficulty.wave_count(sheep_count, episode_time),
+            beams: difficulty.wave_beams(episode_time),
+            time: clock.elapsed + dt,
         });
     }
 
@@ -144,11 +142,13 @@ fn generate_new_wave(
 fn wave_executor(
     mut commands: Commands,
     mut next_wave: ResMut<NextWave>,
-    time: Res<Time>,
+    clock: Res<LevelClock>,
     sheep: Query<(Entity, &Transform), (With<Sheep>, Without<IsScared>, Without<GoTo>)>,
     dog: Query<&Transform, With<Dog>>,
     level_size: Res<LevelSize>,
     mut sheep_wave_status: ResMut<SheepWaveStatus>,
+    mut rand: ResMut<GameRng>,
+    mut transitions: EventWriter<SheepTransition>,
 ) {
     let Ok(dog_transform) = dog.get_single() else {
         return;
@@ -156,13 +156,11 @@ fn wave_executor(
 
     if next_wave.0.is_some() {
         let wave = next_wave.0.as_ref().unwrap().clone();
-        let cur_time = time.elapsed_seconds();
+        let cur_time = clock.elapsed;
         if wave.time <= cur_time {
             next_wave.0 = None;
             *sheep_wave_status = Default::default();
 
-            let mut rand = rand::thread_rng();
-
             let split_c = (wave.count / wave.beams).max(1);
             for _ in 0..wave.beams {
                 let random_dir =
@@ -184,14 +182,14 @@ fn wave_executor(
                 for i in 0..split_c {
                     if let Some((e, pos, dist)) = sorted_sheep.get(i) {
                         info!("Sending {:?} with {:?}", e, dist);
-                        commands
-                            .entity(*e)
-                            .insert(GoTo {
-                                target: *pos + level_size.0 * 2.0 * random_dir,
-                            })
-                            .insert(Decision::Escape)
-                            .remove::<IdleFeeding>()
-                            .insert(ShawshankRedemption);
+                        commands.entity(*e).insert((
+                            EscapeTarget(*pos + level_size.0 * 2.0 * random_dir),
+                            ShawshankRedemption,
+                        ));
+                        transitions.send(SheepTransition {
+                            sheep: *e,
+                            to: ESCAPE,
+                        });
                         sheep_wave_status.sheep.push(*e);
                     }
                 }
@@ -1,35 +1,30 @@
-use bevy::{prelude::*, transform::commands, utils::hashbrown::HashSet};
+use bevy::{ecs::schedule::SystemConfigs, prelude::*, transform::commands, utils::hashbrown::HashSet};
 use rand::{seq::SliceRandom, Rng};
 
 use crate::{
-    level_ui::TaskText,
     safe_area::SafeArea,
     sheep::Sheep,
-    storyteller::{FailReason, GlobalTask},
-    sunday::EpisodeTime,
+    sunday::{DayState, EpisodeTime},
     torch::{TorchBase, TorchLight, TORCH_BASE_RADIUS, TORCH_ILLUMINATION},
-    GameSet, GameState,
+    game_rng::GameRng, difficulty::Difficulty,
 };
 
+use super::{ActiveTasks, GlobalTask, GlobalTaskDef};
+
 pub const BAD_TORCH_COLOR: Color = Color::rgb(1.0, 0.0, 0.0);
 
-pub struct TorchBlinkingPlugin;
-
-impl Plugin for TorchBlinkingPlugin {
-    fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GlobalTask::TorchProblem), start_fire_problems)
-            .add_systems(
-                Update,
-                (
-                    apply_deferred,
-                    delight,
-                    update_delight_system,
-                    apply_deferred,
-                )
-                    .chain()
-                    .run_if(in_state(GlobalTask::TorchProblem))
-                    .in_set(GameSet::Playing),
-            );
+pub struct TorchProblemTask;
+
+impl GlobalTaskDef for TorchProblemTask {
+    const TASK: GlobalTask = GlobalTask::TorchProblem;
+    const DAYS: &'static [DayState] = &[DayState::Night];
+
+    fn start() -> SystemConfigs {
+        start_fire_problems.into_configs()
+    }
+
+    fn update() -> SystemConfigs {
+        (delight, update_delight_system, apply_deferred).chain()
     }
 }
 
@@ -42,7 +37,6 @@ pub struct TorchDelight {
 
 #[derive(Resource)]
 pub struct TorchDelightStatus {
-    pub time_for_mission: f32,
     pub start_sheep_count: usize,
     pub max_dead_sheep: usize,
     pub torches_to_lit: Vec<Entity>,
@@ -53,11 +47,12 @@ fn start_fire_problems(
     torches: Query<(Entity, &SafeArea), With<TorchBase>>,
     episode_time: Res<EpisodeTime>,
     sheep: Query<(Entity,&Transform), With<Sheep>>,
-    mut global_task: ResMut<NextState<GlobalTask>>,
+    mut tasks: ResMut<ActiveTasks>,
+    mut rand: ResMut<GameRng>,
+    difficulty: Res<Difficulty>,
 ) {
     let torch_count = torches.iter().count();
 
-    let mut rand = rand::thread_rng();
     let problem_part = episode_time.0 * 0.5;
 
     let problem_torhes_count = (torch_count as f32 - 1.0).max(1.0);
@@ -83,7 +78,7 @@ fn start_fire_problems(
         .collect::<Vec<_>>();
 
     if (problem_torches.len() == 0) {
-        global_task.set(GlobalTask::None);
+        tasks.cancel(GlobalTask::TorchProblem);
         return;
     }
 
@@ -104,8 +99,12 @@ fn start_fire_problems(
         sheep_in_torches.extend(set.iter());
     }
 
+    tasks.set_deadline(
+        GlobalTask::TorchProblem,
+        difficulty.torch_mission_time,
+        "Not enough torches lit. Seems like your master needed a good shove.",
+    );
     commands.insert_resource(TorchDelightStatus {
-        time_for_mission: 40.0,
         start_sheep_count: sheep.iter().count(),
         max_dead_sheep: (sheep_in_torches.len() / 2).max(10),
         torches_to_lit: problem_torches
@@ -118,25 +117,12 @@ fn start_fire_problems(
 }
 
 fn update_delight_system(
-    mut commands: Commands,
-    mut status: ResMut<TorchDelightStatus>,
-    mut texts: Query<&mut Text, With<TaskText>>,
+    mut tasks: ResMut<ActiveTasks>,
+    status: Res<TorchDelightStatus>,
     torches: Query<(&TorchBase, Option<&TorchDelight>)>,
-    time: Res<Time>,
-    mut gamestate: ResMut<NextState<GameState>>,
-    mut global_task: ResMut<NextState<GlobalTask>>,
     sheep: Query<&Sheep>,
 ) {
     if !status.torches_to_lit.is_empty() {
-        status.time_for_mission -= time.delta_seconds();
-        if status.time_for_mission < 0.0 {
-            gamestate.set(GameState::Finish);
-            commands.insert_resource(FailReason::TaskFailed(format!(
-                "Not enough torches lit. Seems like your master needed a good shove."
-            )));
-            return;
-        }
-
         let mut ok_torches = 0;
         for e in status.torches_to_lit.iter() {
             if let Ok((base, delight)) = torches.get(*e) {
@@ -147,25 +133,20 @@ fn update_delight_system(
         }
 
         if ok_torches == status.torches_to_lit.len() {
-            global_task.set(GlobalTask::None);
-            if let Ok(mut text) = texts.get_single_mut() {
-                text.sections[0].value = format!("");
-            }
+            tasks.succeed(GlobalTask::TorchProblem);
             return;
         } else {
             let lived_sheep_count = sheep.iter().count();
             if status.start_sheep_count - lived_sheep_count > status.max_dead_sheep {
-                gamestate.set(GameState::Finish);
-                commands.insert_resource(FailReason::TaskFailed(format!(
-                    "Too many of your flock got eaten. Seems like your master needed a good shove."
-                )));
+                tasks.fail(
+                    GlobalTask::TorchProblem,
+                    "Too many of your flock got eaten. Seems like your master needed a good shove.",
+                );
                 return;
             }
         }
 
-        if let Ok(mut text) = texts.get_single_mut() {
-            text.sections[0].value = format!("The torches are going out! Wake up the shepherd so he can light them! {} / {} torches lit\n{:.0} seconds left. Dont let to eat more then {}", ok_torches, status.torches_to_lit.len(), status.time_for_mission, status.max_dead_sheep);
-        }
+        tasks.set_objective(GlobalTask::TorchProblem, format!("The torches are going out! Wake up the shepherd so he can light them! {} / {} torches lit\nDont let to eat more then {}", ok_torches, status.torches_to_lit.len(), status.max_dead_sheep));
     }
 }
 
@@ -195,18 +176,21 @@ fn delight(
                 .entity(e)
                 .remove::<TorchDelight>()
                 .remove::<SafeArea>();
-        } else if let Ok(mut light) = lights.get_mut(base.light) {
-            light.color = BAD_TORCH_COLOR;
-            light.intensity = TORCH_ILLUMINATION * delight.rest_time / delight.change_duration;
-
+        } else {
             let new_r = TORCH_BASE_RADIUS * delight.rest_time / delight.change_duration;
-            let h = TORCH_BASE_RADIUS * 0.5;
 
-            let outer_angle = (new_r / h).atan();
-            let inner_angle = outer_angle * 0.85;
+            if let Ok(mut light) = lights.get_mut(base.light) {
+                light.color = BAD_TORCH_COLOR;
+                light.intensity = TORCH_ILLUMINATION * delight.rest_time / delight.change_duration;
+
+                let h = TORCH_BASE_RADIUS * 0.5;
 
-            light.inner_angle = inner_angle;
-            light.outer_angle = outer_angle;
+                let outer_angle = (new_r / h).atan();
+                let inner_angle = outer_angle * 0.85;
+
+                light.inner_angle = inner_angle;
+                light.outer_angle = outer_angle;
+            }
 
             commands.entity(e).insert(SafeArea::Circle {
                 pos: Vec2::new(tr.translation.x, tr.translation.z),
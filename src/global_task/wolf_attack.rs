@@ -1,9 +1,203 @@
-use bevy::prelude::*;
+//Wolf attack: a pack is announced from one side of the pasture and comes after a countdown.
+//Attackers ignore safe areas, they go for the sheep closest to their side wherever they are.
+//Task is done when the whole pack is gone (driven away by barking or left after eating),
+//it fails when the pack eats more than the limit. Sheep eaten by other wolves do not count
 
-pub struct WolfAttackPlugin;
+use std::f32::consts::PI;
 
-impl Plugin for WolfAttackPlugin {
-    fn build(&self, app: &mut App) {
+use bevy::{ecs::schedule::SystemConfigs, prelude::*};
+use rand::Rng;
 
+use crate::{
+    corpse::SpawnCorpse,
+    difficulty::Difficulty,
+    sheep::Sheep,
+    sunday::{DayState, EpisodeTime},
+    test_level::LevelSize,
+    wolf::TryToCatchSheep,
+    wolf_pack::{retreat, spawn_pack, PackMember, PackState, WolfPack},
+    game_rng::GameRng,
+};
+
+use super::{ActiveTasks, GlobalTask, GlobalTaskDef};
+
+//Seconds between the announcement and the attack
+const ANNOUNCE_TIME: f32 = 15.0;
+//Pack gives up after this time
+const ATTACK_TIME: f32 = 40.0;
+//Pack size at full hardness
+const MAX_ATTACK_WOLVES: f32 = 6.0;
+const MIN_ATTACK_WOLVES: usize = 2;
+
+pub struct WolfAttackTask;
+
+impl GlobalTaskDef for WolfAttackTask {
+    const TASK: GlobalTask = GlobalTask::WolfAttack;
+    const DAYS: &'static [DayState] = &[DayState::Night];
+    //Heavy task, comes less often than the others
+    const WEIGHT: f32 = 0.7;
+    const COOLDOWN: f32 = 90.0;
+
+    fn start() -> SystemConfigs {
+        announce_attack.into_configs()
+    }
+
+    fn update() -> SystemConfigs {
+        (wolf_attack_system, apply_deferred).chain()
     }
 }
+
+#[derive(Resource)]
+pub struct WolfAttackStatus {
+    //Unit vector from the pasture center to the side the pack comes from
+    pub direction: Vec3,
+    pub size: usize,
+    pub announce_time: f32,
+    pub attack_time: f32,
+    //None before the attack starts
+    pub pack: Option<Entity>,
+    //Sheep eaten by wolves of this pack
+    pub dead_sheep: usize,
+    pub max_dead_sheep: usize,
+}
+
+/// Side of the pasture as seen on the screen, camera looks to -z
+fn direction_name(direction: Vec3) -> &'static str {
+    if direction.x.abs() > direction.z.abs() {
+        if direction.x > 0.0 {
+            "east"
+        } else {
+            "west"
+        }
+    } else if direction.z > 0.0 {
+        "south"
+    } else {
+        "north"
+    }
+}
+
+fn announce_attack(
+    mut commands: Commands,
+    mut rng: ResMut<GameRng>,
+    difficulty: Res<Difficulty>,
+    episode_time: Res<EpisodeTime>,
+) {
+    let angle = rng.gen_range(0.0..PI * 2.0);
+    let direction = Vec3::new(angle.cos(), 0.0, angle.sin());
+    let size = ((MAX_ATTACK_WOLVES * difficulty.episode_hardness(episode_time.0)).round() as usize)
+        .max(MIN_ATTACK_WOLVES);
+
+    commands.insert_resource(WolfAttackStatus {
+        direction,
+        size,
+        announce_time: ANNOUNCE_TIME,
+        attack_time: ATTACK_TIME,
+        pack: None,
+        dead_sheep: 0,
+        //Dog has to drive away at least half of the pack before it eats
+        max_dead_sheep: (size / 2).max(1),
+    });
+
+    info!(
+        "Wolf attack of {} wolves from the {}",
+        size,
+        direction_name(direction)
+    );
+}
+
+fn wolf_attack_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    level_size: Res<LevelSize>,
+    mut status: ResMut<WolfAttackStatus>,
+    mut packs: Query<&mut WolfPack>,
+    sheep: Query<(Entity, &Transform), With<Sheep>>,
+    members: Query<&PackMember>,
+    mut kills: EventReader<SpawnCorpse>,
+    mut tasks: ResMut<ActiveTasks>,
+) {
+    let side = direction_name(status.direction);
+
+    let Some(pack_entity) = status.pack else {
+        status.announce_time -= time.delta_seconds();
+        if status.announce_time > 0.0 {
+            tasks.set_objective(
+                GlobalTask::WolfAttack,
+                format!(
+                    "Wolves are howling in the {}! {} wolves will attack in {:.0} seconds",
+                    side, status.size, status.announce_time
+                ),
+            );
+            return;
+        }
+
+        //Closest sheep to the side of the attack, in safe areas or not
+        let start_pos = status.direction * level_size.0 * 2.0;
+        let mut targets = sheep
+            .iter()
+            .map(|(e, t)| (e, t.translation.distance(start_pos)))
+            .collect::<Vec<_>>();
+        targets.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let targets = targets
+            .into_iter()
+            .take(status.size)
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>();
+        if targets.is_empty() {
+            tasks.cancel(GlobalTask::WolfAttack);
+            return;
+        }
+
+        let (pack, members) =
+            spawn_pack(&mut commands, start_pos, status.size, targets.clone(), PackState::Attack);
+        for (i, wolf) in members.iter().enumerate() {
+            commands.entity(*wolf).insert(TryToCatchSheep {
+                target: targets[i % targets.len()],
+                ignore_safe: true,
+            });
+        }
+        status.pack = Some(pack);
+        return;
+    };
+
+    let pack_kills = kills
+        .read()
+        .filter(|kill| {
+            members
+                .get(kill.killer)
+                .is_ok_and(|member| member.pack == pack_entity)
+        })
+        .count();
+    status.dead_sheep += pack_kills;
+    let dead_sheep = status.dead_sheep;
+    if dead_sheep > status.max_dead_sheep {
+        tasks.fail(
+            GlobalTask::WolfAttack,
+            "The wolves had a feast in the middle of your flock. Some guard dog you are.",
+        );
+        return;
+    }
+
+    let Ok(mut pack) = packs.get_mut(pack_entity) else {
+        //Whole pack is gone
+        info!("Wolf attack repelled, {} sheep lost", dead_sheep);
+        tasks.succeed(GlobalTask::WolfAttack);
+        return;
+    };
+
+    status.attack_time -= time.delta_seconds();
+    if status.attack_time <= 0.0 && pack.state != PackState::Retreat {
+        retreat(&mut commands, &mut pack);
+    }
+
+    tasks.set_objective(
+        GlobalTask::WolfAttack,
+        format!(
+            "Wolves attack from the {}! Bark them away! {} wolves left, don't let them eat more than {} ({} eaten)",
+            side,
+            pack.members.len(),
+            status.max_dead_sheep,
+            dead_sheep
+        ),
+    );
+}
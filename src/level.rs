@@ -0,0 +1,164 @@
+//Levels are described in assets/levels/*.level.ron and listed in assets/levels.assets.ron
+//Add a new level: write a new .level.ron file and put its path into levels.assets.ron
+//The same list has sheep.behaviour.ron with the StateChance matrix of sheep decisions
+
+use bevy::{prelude::*, reflect::TypePath};
+use bevy_asset_loader::prelude::*;
+use bevy_common_assets::ron::RonAssetPlugin;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    campaign::Campaign,
+    safe_area::SafeArea,
+    sheep::{StateChance, StateChanceDescription},
+    sheep_behaviour::SheepTransitions,
+    GameState,
+};
+
+const LEVELS_LIST_PATH: &str = "levels.assets.ron";
+
+pub struct LevelLoadingPlugin;
+
+impl Plugin for LevelLoadingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<LevelDescription>::new(&["level.ron"]))
+            .add_plugins(RonAssetPlugin::<StateChanceDescription>::new(&["behaviour.ron"]))
+            .add_loading_state(
+                LoadingState::new(GameState::Loading).continue_to_state(GameState::Menu),
+            )
+            .add_dynamic_collection_to_loading_state::<_, StandardDynamicAssetCollection>(
+                GameState::Loading,
+                LEVELS_LIST_PATH,
+            )
+            .add_collection_to_loading_state::<_, LevelAssets>(GameState::Loading)
+            .add_collection_to_loading_state::<_, SheepBehaviourAssets>(GameState::Loading)
+            .add_systems(OnExit(GameState::Loading), (setup_campaign, setup_state_chance));
+    }
+}
+
+#[derive(AssetCollection, Resource)]
+pub struct LevelAssets {
+    #[asset(key = "levels", collection(typed))]
+    pub levels: Vec<Handle<LevelDescription>>,
+}
+
+#[derive(AssetCollection, Resource)]
+pub struct SheepBehaviourAssets {
+    #[asset(key = "sheep_behaviour")]
+    pub state_chance: Handle<StateChanceDescription>,
+}
+
+/// Level which is spawned on enter to GameState::Playing. Selected by the campaign
+#[derive(Resource, Default, Clone)]
+pub struct CurrentLevel(pub LevelDescription);
+
+//All positions are on the ground plane: x is x, y is z
+#[derive(Asset, TypePath, Serialize, Deserialize, Clone, Debug)]
+pub struct LevelDescription {
+    pub name: String,
+    //Half size of the pasture, goes to LevelSize
+    pub size: f32,
+    pub flock: FlockDescription,
+    pub player_spawn: Vec2,
+    pub shepherd_spawn: Vec2,
+    pub safe_areas: Vec<SafeArea>,
+    pub torches: TorchPlacement,
+    //Trees per square meter around the pasture
+    #[serde(default = "default_tree_density")]
+    pub tree_density: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FlockDescription {
+    pub count: usize,
+    //Sheep are spawned uniformly inside circle with this radius around center
+    pub radius: f32,
+    //Ages of lambs among count, set by the campaign for lambs born on previous nights. Random part of the flock if None
+    #[serde(default)]
+    pub lamb_ages: Option<Vec<f32>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum TorchPlacement {
+    //Random positions inside square [-range, range]. Torches closer than torch radius to each other are skipped
+    Random { count: usize, range: f32 },
+    Fixed(Vec<Vec2>),
+}
+
+fn default_tree_density() -> f32 {
+    0.3
+}
+
+//Same as assets/levels/meadow.level.ron. Used when there is no asset server, for example in headless runs
+impl Default for LevelDescription {
+    fn default() -> Self {
+        let size = 50.0;
+        Self {
+            name: "Meadow".to_string(),
+            size,
+            flock: FlockDescription {
+                count: 1000,
+                radius: size / 1.5 / 2.0,
+                lamb_ages: None,
+            },
+            player_spawn: Vec2::new(-size - 2.0, 0.0),
+            shepherd_spawn: Vec2::new(0.0, -size),
+            safe_areas: vec![SafeArea::Rect {
+                pos: Vec2::ZERO,
+                size: Vec2::new(size * 1.5, size * 1.5),
+            }],
+            torches: TorchPlacement::Random {
+                count: 20,
+                range: size / 2.0,
+            },
+            tree_density: default_tree_density(),
+        }
+    }
+}
+
+impl LevelDescription {
+    /// Parse level from RON text, same format as .level.ron assets
+    pub fn from_ron(text: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(text)
+    }
+
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        ron::to_string(self)
+    }
+}
+
+//Levels go to the campaign in the same order as they are listed
+fn setup_campaign(
+    mut campaign: ResMut<Campaign>,
+    level_assets: Res<LevelAssets>,
+    levels: Res<Assets<LevelDescription>>,
+) {
+    let nights = level_assets
+        .levels
+        .iter()
+        .filter_map(|handle| levels.get(handle))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    if nights.is_empty() {
+        warn!("No levels in {}, using built-in one", LEVELS_LIST_PATH);
+        return;
+    }
+
+    info!("Campaign with {} nights", nights.len());
+    campaign.set_levels(nights);
+}
+
+fn setup_state_chance(
+    mut state_chance: ResMut<StateChance>,
+    assets: Res<SheepBehaviourAssets>,
+    descriptions: Res<Assets<StateChanceDescription>>,
+    transitions: Res<SheepTransitions>,
+) {
+    let Some(description) = descriptions.get(&assets.state_chance) else {
+        warn!("No sheep behaviour in {}, using built-in one", LEVELS_LIST_PATH);
+        return;
+    };
+
+    *state_chance = description.to_state_chance(&transitions);
+}
@@ -1,6 +1,8 @@
+use std::time::Duration;
+
 use bevy::prelude::*;
 
-use crate::{storyteller::LevelTimer, GameStuff, player::Stamina, GameSet};
+use crate::{storyteller::{LevelClock, LevelTimer, Score, Storyteller}, GameStuff, player::Stamina, GameSet, global_task::ActiveTasks};
 
 pub struct LevelUiPlugin;
 
@@ -8,7 +10,7 @@ impl Plugin for LevelUiPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<CreateLevelUi>()
             .add_systems(Update, create_level_ui_system)
-            .add_systems(Update, show_stamina.in_set(GameSet::Playing));
+            .add_systems(Update, (show_stamina, level_timer, show_tasks).in_set(GameSet::Playing));
     }
 }
 
@@ -158,4 +160,47 @@ fn show_stamina(
     } else {
         background_color.0 = Color::GREEN;
     }
-}
\ No newline at end of file
+}
+fn level_timer(
+    mut timers: Query<&mut Text, With<LevelTimer>>,
+    teller: Res<Storyteller>,
+    clock: Res<LevelClock>,
+    score: Res<Score>,
+) {
+    for mut timer in timers.iter_mut() {
+        let level_time = teller.get_level_time(&clock);
+        if teller.level_duration - level_time > 0.0 {
+            let dur = Duration::from_secs_f32(teller.level_duration - level_time);
+
+            let time = format!("{:02}:{:02}", dur.as_secs() / 60, dur.as_secs() % 60);
+            let score_text = format!("Score: {:.1}", score.0);
+
+            timer.sections[0].value = format!("{} {}", time, score_text);
+        } else {
+            timer.sections[0].value = format!("{:02}:{:02}", 0, 0);
+        }
+    }
+}
+
+//Objectives of all running tasks, one per line
+fn show_tasks(
+    tasks: Res<ActiveTasks>,
+    mut texts: Query<&mut Text, With<TaskText>>,
+) {
+    let value = tasks
+        .tasks
+        .iter()
+        .filter(|t| !t.objective.is_empty())
+        .map(|t| match t.deadline {
+            Some(time_left) => format!("{}\n{:.0} seconds left", t.objective, time_left.max(0.0)),
+            None => t.objective.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    for mut text in texts.iter_mut() {
+        if text.sections[0].value != value {
+            text.sections[0].value = value.clone();
+        }
+    }
+}
@@ -1,30 +1,52 @@
-#![allow(clippy::type_complexity)]
+#![allow(clippy::type_complexity, clippy::too_many_arguments)]
 
 pub mod common_storage;
+pub mod controls;
 pub mod debug_diagnostic;
+pub mod difficulty;
+pub mod director;
 pub mod finish_screen;
+pub mod flocking;
+pub mod game_rng;
 pub mod global_task;
+pub mod level;
 pub mod level_ui;
 pub mod menu;
+pub mod neighbours;
+pub mod panic;
+pub mod pasture;
+pub mod pause;
 pub mod physics;
 pub mod player;
+pub mod replay;
 pub mod safe_area;
+pub mod save;
 pub mod sheep;
+pub mod sheep_behaviour;
+pub mod sheep_traits;
 pub mod shepherd;
 pub mod sprite_material;
+pub mod storage;
 pub mod storyteller;
 pub mod sunday;
 pub mod test_level;
 pub mod torch;
+pub mod touch;
 pub mod wolf;
+pub mod wolf_pack;
 pub mod ambient;
 pub mod auto_anim;
+pub mod births;
+pub mod bleating;
+pub mod campaign;
 pub mod corpse;
 
 use std::f32::consts::PI;
 
 #[cfg(feature = "dev")]
 use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::audio::SpatialScale;
 use bevy::prelude::*;
 use bevy::{app::App, core_pipeline::clear_color::ClearColorConfig};
 
@@ -53,11 +75,47 @@ pub enum GameSet {
     Finish,
 }
 
+/// Order of simulation modules inside a frame. Without it bevy orders independent systems
+/// by hash, which changes from run to run, and replays drift apart
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub enum SimSet {
+    Player,
+    Storyteller,
+    Sunday,
+    GlobalTask,
+    SafeArea,
+    Sheep,
+    Wolf,
+    Shepherd,
+    Torch,
+    Corpse,
+    Physics,
+}
+
+/// Systems which insert visuals into simulation entities. They change archetypes
+/// of those entities, so they run after the simulation to keep its query order stable
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct VisualsSet;
+
+/// Full game: simulation core plus everything needed to show it on screen.
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((SimulationPlugin, PresentationPlugin));
+    }
+}
+
+/// Pure game logic. Does not touch window, audio, renderer or asset server,
+/// so it can be stepped inside a `MinimalPlugins` app (see `examples/headless_level.rs`)
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
     fn build(&self, app: &mut App) {
         app.add_state::<GameState>();
+        app.init_resource::<test_level::LevelSize>();
+        app.init_resource::<level::CurrentLevel>();
+        app.init_resource::<difficulty::Difficulty>();
 
         //Terrible set configuration
         app.configure_sets(
@@ -67,7 +125,9 @@ impl Plugin for GamePlugin {
         app.configure_sets(Update, GameSet::Menu.run_if(in_state(GameState::Menu)));
         app.configure_sets(
             Update,
-            GameSet::Playing.run_if(in_state(GameState::Playing)),
+            GameSet::Playing
+                .run_if(in_state(GameState::Playing))
+                .run_if(in_state(pause::PauseState::Running)),
         );
         app.configure_sets(Update, GameSet::Finish.run_if(in_state(GameState::Finish)));
 
@@ -78,49 +138,129 @@ impl Plugin for GamePlugin {
         app.configure_sets(FixedUpdate, GameSet::Menu.run_if(in_state(GameState::Menu)));
         app.configure_sets(
             FixedUpdate,
-            GameSet::Playing.run_if(in_state(GameState::Playing)),
+            GameSet::Playing
+                .run_if(in_state(GameState::Playing))
+                .run_if(in_state(pause::PauseState::Running)),
         );
         app.configure_sets(
             FixedUpdate,
             GameSet::Finish.run_if(in_state(GameState::Finish)),
         );
 
-        app.add_plugins(debug_diagnostic::DiagnosticPlugin);
+        for schedule in [Update.intern(), FixedUpdate.intern()] {
+            app.configure_sets(
+                schedule,
+                (
+                    SimSet::Player,
+                    SimSet::Storyteller,
+                    SimSet::Sunday,
+                    SimSet::GlobalTask,
+                    SimSet::SafeArea,
+                    SimSet::Sheep,
+                    SimSet::Wolf,
+                    SimSet::Shepherd,
+                    SimSet::Torch,
+                    SimSet::Corpse,
+                    SimSet::Physics,
+                )
+                    .chain(),
+            );
+        }
+
+        //Replay has to insert its seed before GameRngPlugin picks one
+        app.add_plugins((replay::ReplayPlugin, game_rng::GameRngPlugin));
 
         app.add_plugins((
+            controls::ControlsPlugin,
             player::PlayerPlugin,
             physics::PhysicsPlugin,
-            common_storage::CommonStoragePlugin,
             torch::TorchPlugin,
             safe_area::SafeAreaPlugin,
-            sprite_material::SpriteMaterialPlugin,
             sheep::SheepPlugin,
             storyteller::StorytellerPlugin,
-            level_ui::LevelUiPlugin,
             wolf::WolfPlugin,
-            menu::MenuPlugin,
-            finish_screen::FinishScreenPlugin,
             sunday::SundayPlugin,
             shepherd::ShepherdPlugin,
             global_task::GlobalTaskPlugin,
+            corpse::CorpsePlugin,
+            campaign::CampaignPlugin,
+            pause::PausePlugin,
+            pasture::PasturePlugin,
         ));
 
-        app.add_plugins((
-            ambient::AmbientPlugin,
-            corpse::CorpsePlugin
-        ));
+        app.add_plugins(births::BirthsPlugin);
 
         //For long term updates
         app.insert_resource(Time::<Fixed>::from_seconds(1.0));
 
         app.add_systems(
             OnEnter(GameState::Playing),
-            (test_level::setup, sheep::setup),
+            (test_level::setup, sheep::setup)
+                .chain()
+                .after(game_rng::RngResetSet)
+                .after(campaign::CampaignSet),
         );
 
-        app.add_systems(Startup, (loading, camera_setup));
+        //Next night or retry goes straight from Finish to Playing, so level is cleared on exit from Finish
+        app.add_systems(OnExit(GameState::Finish), clear_game_stuff);
+        //Quit from the pause menu
+        app.add_systems(
+            OnTransition {
+                from: GameState::Playing,
+                to: GameState::Menu,
+            },
+            clear_game_stuff,
+        );
+    }
+}
+
+/// Window, audio, UI and rendering on top of [`SimulationPlugin`]
+pub struct PresentationPlugin;
 
-        app.add_systems(OnEnter(GameState::Menu), clear_game_stuff);
+impl Plugin for PresentationPlugin {
+    fn build(&self, app: &mut App) {
+        app.configure_sets(Update, VisualsSet.after(SimSet::Physics));
+        //Overrides the AudioPlugin default for desktop and mobile entry points alike
+        app.insert_resource(SpatialScale::new(bleating::AUDIO_SCALE));
+
+        app.add_plugins((
+            debug_diagnostic::DiagnosticPlugin,
+            level::LevelLoadingPlugin,
+            save::SavePlugin,
+        ));
+
+        app.add_plugins((
+            player::PlayerVisualsPlugin,
+            common_storage::CommonStoragePlugin,
+            torch::TorchVisualsPlugin,
+            safe_area::SafeAreaVisualsPlugin,
+            sprite_material::SpriteMaterialPlugin,
+            sheep::SheepVisualsPlugin,
+            level_ui::LevelUiPlugin,
+            wolf::WolfVisualsPlugin,
+            menu::MenuPlugin,
+            finish_screen::FinishScreenPlugin,
+            sunday::SundayVisualsPlugin,
+            shepherd::ShepherdVisualsPlugin,
+            pause::PauseMenuPlugin,
+            controls::ControlsMenuPlugin,
+        ));
+
+        app.add_plugins((
+            ambient::AmbientPlugin,
+            corpse::CorpseVisualsPlugin,
+            pasture::PastureVisualsPlugin,
+            bleating::BleatingPlugin,
+        ));
+
+        app.add_systems(
+            OnEnter(GameState::Playing),
+            test_level::setup_decorations
+                .after(game_rng::RngResetSet)
+                .after(campaign::CampaignSet),
+        );
+
+        app.add_systems(Startup, camera_setup);
     }
 }
 
@@ -128,10 +268,6 @@ pub fn get_sprite_rotation() -> Quat {
     Quat::from_euler(EulerRot::XYZ, -PI / 2.0 - PI / 4.0, 0.0, 0.0)
 }
 
-fn loading(mut next_state: ResMut<NextState<GameState>>) {
-    next_state.set(GameState::Menu);
-}
-
 fn camera_setup(mut commands: Commands) {
     commands.spawn(Camera3dBundle {
         transform: Transform::from_xyz(0.0, 30.0, 30.0).looking_at(Vec3::ZERO, Vec3::Y),
@@ -1,6 +1,11 @@
 use bevy::prelude::*;
 
-use crate::{GameSet, GameState};
+use crate::{
+    campaign::Campaign,
+    difficulty::Difficulty,
+    save::SaveGame,
+    GameSet, GameState,
+};
 
 pub struct MenuPlugin;
 
@@ -8,14 +13,32 @@ impl Plugin for MenuPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(OnEnter(GameState::Menu), setup_main_menu);
         app.add_systems(OnExit(GameState::Menu), clear_menu);
-        app.add_systems(Update, button_system.in_set(GameSet::Menu));
+        app.add_systems(
+            Update,
+            (button_system, difficulty_text).chain().in_set(GameSet::Menu),
+        );
     }
 }
 
 #[derive(Component)]
 pub struct MainMenu;
 
-fn setup_main_menu(mut commands: Commands) {
+#[derive(Component)]
+enum MenuButton {
+    //Saved campaign from the next night
+    Continue,
+    //New campaign from this night
+    Night(usize),
+    //Switch to the next difficulty preset
+    Difficulty,
+}
+
+fn setup_main_menu(
+    mut commands: Commands,
+    campaign: Res<Campaign>,
+    save: Res<SaveGame>,
+    difficulty: Res<Difficulty>,
+) {
     let mut text_style = TextStyle::default();
     text_style.font_size = 24.0;
     commands
@@ -42,26 +65,94 @@ Good luck!\n",
                 TextStyle::default(),
             ));
 
-            parent
-                .spawn(ButtonBundle {
-                    style: Style {
-                        width: Val::Px(100.0),
-                        height: Val::Px(50.0),
-                        border: UiRect::all(Val::Px(5.0)),
-                        justify_content: JustifyContent::Center,
-                        align_items: AlignItems::Center,
-                        ..default()
-                    },
-                    border_color: BorderColor(Color::WHITE),
-                    background_color: BackgroundColor(Color::BLACK),
+            if campaign.in_progress() {
+                spawn_menu_button(
+                    parent,
+                    MenuButton::Continue,
+                    format!("Continue night {}", campaign.night + 1),
+                    &text_style,
+                );
+            }
+
+            //One button per unlocked night
+            for night in 0..=campaign.unlocked.min(campaign.night_count() - 1) {
+                let text = if campaign.unlocked == 0 {
+                    "Start".to_string()
+                } else {
+                    format!("Night {}", night + 1)
+                };
+                spawn_menu_button(parent, MenuButton::Night(night), text, &text_style);
+            }
+
+            spawn_menu_button(
+                parent,
+                MenuButton::Difficulty,
+                difficulty_label(&difficulty),
+                &text_style,
+            );
+
+            parent.spawn(TextBundle::from_section(
+                high_scores_text(&save),
+                TextStyle::default(),
+            ));
+        });
+}
+
+fn spawn_menu_button(
+    parent: &mut ChildBuilder,
+    button: MenuButton,
+    text: String,
+    text_style: &TextStyle,
+) {
+    parent
+        .spawn((
+            button,
+            ButtonBundle {
+                style: Style {
+                    min_width: Val::Px(100.0),
+                    height: Val::Px(50.0),
+                    border: UiRect::all(Val::Px(5.0)),
+                    padding: UiRect::horizontal(Val::Px(10.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
                     ..default()
-                })
-                .with_children(|parent| {
-                    parent.spawn(TextBundle::from_section("Start", text_style.clone()));
-                });
+                },
+                border_color: BorderColor(Color::WHITE),
+                background_color: BackgroundColor(Color::BLACK),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(text, text_style.clone()));
         });
 }
 
+fn difficulty_label(difficulty: &Difficulty) -> String {
+    format!("Difficulty: {}", difficulty.preset.name())
+}
+
+fn high_scores_text(save: &SaveGame) -> String {
+    if save.high_scores.is_empty() {
+        return String::new();
+    }
+
+    let mut text = "\nHigh scores".to_string();
+    for (level, table) in save.high_scores.iter() {
+        text += &format!("\n{}:", level);
+        for (idx, high_score) in table.iter().enumerate() {
+            text += &format!(
+                "\n  {}. {:.1}  sheep: {}  seed: {}  {}",
+                idx + 1,
+                high_score.score,
+                high_score.sheep,
+                high_score.seed,
+                high_score.date_string()
+            );
+        }
+    }
+    text
+}
+
 fn clear_menu(mut commands: Commands, query: Query<Entity, With<MainMenu>>) {
     for entity in query.iter() {
         commands.entity(entity).despawn_recursive();
@@ -70,16 +161,25 @@ fn clear_menu(mut commands: Commands, query: Query<Entity, With<MainMenu>>) {
 
 fn button_system(
     mut next_state: ResMut<NextState<GameState>>,
+    mut campaign: ResMut<Campaign>,
+    mut difficulty: ResMut<Difficulty>,
     mut interaction_query: Query<
-        (&Interaction, &mut BackgroundColor),
+        (&Interaction, &mut BackgroundColor, &MenuButton),
         (Changed<Interaction>, With<Button>),
     >,
 ) {
-    for (interaction, mut color) in &mut interaction_query {
+    for (interaction, mut color, button) in &mut interaction_query {
         match *interaction {
-            Interaction::Pressed => {
-                next_state.set(GameState::Playing);
-            }
+            Interaction::Pressed => match button {
+                MenuButton::Continue => next_state.set(GameState::Playing),
+                MenuButton::Night(night) => {
+                    campaign.select_night(*night);
+                    next_state.set(GameState::Playing);
+                }
+                MenuButton::Difficulty => {
+                    *difficulty = Difficulty::load(difficulty.preset.next());
+                }
+            },
             Interaction::Hovered => {
                 *color = BackgroundColor(Color::DARK_GRAY);
             }
@@ -89,3 +189,24 @@ fn button_system(
         }
     }
 }
+
+fn difficulty_text(
+    difficulty: Res<Difficulty>,
+    buttons: Query<(&MenuButton, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    if !difficulty.is_changed() {
+        return;
+    }
+
+    for (button, children) in buttons.iter() {
+        if !matches!(button, MenuButton::Difficulty) {
+            continue;
+        }
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = difficulty_label(&difficulty);
+            }
+        }
+    }
+}
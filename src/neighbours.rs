@@ -0,0 +1,249 @@
+//Nearest sheep for flocking and scared sheep. Sheep positions go to a uniform grid every frame,
+//neighbour lists are refreshed in parallel for a part of the flock: with N sheep and a budget of B
+//every sheep is refreshed once in ceil(N / B) frames. Sheep are picked by entity index, so the
+//result does not depend on thread timing and replays stay deterministic
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::{sheep::Sheep, GameSet, SimSet};
+
+pub struct SheepNeighboursPlugin;
+
+impl Plugin for SheepNeighboursPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NeighbourSettings>()
+            .init_resource::<NeighbourGrid>()
+            .init_resource::<NeighbourStats>()
+            .register_type::<NeighbourSettings>()
+            .add_systems(
+                Update,
+                (rebuild_grid, update_nearest, neighbour_stats)
+                    .chain()
+                    .in_set(NeighbourSet)
+                    .in_set(GameSet::Playing)
+                    .in_set(SimSet::Sheep),
+            );
+    }
+}
+
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub struct NeighbourSet;
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource, Default)]
+pub struct NeighbourSettings {
+    pub cell_size: f32,
+    //Neighbours per sheep, the sheep itself included
+    pub count: usize,
+    //Sheep which get fresh neighbours in one frame
+    pub budget: usize,
+    //Search stops after this many rings of cells around the sheep cell
+    pub max_rings: i32,
+}
+
+impl Default for NeighbourSettings {
+    fn default() -> Self {
+        Self {
+            cell_size: 2.0,
+            count: 7,
+            budget: 2000,
+            max_rings: 3,
+        }
+    }
+}
+
+/// Sheep positions by grid cell on the ground plane
+#[derive(Resource, Default)]
+pub struct NeighbourGrid {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<(Vec3, Entity)>>,
+    //Sheep in all cells
+    len: usize,
+    //Simulation frames, never zero after the first rebuild
+    frame: u64,
+}
+
+impl NeighbourGrid {
+    fn cell(&self, pos: Vec3) -> IVec2 {
+        IVec2::new(
+            (pos.x / self.cell_size).floor() as i32,
+            (pos.z / self.cell_size).floor() as i32,
+        )
+    }
+
+    fn clear(&mut self, cell_size: f32) {
+        self.cell_size = cell_size;
+        self.len = 0;
+        //Keep allocations of cells, empty ones are removed so the map does not grow forever
+        self.cells.retain(|_, sheep| !sheep.is_empty());
+        for sheep in self.cells.values_mut() {
+            sheep.clear();
+        }
+    }
+
+    fn insert(&mut self, pos: Vec3, e: Entity) {
+        let cell = self.cell(pos);
+        self.cells.entry(cell).or_default().push((pos, e));
+        self.len += 1;
+    }
+
+    /// Up to `count` nearest sheep around `pos`, closest first
+    pub fn nearest(&self, pos: Vec3, count: usize, max_rings: i32) -> Vec<(Vec3, Option<Entity>)> {
+        let center = self.cell(pos);
+        let mut found: Vec<(f32, Vec3, Entity)> = Vec::new();
+
+        for ring in 0..=max_rings {
+            for x in -ring..=ring {
+                for y in -ring..=ring {
+                    //Only the border of the ring, inner cells are already checked
+                    if x.abs() != ring && y.abs() != ring {
+                        continue;
+                    }
+                    if let Some(sheep) = self.cells.get(&(center + IVec2::new(x, y))) {
+                        found.extend(sheep.iter().map(|(p, e)| (p.distance_squared(pos), *p, *e)));
+                    }
+                }
+            }
+
+            //Sheep in the next ring are at least `ring * cell_size` away, closer ones are all found
+            let checked_radius = ring as f32 * self.cell_size;
+            let closer = found
+                .iter()
+                .filter(|(d, _, _)| *d <= checked_radius * checked_radius)
+                .count();
+            //Small flock is found completely before max_rings
+            if closer >= count || found.len() == self.len {
+                break;
+            }
+        }
+
+        found.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.2.cmp(&b.2)));
+        found.truncate(count);
+        found.into_iter().map(|(_, p, e)| (p, Some(e))).collect()
+    }
+}
+
+/// Nearest sheep, closest first. The sheep itself is the first one
+#[derive(Component, Default)]
+pub struct NearestSheep(pub Vec<(Vec3, Option<Entity>)>);
+
+/// Simulation frame when NearestSheep was refreshed
+#[derive(Component, Default)]
+pub struct NeighboursUpdated(pub u64);
+
+/// Staleness of neighbour lists in simulation frames, for the debug panel
+#[derive(Resource, Default, Debug)]
+pub struct NeighbourStats {
+    pub sheep: usize,
+    pub updated: usize,
+    pub mean_age: f32,
+    pub max_age: u64,
+}
+
+fn rebuild_grid(
+    settings: Res<NeighbourSettings>,
+    mut grid: ResMut<NeighbourGrid>,
+    sheep: Query<(Entity, &Transform), With<Sheep>>,
+) {
+    grid.clear(settings.cell_size);
+    grid.frame += 1;
+    for (e, t) in sheep.iter() {
+        grid.insert(t.translation, e);
+    }
+}
+
+fn update_nearest(
+    settings: Res<NeighbourSettings>,
+    grid: Res<NeighbourGrid>,
+    mut sheep: Query<(Entity, &Transform, &mut NearestSheep, &mut NeighboursUpdated), With<Sheep>>,
+) {
+    let period = sheep.iter().len().div_ceil(settings.budget.max(1)).max(1) as u64;
+    let frame = grid.frame;
+
+    sheep
+        .par_iter_mut()
+        .for_each(|(e, t, mut nearest, mut updated)| {
+            //Sheep without neighbours yet are refreshed at once
+            let due = (frame + e.index() as u64) % period == 0 || updated.0 == 0;
+            if due {
+                nearest.0 = grid.nearest(t.translation, settings.count, settings.max_rings);
+                updated.0 = frame;
+            }
+        });
+}
+
+fn neighbour_stats(
+    grid: Res<NeighbourGrid>,
+    mut stats: ResMut<NeighbourStats>,
+    sheep: Query<&NeighboursUpdated, With<Sheep>>,
+) {
+    let mut total_age = 0;
+    *stats = NeighbourStats::default();
+    for updated in sheep.iter() {
+        let age = grid.frame - updated.0;
+        stats.sheep += 1;
+        if age == 0 {
+            stats.updated += 1;
+        }
+        total_age += age;
+        stats.max_age = stats.max_age.max(age);
+    }
+    if stats.sheep > 0 {
+        stats.mean_age = total_age as f32 / stats.sheep as f32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    fn grid(points: &[Vec3]) -> NeighbourGrid {
+        let mut grid = NeighbourGrid::default();
+        grid.clear(2.0);
+        for (idx, p) in points.iter().enumerate() {
+            grid.insert(*p, Entity::from_raw(idx as u32));
+        }
+        grid
+    }
+
+    #[test]
+    fn nearest_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let points = (0..500)
+            .map(|_| Vec3::new(rng.gen_range(-20.0..20.0), 0.0, rng.gen_range(-20.0..20.0)))
+            .collect::<Vec<_>>();
+        let grid = grid(&points);
+
+        for _ in 0..50 {
+            let pos = Vec3::new(rng.gen_range(-25.0..25.0), 0.0, rng.gen_range(-25.0..25.0));
+            let mut expected = points
+                .iter()
+                .enumerate()
+                .map(|(idx, p)| (p.distance_squared(pos), *p, Entity::from_raw(idx as u32)))
+                .collect::<Vec<_>>();
+            expected.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.2.cmp(&b.2)));
+            let expected = expected
+                .into_iter()
+                .take(7)
+                .map(|(_, p, e)| (p, Some(e)))
+                .collect::<Vec<_>>();
+
+            assert_eq!(grid.nearest(pos, 7, 100), expected);
+        }
+    }
+
+    #[test]
+    fn nearest_stops_when_flock_is_smaller_than_count() {
+        let points = [Vec3::ZERO, Vec3::new(5.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -9.0)];
+        let grid = grid(&points);
+
+        //Unlimited rings end as soon as every sheep is found
+        let nearest = grid.nearest(Vec3::new(1.0, 0.0, 0.0), 7, i32::MAX);
+        let entities = nearest.iter().map(|(_, e)| e.unwrap().index()).collect::<Vec<_>>();
+        assert_eq!(entities, vec![0, 1, 2]);
+
+        assert!(NeighbourGrid::default().nearest(Vec3::ZERO, 7, i32::MAX).is_empty());
+    }
+}
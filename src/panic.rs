@@ -0,0 +1,165 @@
+//Panic of the flock. Every sheep has a fear level in [0, 1]:
+//  - bark fills fear of sheep around the dog, see sheep::scared_sheeps
+//  - scared sheep infect calm neighbours (NearestSheep), more frightened ones infect faster
+//  - wolf kill is an epicentre, sheep around a new corpse are frightened by distance
+//  - fear decays with time, scared sheep calm down below calm_threshold
+//Calm sheep becomes scared when its fear reaches panic_threshold, so a dense frightened
+//flock turns into a stampede which runs from the epicentre until the panic fades
+
+use bevy::prelude::*;
+
+use crate::{
+    corpse::SpawnCorpse,
+    neighbours::NearestSheep,
+    sheep::{scared_sheeps, IsScared, Sheep, IDLE, SCARED},
+    sheep_behaviour::{SheepBehaviourSet, SheepTransition},
+    GameSet,
+};
+
+pub struct PanicPlugin;
+
+impl Plugin for PanicPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PanicParams>()
+            .register_type::<PanicParams>()
+            .register_type::<Fear>()
+            .add_systems(
+                Update,
+                (corpse_panic, spread_panic, decay_fear)
+                    .chain()
+                    .after(scared_sheeps)
+                    .in_set(SheepBehaviourSet::Update)
+                    .in_set(GameSet::Playing),
+            );
+    }
+}
+
+#[derive(Resource, Reflect, Clone, Debug, PartialEq)]
+#[reflect(Resource, Default)]
+pub struct PanicParams {
+    //Fear per second which a fully scared neighbour passes to a calm sheep
+    pub contagion_rate: f32,
+    //Only neighbours closer than this are heard
+    pub contagion_radius: f32,
+    pub corpse_radius: f32,
+    //Fear at the corpse, falls to zero at corpse_radius
+    pub corpse_fear: f32,
+    //Fear lost per second
+    pub decay_rate: f32,
+    pub panic_threshold: f32,
+    pub calm_threshold: f32,
+}
+
+impl Default for PanicParams {
+    fn default() -> Self {
+        Self {
+            contagion_rate: 0.5,
+            contagion_radius: 2.0,
+            corpse_radius: 8.0,
+            corpse_fear: 1.5,
+            decay_rate: 0.3,
+            panic_threshold: 0.5,
+            calm_threshold: 0.1,
+        }
+    }
+}
+
+/// Fear level of a sheep and the point it runs from
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component, Default)]
+pub struct Fear {
+    pub level: f32,
+    pub source: Vec3,
+}
+
+impl Fear {
+    /// Raise fear to `level` with a new source, lower fear is kept as is
+    pub fn frighten(&mut self, level: f32, source: Vec3) {
+        if level > self.level {
+            self.level = level.min(1.0);
+            self.source = source;
+        }
+    }
+}
+
+fn corpse_panic(
+    params: Res<PanicParams>,
+    mut corpses: EventReader<SpawnCorpse>,
+    mut sheep: Query<(Entity, &Transform, &mut Fear, Option<&IsScared>), With<Sheep>>,
+    mut transitions: EventWriter<SheepTransition>,
+) {
+    for corpse in corpses.read() {
+        for (e, t, mut fear, scared) in sheep.iter_mut() {
+            let distance = t.translation.distance(corpse.position);
+            if distance >= params.corpse_radius {
+                continue;
+            }
+            let level = params.corpse_fear * (1.0 - distance / params.corpse_radius);
+            fear.frighten(level, corpse.position);
+            if scared.is_none() && fear.level >= params.panic_threshold {
+                transitions.send(SheepTransition { sheep: e, to: SCARED });
+            }
+        }
+    }
+}
+
+//Raises are collected first and applied after, so the order of sheep does not matter
+fn spread_panic(
+    time: Res<Time>,
+    params: Res<PanicParams>,
+    mut sheep: Query<(Entity, &Transform, &mut Fear, &NearestSheep, Option<&IsScared>), With<Sheep>>,
+    mut transitions: EventWriter<SheepTransition>,
+) {
+    let dt = time.delta_seconds();
+    let mut raised = Vec::new();
+
+    for (e, t, _, nearest, scared) in sheep.iter() {
+        if scared.is_some() {
+            continue;
+        }
+        let mut panic = 0.0;
+        let mut source = Vec3::ZERO;
+        for (pos, n_e) in nearest.0.iter() {
+            let Some(n_e) = *n_e else {
+                continue;
+            };
+            if n_e == e || pos.distance(t.translation) > params.contagion_radius {
+                continue;
+            }
+            if let Ok((_, _, n_fear, _, Some(_))) = sheep.get(n_e) {
+                panic += n_fear.level;
+                source += n_fear.source * n_fear.level;
+            }
+        }
+        if panic > 0.0 {
+            raised.push((e, panic * params.contagion_rate * dt, source / panic));
+        }
+    }
+
+    for (e, raise, source) in raised {
+        if let Ok((_, _, mut fear, _, _)) = sheep.get_mut(e) {
+            fear.level = (fear.level + raise).min(1.0);
+            fear.source = source;
+            if fear.level >= params.panic_threshold {
+                transitions.send(SheepTransition { sheep: e, to: SCARED });
+            }
+        }
+    }
+}
+
+fn decay_fear(
+    time: Res<Time>,
+    params: Res<PanicParams>,
+    mut sheep: Query<(Entity, &mut Fear, Option<&IsScared>), With<Sheep>>,
+    mut transitions: EventWriter<SheepTransition>,
+) {
+    let decay = params.decay_rate * time.delta_seconds();
+    for (e, mut fear, scared) in sheep.iter_mut() {
+        if fear.level > 0.0 {
+            fear.level = (fear.level - decay).max(0.0);
+        }
+        if scared.is_some() && fear.level < params.calm_threshold {
+            transitions.send(SheepTransition { sheep: e, to: IDLE });
+        }
+    }
+}
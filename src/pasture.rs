@@ -0,0 +1,218 @@
+//Grass on the pasture. Level is covered by a grid of cells with grass amount in [0, 1]
+//Feeding sheep eat the grass of their cell, which reduces their hunger. Empty cell ends feeding
+//Grass regrows slowly, hungry sheep drift to richer cells (see FlockingParams::grass_weight),
+//so a grazed out safe area makes the flock wander away on its own
+
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+
+use crate::{
+    sheep::{IdleFeeding, Sheep, IDLE},
+    sheep_behaviour::{SheepBehaviourSet, SheepTransition},
+    sheep_traits::SheepTraits,
+    test_level::{self, LevelSize},
+    GameSet, GameState, SimSet,
+};
+
+const CELL_SIZE: f32 = 2.0;
+//Grid covers the pasture and a border around it, in level sizes
+const PASTURE_EXTENT: f32 = 1.5;
+//Grass eaten by one sheep per second
+const GRAZE_RATE: f32 = 0.01;
+//Hunger removed by one unit of grass
+const HUNGER_PER_GRASS: f32 = 10.0;
+//Sheep stops feeding when there is less grass in its cell
+const EMPTY_GRASS: f32 = 0.05;
+//Empty cell grows back in 200 seconds
+const REGROW_RATE: f32 = 0.005;
+//Sheep looks for richer grass this many cells around
+const SEARCH_CELLS: i32 = 5;
+//Directions to richer grass change slowly, no need to update them every frame
+const RICHER_UPDATE_PERIOD: f32 = 1.0;
+
+pub struct PasturePlugin;
+
+impl Plugin for PasturePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Pasture>()
+            .add_systems(
+                OnEnter(GameState::Playing),
+                setup_pasture.after(test_level::setup),
+            )
+            .add_systems(
+                Update,
+                graze
+                    .in_set(SheepBehaviourSet::Update)
+                    .in_set(GameSet::Playing),
+            )
+            .add_systems(
+                Update,
+                regrow
+                    .before(SheepBehaviourSet::Update)
+                    .in_set(GameSet::Playing)
+                    .in_set(SimSet::Sheep),
+            );
+    }
+}
+
+pub struct PastureVisualsPlugin;
+
+impl Plugin for PastureVisualsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, draw_pasture.in_set(GameSet::Playing));
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Pasture {
+    half_size: f32,
+    width: i32,
+    grass: Vec<f32>,
+    //Direction to richer grass for each cell, updated together with regrowth
+    richer: Vec<Vec2>,
+    since_richer_update: f32,
+}
+
+impl Pasture {
+    pub fn new(level_size: f32) -> Self {
+        let half_size = level_size * PASTURE_EXTENT;
+        let width = (half_size * 2.0 / CELL_SIZE).ceil() as i32;
+        let cells = (width * width) as usize;
+        Self {
+            half_size,
+            width,
+            grass: vec![1.0; cells],
+            richer: vec![Vec2::ZERO; cells],
+            since_richer_update: 0.0,
+        }
+    }
+
+    fn cell(&self, pos: Vec3) -> IVec2 {
+        IVec2::new(
+            ((pos.x + self.half_size) / CELL_SIZE).floor() as i32,
+            ((pos.z + self.half_size) / CELL_SIZE).floor() as i32,
+        )
+    }
+
+    fn index(&self, cell: IVec2) -> Option<usize> {
+        let inside = cell.x >= 0 && cell.y >= 0 && cell.x < self.width && cell.y < self.width;
+        inside.then(|| (cell.y * self.width + cell.x) as usize)
+    }
+
+    fn cell_center(&self, cell: IVec2) -> Vec3 {
+        Vec3::new(
+            (cell.x as f32 + 0.5) * CELL_SIZE - self.half_size,
+            0.0,
+            (cell.y as f32 + 0.5) * CELL_SIZE - self.half_size,
+        )
+    }
+
+    /// Grass in the cell under `pos`, nothing grows outside the grid
+    pub fn grass_at(&self, pos: Vec3) -> f32 {
+        self.index(self.cell(pos)).map_or(0.0, |i| self.grass[i])
+    }
+
+    /// Eat up to `amount` of grass under `pos`, returns eaten amount
+    pub fn graze(&mut self, pos: Vec3, amount: f32) -> f32 {
+        let Some(i) = self.index(self.cell(pos)) else {
+            return 0.0;
+        };
+        let eaten = self.grass[i].min(amount);
+        self.grass[i] -= eaten;
+        eaten
+    }
+
+    /// Direction to richer grass around `pos`, longer when the difference is larger
+    pub fn richer_direction(&self, pos: Vec3) -> Vec3 {
+        let dir = self.index(self.cell(pos)).map_or_else(
+            //Outside of the grid all grass is back on the pasture
+            || -Vec2::new(pos.x, pos.z).normalize_or_zero(),
+            |i| self.richer[i],
+        );
+        Vec3::new(dir.x, 0.0, dir.y)
+    }
+
+    fn regrow(&mut self, dt: f32) {
+        for grass in self.grass.iter_mut() {
+            *grass = (*grass + REGROW_RATE * dt).min(1.0);
+        }
+    }
+
+    //Weighted sum of directions to the cells around, missing cells have no grass
+    fn update_richer(&mut self) {
+        for y in 0..self.width {
+            for x in 0..self.width {
+                let cell = IVec2::new(x, y);
+                let own = self.grass[self.index(cell).unwrap()];
+                let mut sum = Vec2::ZERO;
+                let mut count = 0.0;
+                for dy in -SEARCH_CELLS..=SEARCH_CELLS {
+                    for dx in -SEARCH_CELLS..=SEARCH_CELLS {
+                        if dx == 0 && dy == 0 {
+                            continue;
+                        }
+                        let offset = IVec2::new(dx, dy);
+                        let grass = self.index(cell + offset).map_or(0.0, |i| self.grass[i]);
+                        sum += offset.as_vec2().normalize() * (grass - own);
+                        count += 1.0;
+                    }
+                }
+                let i = self.index(cell).unwrap();
+                self.richer[i] = (sum / count).clamp_length_max(1.0);
+            }
+        }
+    }
+}
+
+fn setup_pasture(mut commands: Commands, level_size: Res<LevelSize>) {
+    commands.insert_resource(Pasture::new(level_size.0));
+}
+
+fn regrow(time: Res<Time>, mut pasture: ResMut<Pasture>) {
+    pasture.regrow(time.delta_seconds());
+
+    pasture.since_richer_update += time.delta_seconds();
+    if pasture.since_richer_update >= RICHER_UPDATE_PERIOD {
+        pasture.since_richer_update = 0.0;
+        pasture.update_richer();
+    }
+}
+
+fn graze(
+    time: Res<Time>,
+    mut pasture: ResMut<Pasture>,
+    mut sheep: Query<(Entity, &Transform, &mut SheepTraits), (With<Sheep>, With<IdleFeeding>)>,
+    mut transitions: EventWriter<SheepTransition>,
+) {
+    let amount = GRAZE_RATE * time.delta_seconds();
+    for (e, t, mut traits) in sheep.iter_mut() {
+        let eaten = pasture.graze(t.translation, amount);
+        traits.hunger = (traits.hunger - eaten * HUNGER_PER_GRASS).max(0.0);
+
+        if pasture.grass_at(t.translation) < EMPTY_GRASS {
+            transitions.send(SheepTransition { sheep: e, to: IDLE });
+        }
+    }
+}
+
+//Grazed cells are drawn darker, full ones are not drawn
+fn draw_pasture(mut gizmos: Gizmos, pasture: Res<Pasture>) {
+    for y in 0..pasture.width {
+        for x in 0..pasture.width {
+            let cell = IVec2::new(x, y);
+            let grass = pasture.grass[pasture.index(cell).unwrap()];
+            if grass > 0.9 {
+                continue;
+            }
+            let mut pos = pasture.cell_center(cell);
+            pos.y = 0.002;
+            gizmos.rect(
+                pos,
+                Quat::from_euler(EulerRot::XYZ, PI / 2.0, 0.0, 0.0),
+                Vec2::splat(CELL_SIZE * 0.9),
+                Color::rgba(0.4, 0.3, 0.1, 1.0 - grass),
+            );
+        }
+    }
+}
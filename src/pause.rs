@@ -0,0 +1,189 @@
+//Pause during GameState::Playing. Pause is a separate state, so OnEnter/OnExit(Playing) are not triggered by it
+//Virtual time is paused as well: storyteller timer, EpisodeTime, wolf eating and corpse decay are all based on it
+
+use bevy::prelude::*;
+
+use crate::{GameState, GameStuff};
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_state::<PauseState>()
+            .add_event::<RestartLevel>()
+            .add_systems(OnEnter(PauseState::Paused), pause_time)
+            .add_systems(OnExit(PauseState::Paused), resume_time)
+            .add_systems(OnExit(GameState::Playing), unpause)
+            .add_systems(
+                Update,
+                restart_level
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(on_event::<RestartLevel>()),
+            );
+    }
+}
+
+/// Pause menu with Escape
+pub struct PauseMenuPlugin;
+
+impl Plugin for PauseMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            toggle_pause.run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(OnEnter(PauseState::Paused), setup_pause_menu)
+        .add_systems(OnExit(PauseState::Paused), cleanup_pause_menu)
+        .add_systems(
+            Update,
+            pause_menu_system.run_if(in_state(PauseState::Paused)),
+        );
+    }
+}
+
+#[derive(States, Default, Clone, Eq, PartialEq, Debug, Hash)]
+pub enum PauseState {
+    #[default]
+    Running,
+    Paused,
+}
+
+/// Respawn current level from scratch, same as new enter to GameState::Playing
+#[derive(Event)]
+pub struct RestartLevel;
+
+fn pause_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+fn resume_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+fn unpause(mut next_state: ResMut<NextState<PauseState>>) {
+    next_state.set(PauseState::Running);
+}
+
+fn restart_level(world: &mut World) {
+    world.run_schedule(OnExit(GameState::Playing));
+
+    let game_stuff = world
+        .query_filtered::<Entity, With<GameStuff>>()
+        .iter(world)
+        .collect::<Vec<_>>();
+    for entity in game_stuff {
+        world.entity_mut(entity).despawn_recursive();
+    }
+
+    world.run_schedule(OnEnter(GameState::Playing));
+}
+
+#[derive(Component)]
+struct PauseMenu;
+
+#[derive(Component)]
+enum PauseButton {
+    Resume,
+    Restart,
+    Menu,
+}
+
+fn toggle_pause(
+    input: Res<Input<KeyCode>>,
+    state: Res<State<PauseState>>,
+    mut next_state: ResMut<NextState<PauseState>>,
+) {
+    if !input.just_pressed(KeyCode::Escape) {
+        return;
+    }
+
+    match state.get() {
+        PauseState::Running => next_state.set(PauseState::Paused),
+        PauseState::Paused => next_state.set(PauseState::Running),
+    }
+}
+
+fn setup_pause_menu(mut commands: Commands) {
+    let mut text_style = TextStyle::default();
+    text_style.font_size = 24.0;
+
+    commands
+        .spawn((
+            PauseMenu,
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(10.0),
+                    ..default()
+                },
+                background_color: Color::rgba(0.15, 0.15, 0.15, 0.7).into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section("Paused", text_style.clone()));
+
+            for (button, text) in [
+                (PauseButton::Resume, "Resume"),
+                (PauseButton::Restart, "Restart"),
+                (PauseButton::Menu, "Menu"),
+            ] {
+                parent
+                    .spawn((
+                        button,
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Px(150.0),
+                                height: Val::Px(50.0),
+                                border: UiRect::all(Val::Px(5.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            border_color: BorderColor(Color::WHITE),
+                            background_color: BackgroundColor(Color::BLACK),
+                            ..default()
+                        },
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(text, text_style.clone()));
+                    });
+            }
+        });
+}
+
+fn cleanup_pause_menu(mut commands: Commands, query: Query<Entity, With<PauseMenu>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn pause_menu_system(
+    mut next_state: ResMut<NextState<GameState>>,
+    mut next_pause: ResMut<NextState<PauseState>>,
+    mut restart: EventWriter<RestartLevel>,
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &PauseButton),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, mut color, button) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => match button {
+                PauseButton::Resume => next_pause.set(PauseState::Running),
+                PauseButton::Restart => restart.send(RestartLevel),
+                PauseButton::Menu => next_state.set(GameState::Menu),
+            },
+            Interaction::Hovered => {
+                *color = BackgroundColor(Color::DARK_GRAY);
+            }
+            Interaction::None => {
+                *color = BackgroundColor(Color::BLACK);
+            }
+        }
+    }
+}
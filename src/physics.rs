@@ -1,6 +1,6 @@
 use bevy::prelude::*;
 
-use crate::GameSet;
+use crate::{GameSet, SimSet};
 
 pub struct PhysicsPlugin;
 
@@ -12,7 +12,8 @@ impl Plugin for PhysicsPlugin {
             Update,
             (walk_system, apply_velocity)
                 .chain()
-                .in_set(GameSet::Playing),
+                .in_set(GameSet::Playing)
+                .in_set(SimSet::Physics),
         );
     }
 }
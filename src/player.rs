@@ -1,17 +1,18 @@
 use std::f32::consts::PI;
 
 use bevy::{
-    input::mouse::MouseWheel,
+    input::{mouse::MouseWheel, InputSystem},
     pbr::{CascadeShadowConfig, CascadeShadowConfigBuilder},
     prelude::*,
     window::PrimaryWindow, audio::{Volume, PlaybackMode},
 };
 
 use crate::{
+    controls::{ActionState, ControlAction},
     get_sprite_rotation,
     physics::Velocity,
     sprite_material::{create_plane_mesh, SpriteExtension, SpriteMaterial},
-    GameStuff, GameSet, auto_anim::{AnimSet, AnimRange, AutoAnimPlugin, AutoAnim},
+    GameStuff, GameSet, SimSet, VisualsSet, auto_anim::{AnimSet, AnimRange, AutoAnimPlugin, AutoAnim},
 };
 
 const DOG_PATH: &str = "test/dog.png";
@@ -25,6 +26,8 @@ pub const STAMINA_DECREASE: f32 = 1.0 / 5.0 + STAMINA_INCREASE;
 
 pub const DOG_RUN_PATH: &str = "audio/running-in-grass.ogg";
 pub const BARK_PATH: &str = "audio/barking.ogg";
+//Distance between ears of the spatial audio listener
+const DOG_EAR_GAP: f32 = 1.0;
 
 pub struct PlayerPlugin;
 
@@ -40,17 +43,32 @@ impl Plugin for PlayerPlugin {
         app.add_event::<SpawnPlayer>()
             .add_event::<Bark>()
             .add_state::<MovementStyle>()
-            .add_systems(Update, spawn_player_by_event.in_set(GameSet::Playing))
+            .init_resource::<MouseTarget>()
+            .configure_sets(PreUpdate, PlayerInputSet.after(InputSystem))
             .add_systems(
                 Update,
-                player_movemnt_by_wasd.run_if(in_state(MovementStyle::WASD)),
-            )
-            .add_systems(
-                Update,
-                player_movemnt_by_mouse.run_if(in_state(MovementStyle::Mouse)),
-            )
-            .add_systems(Update, (change_movement_style, bark).in_set(GameSet::Playing))
-            .add_systems(Update, (set_cam_distance, camera_movement, stamina_increse).in_set(GameSet::Playing))
+                (
+                    spawn_player_by_event.in_set(GameSet::Playing),
+                    change_movement_style.in_set(GameSet::Playing),
+                    player_movemnt_by_wasd.run_if(in_state(MovementStyle::WASD)),
+                    player_movemnt_by_mouse.run_if(in_state(MovementStyle::Mouse)),
+                    (bark, stamina_increse).chain().in_set(GameSet::Playing),
+                )
+                    .chain()
+                    .in_set(SimSet::Player),
+            );
+    }
+}
+
+pub struct PlayerVisualsPlugin;
+
+impl Plugin for PlayerVisualsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CameraZoom>()
+            .add_systems(PreUpdate, update_mouse_target.in_set(PlayerInputSet))
+            .add_systems(Update, attach_dog_visuals.in_set(VisualsSet))
+            .add_systems(Update, (set_cam_distance, camera_movement).in_set(GameSet::Playing))
+            .add_systems(Update, (dog_footsteps, bark_sound).in_set(GameSet::Playing))
             .add_plugins(AutoAnimPlugin::<PlayerAnim>::default())
             .add_systems(Update, set_anim_state.in_set(GameSet::Playing));
     }
@@ -65,9 +83,9 @@ pub struct Stamina {
 fn change_movement_style(
     mut next_state: ResMut<NextState<MovementStyle>>,
     current_state: Res<State<MovementStyle>>,
-    keyboard_input: Res<Input<KeyCode>>,
+    actions: Res<ActionState>,
 ) {
-    if keyboard_input.just_pressed(KeyCode::Tab) {
+    if actions.just_pressed(ControlAction::ToggleMovementStyle) {
         if *current_state.get() == MovementStyle::Mouse {
             next_state.set(MovementStyle::WASD);
         } else {
@@ -122,9 +140,21 @@ impl AnimSet for PlayerAnim {
 #[derive(Component)]
 pub struct Player;
 
+/// Point on the ground under the cursor. Filled from window by presentation or from a replay file
+#[derive(Resource, Default)]
+pub struct MouseTarget(pub Option<Vec3>);
+
+/// Systems which write player input resources (ActionState, MouseTarget) in PreUpdate
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub struct PlayerInputSet;
+
 #[derive(Component)]
 pub struct CameraDistance(f32);
 
+/// Multiply camera distance, for example by pinch on a touch screen. Mouse wheel does the same
+#[derive(Event)]
+pub struct CameraZoom(pub f32);
+
 #[derive(Component)]
 pub struct Dog;
 
@@ -154,30 +184,16 @@ pub struct FootstepsSource;
 fn spawn_player_by_event(
     mut commands: Commands,
     mut event_reader: EventReader<SpawnPlayer>,
-    asset_server: Res<AssetServer>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
     for event in event_reader.read() {
-        let plane = meshes.add(create_plane_mesh());
-        let material = materials.add(
-            StandardMaterial {
-                base_color_texture: Some(asset_server.load(DOG_PATH)),
-                alpha_mode: AlphaMode::Opaque,
-                ..default()
-            });
-
         info!("Spawn player at {:?}", event.position);
 
         commands.spawn((
-            PbrBundle {
-                mesh: plane.clone(),
-                material: material.clone(),
-                transform: Transform::from_translation(event.position)
+            SpatialBundle::from_transform(
+                Transform::from_translation(event.position)
                     .with_rotation(get_sprite_rotation())
                     .with_scale(Vec3::new(1.0, 1.0, 1.0) * 2.0),
-                ..default()
-            },
+            ),
             Player,
             Dog,
             Velocity::default(),
@@ -186,11 +202,38 @@ fn spawn_player_by_event(
                 value: 1.0,
                 blocked: false
             },
+        ));
+    }
+
+    event_reader.clear();
+}
+
+fn attach_dog_visuals(
+    mut commands: Commands,
+    dogs: Query<Entity, Added<Dog>>,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for dog in dogs.iter() {
+        let plane = meshes.add(create_plane_mesh());
+        let material = materials.add(
+            StandardMaterial {
+                base_color_texture: Some(asset_server.load(DOG_PATH)),
+                alpha_mode: AlphaMode::Opaque,
+                ..default()
+            });
+
+        commands.entity(dog).insert((
+            plane,
+            material,
             AutoAnim {
                 set: PlayerAnim::Idle,
                 timer: Timer::from_seconds(0.1, TimerMode::Repeating),
                 current_frame: 0,
-            }
+            },
+            //Sheep bleats and wolf kills are heard from the dog
+            SpatialListener::new(DOG_EAR_GAP),
         )).with_children(|parent| {
             parent.spawn((
                 DogBarkSource,
@@ -219,37 +262,33 @@ fn spawn_player_by_event(
                 }
             ));
         });
-    }
-
-    commands.insert_resource(DogSounds {
-        bark: asset_server.load(BARK_PATH),
-        run: asset_server.load(DOG_RUN_PATH),
-    });
 
-    event_reader.clear();
+        commands.insert_resource(DogSounds {
+            bark: asset_server.load(BARK_PATH),
+            run: asset_server.load(DOG_RUN_PATH),
+        });
+    }
 }
 
-fn player_movemnt_by_mouse(
-    mut player_query: Query<(&Transform, &mut Velocity, &mut Stamina), With<Player>>,
-    time: Res<Time>,
+fn update_mouse_target(
+    mut mouse_target: ResMut<MouseTarget>,
+    player_query: Query<&Transform, With<Player>>,
     q_window: Query<&Window, With<PrimaryWindow>>,
     q_camera: Query<(&Camera, &GlobalTransform)>,
-    input: Res<Input<KeyCode>>,
-    mut footstep_source: Query<&mut AudioSink, With<FootstepsSource>>,
 ) {
-    let Ok((transform, mut vel, mut stamine)) = player_query.get_single_mut() else {
+    mouse_target.0 = None;
+
+    let Ok(transform) = player_query.get_single() else {
         return;
     };
-
-    let Ok(mut footstep) = footstep_source.get_single_mut() else {
+    let Ok((camera, camera_transform)) = q_camera.get_single() else {
+        return;
+    };
+    let Ok(window) = q_window.get_single() else {
         return;
     };
-
-    let (camera, camera_transform) = q_camera.single();
-    let window = q_window.single();
 
     let Some(cursor_position) = window.cursor_position() else {
-        // if the cursor is not inside the window, we can't do anything
         return;
     };
 
@@ -262,7 +301,25 @@ fn player_movemnt_by_mouse(
         return;
     };
 
-    let mut use_stamina = input.pressed(KeyCode::ShiftLeft);
+    mouse_target.0 = Some(ray.get_point(distance));
+}
+
+fn player_movemnt_by_mouse(
+    mut player_query: Query<(&Transform, &mut Velocity, &mut Stamina), With<Player>>,
+    time: Res<Time>,
+    mouse_target: Res<MouseTarget>,
+    actions: Res<ActionState>,
+) {
+    let Ok((transform, mut vel, mut stamine)) = player_query.get_single_mut() else {
+        return;
+    };
+
+    let Some(globel_cursor) = mouse_target.0 else {
+        // if the cursor is not inside the window, we can't do anything
+        return;
+    };
+
+    let mut use_stamina = actions.pressed(ControlAction::Sprint);
     if stamine.blocked {
         use_stamina = false;
     }
@@ -276,8 +333,6 @@ fn player_movemnt_by_mouse(
 
     let speed_k = if use_stamina { 1.0 } else {RUN_K};
 
-    let globel_cursor = ray.get_point(distance);
-
     let speed: f32 = DOG_SPEED * speed_k;
     let accel: f32 = DOG_ACCELERATION;
 
@@ -291,21 +346,14 @@ fn player_movemnt_by_mouse(
     vel.0 += dspeed.normalize_or_zero() * accel * time.delta_seconds();
 
     vel.0 = vel.0.clamp_length_max(speed);
-
-    if vel.0.length() > 1.0 {
-        footstep.play();
-    } else {
-        footstep.pause();
-    }
 }
 
 pub fn bark(
     player_query: Query<&Transform, With<Player>>,
-    input: Res<Input<KeyCode>>,
+    actions: Res<ActionState>,
     mut event_writer: EventWriter<Bark>,
     mut stamina : Query<&mut Stamina>,
     time : Res<Time>,
-    bark_sink : Query<&AudioSink, With<DogBarkSource>>,
 ) {
     let Ok(bark) = player_query.get_single() else {
         return;
@@ -317,9 +365,7 @@ pub fn bark(
 
     let mut radius = 10.;
 
-    let mut play_bark = false;
-
-    if input.pressed(KeyCode::ControlLeft) && !stamina.blocked {
+    if actions.pressed(ControlAction::BigBark) && !stamina.blocked {
         radius *= 1.5;
         stamina.value -= STAMINA_DECREASE * 3.0 * time.delta_seconds();
 
@@ -331,68 +377,66 @@ pub fn bark(
             radius: radius,
             position: bark.translation,
         });
-        play_bark = true;
     }
 
-    if input.pressed(KeyCode::Space) {
+    if actions.pressed(ControlAction::Bark) {
         event_writer.send(Bark {
             radius: radius,
             position: bark.translation,
         });
-        play_bark = true;
     }
+}
 
-    if play_bark {
-        let Ok(bark) = bark_sink.get_single() else {
-            warn!("Could not get bark source");
-            return;
-        };
+fn bark_sound(
+    mut barks: EventReader<Bark>,
+    bark_sink : Query<&AudioSink, With<DogBarkSource>>,
+) {
+    let Ok(bark) = bark_sink.get_single() else {
+        return;
+    };
+
+    if barks.read().next().is_some() {
         bark.play();
     } else {
-        let Ok(bark) = bark_sink.get_single() else {
-            warn!("Could not get bark source");
-            return;
-        };
         bark.pause();
     }
+    barks.clear();
 }
 
-fn player_movemnt_by_wasd(
-    mut player_query: Query<(&mut Velocity, &mut Stamina), With<Player>>,
-    input: Res<Input<KeyCode>>,
-    time: Res<Time>,
-    mut footstep_source: Query<&mut AudioSink, With<FootstepsSource>>
+fn dog_footsteps(
+    dog: Query<&Velocity, With<Dog>>,
+    footstep_source: Query<&AudioSink, With<FootstepsSource>>,
 ) {
-    let Ok((mut player, mut stamina)) = player_query.get_single_mut() else {
+    let Ok(vel) = dog.get_single() else {
         return;
     };
 
-    let Ok(mut footstep) = footstep_source.get_single_mut() else {
-        warn!("Could not get footstep source");
+    let Ok(footstep) = footstep_source.get_single() else {
         return;
     };
 
-    let accel = DOG_ACCELERATION;
-
-    let mut dir = Vec3::ZERO;
-
-    if input.pressed(KeyCode::W) {
-        dir += Vec3::new(0.0, 0.0, -1.0);
+    if vel.0.length() > 1.0 {
+        footstep.play();
+    } else {
+        footstep.pause();
     }
+}
 
-    if input.pressed(KeyCode::S) {
-        dir += Vec3::new(0.0, 0.0, 1.0);
-    }
+fn player_movemnt_by_wasd(
+    mut player_query: Query<(&mut Velocity, &mut Stamina), With<Player>>,
+    actions: Res<ActionState>,
+    time: Res<Time>,
+) {
+    let Ok((mut player, mut stamina)) = player_query.get_single_mut() else {
+        return;
+    };
 
-    if input.pressed(KeyCode::A) {
-        dir += Vec3::new(-1.0, 0.0, 0.0);
-    }
+    let accel = DOG_ACCELERATION;
 
-    if input.pressed(KeyCode::D) {
-        dir += Vec3::new(1.0, 0.0, 0.0);
-    }
+    //Forward on the move axis is -z on the ground
+    let dir = Vec3::new(actions.move_axis.x, 0.0, -actions.move_axis.y);
 
-    let mut use_stamina = input.pressed(KeyCode::ShiftLeft);
+    let mut use_stamina = actions.pressed(ControlAction::Sprint);
 
     if stamina.blocked {
         use_stamina = false;
@@ -411,9 +455,8 @@ fn player_movemnt_by_wasd(
         DOG_SPEED
     };
 
-    dir = dir.normalize_or_zero();
-
-    let target_speed = dir * speed;
+    //Half-tilted stick gives slower walk
+    let target_speed = dir.clamp_length_max(1.0) * speed;
 
     let dspeed = target_speed - player.0;
 
@@ -422,12 +465,6 @@ fn player_movemnt_by_wasd(
     player.0 += dspeed.normalize_or_zero() * accel * time.delta_seconds();
 
     player.0 = player.0.clamp_length_max(speed);
-
-    if player.0.length() > 1.0 {
-        footstep.play();
-    } else {
-        footstep.pause();
-    }
 }
 
 fn camera_movement(
@@ -435,6 +472,7 @@ fn camera_movement(
     player_query: Query<&Transform, (With<Player>, Without<Camera>)>,
     time: Res<Time>,
     mut scroll_evr: EventReader<MouseWheel>,
+    mut zoom_evr: EventReader<CameraZoom>,
     mut sun: Query<&mut CascadeShadowConfig>,
 ) {
     let Ok((mut camera, mut distance)) = camera_query.get_single_mut() else {
@@ -448,12 +486,22 @@ fn camera_movement(
         return;
     };
 
-    for ev in scroll_evr.read() {
-        let delta = ev.y;
-        if delta < 0.0 {
-            distance.0 *= 1.1;
-        } else if delta > 0.0 {
-            distance.0 /= 1.1;
+    let scroll_zoom = scroll_evr.read().filter_map(|ev| {
+        if ev.y < 0.0 {
+            Some(1.1)
+        } else if ev.y > 0.0 {
+            Some(1.0 / 1.1)
+        } else {
+            None
+        }
+    });
+    let zoom = scroll_zoom
+        .chain(zoom_evr.read().map(|ev| ev.0))
+        .collect::<Vec<_>>();
+
+    if !zoom.is_empty() {
+        for k in zoom {
+            distance.0 *= k;
         }
 
         distance.0 = distance.0.clamp(10.0, 150.0);
@@ -493,15 +541,16 @@ fn set_cam_distance(
 
 fn set_anim_state(
     mut player : Query<(&mut AutoAnim<PlayerAnim>, &Velocity, &mut Transform)>,
-    input: Res<Input<KeyCode>>
+    actions: Res<ActionState>,
 ) {
     let Ok((mut player, vel, mut t)) = player.get_single_mut() else {
         return;
     };
 
-    let moving = input.any_pressed([KeyCode::W, KeyCode::S, KeyCode::A, KeyCode::D]);
-    let barking = input.pressed(KeyCode::Space);
-    let big_bark = input.pressed(KeyCode::ControlLeft);
+    //Same threshold as footsteps sound
+    let moving = vel.0.length() > 1.0;
+    let barking = actions.pressed(ControlAction::Bark);
+    let big_bark = actions.pressed(ControlAction::BigBark);
 
     if big_bark {
         player.set = PlayerAnim::BigBark;
@@ -0,0 +1,638 @@
+//Records player input of a level into a small file and plays it back
+//Record: cargo run -- --record session.rpl
+//Replay: cargo run -- --replay session.rpl (or the same args for examples/headless_level.rs)
+//Every level played with --record goes to its own file: session.rpl, session-2.rpl, session-3.rpl and so on
+//Replay restores the level seed, difficulty, level with the carried flock, frame deltas, player actions and mouse target,
+//so the level goes the same way as in the recorded session
+
+use std::{
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use bevy::{
+    input::InputSystem,
+    prelude::*,
+    time::{TimeSystem, TimeUpdateStrategy},
+};
+
+use crate::{
+    campaign::{self, Campaign, CampaignSet},
+    controls::{ActionState, ControlAction},
+    difficulty::{Difficulty, DifficultyPreset},
+    game_rng::GameRng,
+    level::{CurrentLevel, LevelDescription},
+    player::{MouseTarget, PlayerInputSet},
+    pause::PauseState,
+    GameState,
+};
+
+const MAGIC: &[u8; 4] = b"SHRP";
+//Version 2: player actions instead of raw keys
+//Version 3: difficulty in the header
+//Version 4: night and level with the carried flock in the header
+const VERSION: u8 = 4;
+
+//Per frame flags. Delta, move axis and mouse target are written only when they change.
+//Bit index in the buttons byte is the position in ControlAction::BUTTONS
+const FLAG_DELTA: u8 = 1 << 0;
+const FLAG_MOUSE: u8 = 1 << 1;
+const FLAG_MOUSE_CHANGED: u8 = 1 << 2;
+const FLAG_MOVE: u8 = 1 << 3;
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(path) = arg_value("--replay") {
+            match Replay::load(&path) {
+                Ok(replay) => {
+                    info!(
+                        "Replay {:?}: night {} ({}), seed {}, {} difficulty, {} frames",
+                        path,
+                        replay.night + 1,
+                        replay.level.name,
+                        replay.seed,
+                        replay.difficulty.preset.name(),
+                        replay.frames.len()
+                    );
+                    app.insert_resource(GameRng::new(replay.seed));
+                    //Difficulty and level are restored on every level start, they can be changed after plugins are built
+                    app.add_systems(
+                        OnEnter(GameState::Playing),
+                        (restore_difficulty, restore_level.after(campaign::start_night))
+                            .in_set(CampaignSet),
+                    );
+                    app.insert_resource(ReplayPlayback {
+                        replay,
+                        cursor: 0,
+                        prev_buttons: 0,
+                    });
+                }
+                Err(err) => error!("Failed to load replay {:?}: {}", path, err),
+            }
+        } else if let Some(path) = arg_value("--record") {
+            app.insert_resource(ReplayRecorder {
+                path,
+                replay: None,
+                saved: 0,
+            });
+        }
+
+        app.add_systems(
+            First,
+            feed_frame_delta
+                .before(TimeSystem)
+                .run_if(resource_exists::<ReplayPlayback>()),
+        )
+        .add_systems(
+            PreUpdate,
+            (
+                feed_input.run_if(resource_exists::<ReplayPlayback>()),
+                record_input.run_if(resource_exists::<ReplayRecorder>()),
+            )
+                .chain()
+                .after(InputSystem)
+                .after(PlayerInputSet),
+        )
+        .add_systems(
+            OnEnter(GameState::Playing),
+            start_recording
+                .after(CampaignSet)
+                .run_if(resource_exists::<ReplayRecorder>()),
+        )
+        .add_systems(
+            OnExit(GameState::Playing),
+            save_recording.run_if(resource_exists::<ReplayRecorder>()),
+        );
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
+pub struct FrameInput {
+    pub delta: Duration,
+    pub buttons: u8,
+    pub move_axis: Vec2,
+    pub mouse_target: Option<Vec3>,
+}
+
+impl FrameInput {
+    pub fn pressed(&self, action: ControlAction) -> bool {
+        ControlAction::BUTTONS
+            .iter()
+            .position(|a| *a == action)
+            .is_some_and(|idx| self.buttons & (1 << idx) != 0)
+    }
+
+    pub fn pressed_actions(&self) -> Vec<ControlAction> {
+        ControlAction::BUTTONS
+            .into_iter()
+            .filter(|action| self.pressed(*action))
+            .collect()
+    }
+}
+
+/// Seed, difficulty, level and input of every frame of one level spent in GameState::Playing
+#[derive(Default, Clone, Debug)]
+pub struct Replay {
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    //Campaign night, for the log only
+    pub night: usize,
+    //Level as it was started: flock size and lambs carried from the previous nights are in it
+    pub level: LevelDescription,
+    pub frames: Vec<FrameInput>,
+}
+
+impl Replay {
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read(&mut io::BufReader::new(std::fs::File::open(path)?))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+        self.write(&mut file)?;
+        file.flush()
+    }
+
+    pub fn write(&self, w: &mut impl Write) -> io::Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&[VERSION])?;
+        w.write_all(&self.seed.to_le_bytes())?;
+        write_difficulty(w, &self.difficulty)?;
+        w.write_all(&(self.night as u32).to_le_bytes())?;
+        let level = self
+            .level
+            .to_ron()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        w.write_all(&(level.len() as u32).to_le_bytes())?;
+        w.write_all(level.as_bytes())?;
+        w.write_all(&(self.frames.len() as u32).to_le_bytes())?;
+
+        let mut prev = FrameInput::default();
+        for frame in self.frames.iter() {
+            let mut flags = 0;
+            if frame.delta != prev.delta {
+                flags |= FLAG_DELTA;
+            }
+            if frame.move_axis != prev.move_axis {
+                flags |= FLAG_MOVE;
+            }
+            if frame.mouse_target.is_some() {
+                flags |= FLAG_MOUSE;
+                if frame.mouse_target != prev.mouse_target {
+                    flags |= FLAG_MOUSE_CHANGED;
+                }
+            }
+
+            w.write_all(&[flags, frame.buttons])?;
+            if flags & FLAG_DELTA != 0 {
+                //Virtual time delta is clamped to 250 ms, so u32 nanoseconds is more than enough
+                w.write_all(&(frame.delta.as_nanos() as u32).to_le_bytes())?;
+            }
+            if flags & FLAG_MOVE != 0 {
+                for v in frame.move_axis.to_array() {
+                    w.write_all(&v.to_le_bytes())?;
+                }
+            }
+            if flags & FLAG_MOUSE_CHANGED != 0 {
+                let target = frame.mouse_target.unwrap_or_default();
+                for v in target.to_array() {
+                    w.write_all(&v.to_le_bytes())?;
+                }
+            }
+
+            prev = *frame;
+        }
+
+        Ok(())
+    }
+
+    pub fn read(r: &mut impl Read) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a replay file"));
+        }
+
+        let mut version = [0; 1];
+        r.read_exact(&mut version)?;
+        if version[0] != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported replay version {}", version[0]),
+            ));
+        }
+
+        let mut seed = [0; 8];
+        r.read_exact(&mut seed)?;
+        let difficulty = read_difficulty(r)?;
+        let mut night = [0; 4];
+        r.read_exact(&mut night)?;
+        let mut level_len = [0; 4];
+        r.read_exact(&mut level_len)?;
+        //Level is read in chunks, so a broken length fails on the end of the file instead of a huge allocation
+        let mut level = Vec::new();
+        r.take(u32::from_le_bytes(level_len) as u64).read_to_end(&mut level)?;
+        if level.len() != u32::from_le_bytes(level_len) as usize {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let level = std::str::from_utf8(&level)
+            .ok()
+            .and_then(|text| LevelDescription::from_ron(text).ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "broken level"))?;
+        let mut count = [0; 4];
+        r.read_exact(&mut count)?;
+        let count = u32::from_le_bytes(count) as usize;
+
+        let mut frames = Vec::with_capacity(count);
+        let mut prev = FrameInput::default();
+        for _ in 0..count {
+            let mut head = [0; 2];
+            r.read_exact(&mut head)?;
+            let [flags, buttons] = head;
+
+            let mut frame = FrameInput {
+                delta: prev.delta,
+                buttons,
+                move_axis: prev.move_axis,
+                mouse_target: None,
+            };
+
+            if flags & FLAG_DELTA != 0 {
+                let mut nanos = [0; 4];
+                r.read_exact(&mut nanos)?;
+                frame.delta = Duration::from_nanos(u32::from_le_bytes(nanos) as u64);
+            }
+
+            if flags & FLAG_MOVE != 0 {
+                let mut axis = [0.0; 2];
+                for v in axis.iter_mut() {
+                    let mut bytes = [0; 4];
+                    r.read_exact(&mut bytes)?;
+                    *v = f32::from_le_bytes(bytes);
+                }
+                frame.move_axis = Vec2::from_array(axis);
+            }
+
+            if flags & FLAG_MOUSE != 0 {
+                if flags & FLAG_MOUSE_CHANGED != 0 {
+                    let mut target = [0.0; 3];
+                    for v in target.iter_mut() {
+                        let mut bytes = [0; 4];
+                        r.read_exact(&mut bytes)?;
+                        *v = f32::from_le_bytes(bytes);
+                    }
+                    frame.mouse_target = Some(Vec3::from_array(target));
+                } else {
+                    frame.mouse_target = prev.mouse_target;
+                }
+            }
+
+            frames.push(frame);
+            prev = frame;
+        }
+
+        Ok(Self {
+            seed: u64::from_le_bytes(seed),
+            difficulty,
+            night: u32::from_le_bytes(night) as usize,
+            level,
+            frames,
+        })
+    }
+}
+
+fn write_difficulty(w: &mut impl Write, difficulty: &Difficulty) -> io::Result<()> {
+    let preset = DifficultyPreset::ALL
+        .iter()
+        .position(|preset| *preset == difficulty.preset)
+        .unwrap_or_default();
+    w.write_all(&[preset as u8])?;
+    w.write_all(&difficulty.hardness.to_le_bytes())?;
+    w.write_all(&difficulty.wave_sheep_part.to_le_bytes())?;
+    w.write_all(&(difficulty.max_wolves as u32).to_le_bytes())?;
+    w.write_all(&difficulty.torch_mission_time.to_le_bytes())?;
+    w.write_all(&difficulty.fail_threshold.to_le_bytes())
+}
+
+fn read_difficulty(r: &mut impl Read) -> io::Result<Difficulty> {
+    let mut preset = [0; 1];
+    r.read_exact(&mut preset)?;
+    let preset = *DifficultyPreset::ALL
+        .get(preset[0] as usize)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unknown difficulty preset"))?;
+
+    let mut read_bytes = || -> io::Result<[u8; 4]> {
+        let mut bytes = [0; 4];
+        r.read_exact(&mut bytes)?;
+        Ok(bytes)
+    };
+    let hardness = f32::from_le_bytes(read_bytes()?);
+    let wave_sheep_part = f32::from_le_bytes(read_bytes()?);
+    let max_wolves = u32::from_le_bytes(read_bytes()?) as usize;
+    let torch_mission_time = f32::from_le_bytes(read_bytes()?);
+    let fail_threshold = f32::from_le_bytes(read_bytes()?);
+
+    Ok(Difficulty {
+        preset,
+        hardness,
+        wave_sheep_part,
+        max_wolves,
+        torch_mission_time,
+        fail_threshold,
+    })
+}
+
+#[derive(Resource)]
+pub struct ReplayRecorder {
+    pub path: PathBuf,
+    pub replay: Option<Replay>,
+    //Levels already written, the next one goes to a numbered file
+    pub saved: usize,
+}
+
+impl ReplayRecorder {
+    /// session.rpl for the first level, session-2.rpl for the second one and so on
+    pub fn next_path(&self) -> PathBuf {
+        if self.saved == 0 {
+            return self.path.clone();
+        }
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let mut name = format!("{}-{}", stem, self.saved + 1);
+        if let Some(extension) = self.path.extension() {
+            name = format!("{}.{}", name, extension.to_string_lossy());
+        }
+        self.path.with_file_name(name)
+    }
+}
+
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    pub replay: Replay,
+    pub cursor: usize,
+    //Buttons of the last played frame, just pressed actions are computed against them
+    pub prev_buttons: u8,
+}
+
+impl ReplayPlayback {
+    pub fn finished(&self) -> bool {
+        self.cursor >= self.replay.frames.len()
+    }
+}
+
+fn arg_value(name: &str) -> Option<PathBuf> {
+    let mut args = std::env::args();
+    while let Some(arg) = args.next() {
+        if arg == name {
+            return args.next().map(PathBuf::from);
+        }
+    }
+    None
+}
+
+//Playing systems run in Update, but state transition happens after PreUpdate.
+//So the frame is a playing one if we are already playing or going to enter it right now
+fn is_playing_frame(state: &State<GameState>, next_state: &NextState<GameState>) -> bool {
+    match &next_state.0 {
+        Some(next) => *next == GameState::Playing,
+        None => *state.get() == GameState::Playing,
+    }
+}
+
+fn feed_frame_delta(
+    mut commands: Commands,
+    playback: Res<ReplayPlayback>,
+    state: Res<State<GameState>>,
+    next_state: Res<NextState<GameState>>,
+    pause: Res<State<PauseState>>,
+) {
+    //Paused frames were not recorded, see record_input
+    if playback.finished()
+        || !is_playing_frame(&state, &next_state)
+        || *pause.get() == PauseState::Paused
+    {
+        return;
+    }
+
+    let delta = playback.replay.frames[playback.cursor].delta;
+    commands.insert_resource(TimeUpdateStrategy::ManualDuration(delta));
+}
+
+fn feed_input(
+    mut commands: Commands,
+    mut playback: ResMut<ReplayPlayback>,
+    mut actions: ResMut<ActionState>,
+    mut mouse_target: ResMut<MouseTarget>,
+    state: Res<State<GameState>>,
+    next_state: Res<NextState<GameState>>,
+    pause: Res<State<PauseState>>,
+) {
+    if !is_playing_frame(&state, &next_state) || *pause.get() == PauseState::Paused {
+        return;
+    }
+
+    if playback.finished() {
+        if playback.cursor == playback.replay.frames.len() {
+            info!("Replay finished after {} frames", playback.cursor);
+            commands.insert_resource(TimeUpdateStrategy::Automatic);
+            //Give control back to the player
+            playback.cursor += 1;
+        }
+        return;
+    }
+
+    let frame = playback.replay.frames[playback.cursor];
+    let previous = FrameInput {
+        buttons: playback.prev_buttons,
+        ..default()
+    };
+    playback.cursor += 1;
+    playback.prev_buttons = frame.buttons;
+
+    //Live input was already written by update_action_state this frame, replay overrides all of it
+    actions.move_axis = frame.move_axis;
+    actions.set(frame.pressed_actions(), &previous.pressed_actions());
+    mouse_target.0 = frame.mouse_target;
+}
+
+fn record_input(
+    mut recorder: ResMut<ReplayRecorder>,
+    actions: Res<ActionState>,
+    mouse_target: Res<MouseTarget>,
+    time: Res<Time>,
+    state: Res<State<GameState>>,
+    next_state: Res<NextState<GameState>>,
+    pause: Res<State<PauseState>>,
+) {
+    //Simulation does not run during pause, so paused frames are not a part of the level
+    if !is_playing_frame(&state, &next_state) || *pause.get() == PauseState::Paused {
+        return;
+    }
+
+    let mut buttons = 0;
+    for (idx, action) in ControlAction::BUTTONS.iter().enumerate() {
+        if actions.pressed(*action) {
+            buttons |= 1 << idx;
+        }
+    }
+
+    let frame = FrameInput {
+        delta: time.delta(),
+        buttons,
+        move_axis: actions.move_axis,
+        mouse_target: mouse_target.0,
+    };
+
+    //New recording starts with the first playing frame, level is filled on enter and seed on save
+    recorder
+        .replay
+        .get_or_insert_with(Default::default)
+        .frames
+        .push(frame);
+}
+
+fn restore_level(playback: Res<ReplayPlayback>, mut current_level: ResMut<CurrentLevel>) {
+    current_level.0 = playback.replay.level.clone();
+}
+
+//Runs after the campaign has selected the level, so carried flock and lambs are in it.
+//The first frame of the level is already recorded, see is_playing_frame
+fn start_recording(
+    mut recorder: ResMut<ReplayRecorder>,
+    campaign: Res<Campaign>,
+    current_level: Res<CurrentLevel>,
+) {
+    let replay = recorder.replay.get_or_insert_with(Default::default);
+    replay.night = campaign.night;
+    replay.level = current_level.0.clone();
+}
+
+fn restore_difficulty(playback: Res<ReplayPlayback>, mut difficulty: ResMut<Difficulty>) {
+    *difficulty = playback.replay.difficulty.clone();
+}
+
+fn save_recording(
+    mut recorder: ResMut<ReplayRecorder>,
+    rng: Res<GameRng>,
+    difficulty: Res<Difficulty>,
+) {
+    let Some(mut replay) = recorder.replay.take() else {
+        return;
+    };
+    replay.seed = rng.seed();
+    replay.difficulty = difficulty.clone();
+
+    let path = recorder.next_path();
+    match replay.save(&path) {
+        Ok(()) => {
+            info!(
+                "Saved replay of night {} with {} frames to {:?}",
+                replay.night + 1,
+                replay.frames.len(),
+                path
+            );
+            recorder.saved += 1;
+        }
+        Err(err) => error!("Failed to save replay to {:?}: {}", path, err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(delta_ms: u64, buttons: u8, move_axis: Vec2, mouse_target: Option<Vec3>) -> FrameInput {
+        FrameInput {
+            delta: Duration::from_millis(delta_ms),
+            buttons,
+            move_axis,
+            mouse_target,
+        }
+    }
+
+    fn replay(frames: Vec<FrameInput>) -> Replay {
+        let mut level = LevelDescription::default();
+        level.flock.count = 321;
+        level.flock.lamb_ages = Some(vec![0.25, 0.5]);
+        Replay {
+            seed: 42,
+            difficulty: Difficulty::preset(DifficultyPreset::Hard),
+            night: 2,
+            level,
+            frames,
+        }
+    }
+
+    fn write(replay: &Replay) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        replay.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let mouse = Some(Vec3::new(1.0, 0.0, -2.0));
+        let frames = vec![
+            frame(16, 0, Vec2::ZERO, None),
+            //Delta, move and mouse are all new
+            frame(17, 0b101, Vec2::new(0.5, -1.0), mouse),
+            //Nothing changed, mouse is still there
+            frame(17, 0b101, Vec2::new(0.5, -1.0), mouse),
+            //Mouse is gone, move is back to zero
+            frame(17, 0, Vec2::ZERO, None),
+            //Mouse comes back at the same place
+            frame(33, 0b10, Vec2::ZERO, mouse),
+            frame(33, 0b10, Vec2::ZERO, Some(Vec3::new(3.0, 0.0, 4.0))),
+        ];
+        let original = replay(frames);
+        let read = Replay::read(&mut write(&original).as_slice()).unwrap();
+
+        assert_eq!(read.frames, original.frames);
+        assert_eq!(read.seed, original.seed);
+        assert_eq!(read.difficulty, original.difficulty);
+        assert_eq!(read.night, original.night);
+        assert_eq!(read.level.name, original.level.name);
+        assert_eq!(read.level.flock.count, 321);
+        assert_eq!(read.level.flock.lamb_ages, Some(vec![0.25, 0.5]));
+    }
+
+    #[test]
+    fn unchanged_frames_take_two_bytes() {
+        let moving = frame(16, 1, Vec2::X, Some(Vec3::ONE));
+        let one = write(&replay(vec![moving]));
+        let two = write(&replay(vec![moving, moving]));
+        assert_eq!(two.len() - one.len(), 2);
+
+        //Changed mouse target is written again, 3 floats
+        let moved = FrameInput {
+            mouse_target: Some(Vec3::ZERO),
+            ..moving
+        };
+        let changed = write(&replay(vec![moving, moved]));
+        assert_eq!(changed.len() - one.len(), 2 + 12);
+    }
+
+    #[test]
+    fn unknown_version_is_rejected() {
+        let mut bytes = write(&replay(vec![frame(16, 0, Vec2::ZERO, None)]));
+        bytes[MAGIC.len()] = VERSION + 1;
+        let err = Replay::read(&mut bytes.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let err = Replay::read(&mut &b"NOPE"[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn truncated_input_is_an_error() {
+        let bytes = write(&replay(vec![
+            frame(16, 0, Vec2::ZERO, None),
+            frame(17, 1, Vec2::Y, Some(Vec3::ONE)),
+        ]));
+        for len in 0..bytes.len() {
+            assert!(Replay::read(&mut &bytes[..len]).is_err(), "{len} bytes");
+        }
+    }
+}
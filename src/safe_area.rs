@@ -4,22 +4,29 @@ use std::f32::consts::PI;
 
 use bevy::prelude::*;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
-use crate::sheep::Sheep;
+use crate::{sheep::Sheep, SimSet};
 
 pub struct SafeAreaPlugin;
 
 impl Plugin for SafeAreaPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, draw_safe_area);
-
-        app.add_systems(FixedUpdate, count_sheeps);
+        app.add_systems(FixedUpdate, count_sheeps.in_set(SimSet::SafeArea));
 
         app.init_resource::<SheepCounter>();
     }
 }
 
-#[derive(Component, Clone)]
+pub struct SafeAreaVisualsPlugin;
+
+impl Plugin for SafeAreaVisualsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, draw_safe_area);
+    }
+}
+
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
 pub enum SafeArea {
     Rect { pos: Vec2, size: Vec2 },
     Circle { pos: Vec2, radius: f32 },
@@ -65,8 +72,7 @@ impl SafeArea {
         }
     }
 
-    pub fn get_random_point_inside(&self, level_size: f32) -> Vec3 {
-        let mut rng = rand::thread_rng();
+    pub fn get_random_point_inside(&self, level_size: f32, rng: &mut impl Rng) -> Vec3 {
         let v2 = (0..)
             .map(|_| Vec2 {
                 x: rng.gen_range(-level_size..level_size),
@@ -95,6 +101,27 @@ impl SafeArea {
         }
     }
 
+    /// Distance from the point to the area border, zero inside
+    pub fn distance(&self, point: Vec2) -> f32 {
+        match self {
+            SafeArea::Rect { pos, size } => {
+                let d = (point - *pos).abs() - *size / 2.0;
+                d.max(Vec2::ZERO).length()
+            }
+            SafeArea::Circle { pos, radius } => ((point - *pos).length() - *radius).max(0.0),
+        }
+    }
+
+    /// Closest point of the area, the point itself inside
+    pub fn nearest_point(&self, point: Vec2) -> Vec2 {
+        match self {
+            SafeArea::Rect { pos, size } => point.clamp(*pos - *size / 2.0, *pos + *size / 2.0),
+            SafeArea::Circle { pos, radius } => {
+                *pos + (point - *pos).clamp_length_max(*radius)
+            }
+        }
+    }
+
     pub fn get_width(&self) -> f32 {
         match self {
             SafeArea::Rect { pos: _, size } => size.x,
@@ -119,19 +146,27 @@ impl SafeArea {
 #[derive(Component)]
 pub struct HiddenSafeArea;
 
-fn draw_safe_area(mut gizmos: Gizmos, query: Query<&SafeArea, Without<HiddenSafeArea>>) {
-    for safe_area in query.iter() {
+/// Pen which the dog has to fill with sheep, see global_task::collect_sheep_in_area
+#[derive(Component)]
+pub struct TargetPen;
+
+fn draw_safe_area(
+    mut gizmos: Gizmos,
+    query: Query<(&SafeArea, Option<&TargetPen>), Without<HiddenSafeArea>>,
+) {
+    for (safe_area, pen) in query.iter() {
+        let color = if pen.is_some() { Color::GREEN } else { Color::RED };
         match safe_area {
             SafeArea::Rect { pos, size } => {
                 gizmos.rect(
                     Vec3::new(pos.x, 0.001, pos.y),
                     Quat::from_euler(EulerRot::XYZ, PI / 2.0, 0.0, 0.0),
                     *size,
-                    Color::RED,
+                    color,
                 );
             }
             SafeArea::Circle { pos, radius } => {
-                gizmos.circle(Vec3::new(pos.x, 0.001, pos.y), Vec3::Y, *radius, Color::RED);
+                gizmos.circle(Vec3::new(pos.x, 0.001, pos.y), Vec3::Y, *radius, color);
             }
         }
     }
@@ -140,6 +175,8 @@ fn draw_safe_area(mut gizmos: Gizmos, query: Query<&SafeArea, Without<HiddenSafe
 #[derive(Resource, Default)]
 pub struct SheepCounter {
     pub count: u32,
+    //Sheep inside the TargetPen
+    pub in_pen: u32,
 }
 
 #[derive(Component)]
@@ -147,18 +184,25 @@ pub struct OutOfSafeArea;
 
 fn count_sheeps(
     mut commands: Commands,
-    safe_areas: Query<&SafeArea>,
+    safe_areas: Query<(&SafeArea, Option<&TargetPen>)>,
     sheep: Query<(Entity, &Transform), With<Sheep>>,
     mut counter: ResMut<SheepCounter>,
 ) {
     let mut count = 0;
+    let mut in_pen = 0;
     for (e, sheep) in sheep.iter() {
         let mut in_safe = false;
-        for safe_area in safe_areas.iter() {
+        let mut in_target = false;
+        for (safe_area, pen) in safe_areas.iter() {
             if safe_area.in_area(Vec2::new(sheep.translation.x, sheep.translation.z)) {
-                in_safe = true;
+                //Pen is not a shelter, wolves still hunt sheep in it
+                in_safe |= pen.is_none();
+                in_target |= pen.is_some();
             }
         }
+        if in_target {
+            in_pen += 1;
+        }
         if in_safe {
             count += 1;
             commands.entity(e).remove::<OutOfSafeArea>();
@@ -167,4 +211,5 @@ fn count_sheeps(
         }
     }
     counter.count = count;
+    counter.in_pen = in_pen;
 }
@@ -0,0 +1,249 @@
+//Campaign progress and per-level high scores, kept between game launches
+//Stored as "save" in crate::storage
+//The file is plain RON with a format version, so it can be migrated when the format changes
+
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    campaign::{Campaign, CampaignSet},
+    game_rng::GameRng,
+    storage, GameState,
+};
+
+const SAVE_NAME: &str = "save";
+
+pub const SAVE_VERSION: u32 = 1;
+//Best results kept per level
+const HIGH_SCORES_PER_LEVEL: usize = 5;
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        let save = SaveGame::load();
+        app.init_resource::<Campaign>();
+        save.progress.apply(&mut app.world.resource_mut::<Campaign>());
+        app.insert_resource(save);
+
+        app.add_systems(
+            OnEnter(GameState::Finish),
+            save_night_result.after(CampaignSet),
+        );
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Clone, Debug)]
+pub struct SaveGame {
+    pub version: u32,
+    #[serde(default)]
+    pub progress: CampaignProgress,
+    //Level name -> best results, highest score first
+    #[serde(default)]
+    pub high_scores: BTreeMap<String, Vec<HighScore>>,
+}
+
+impl Default for SaveGame {
+    fn default() -> Self {
+        Self {
+            version: SAVE_VERSION,
+            progress: CampaignProgress::default(),
+            high_scores: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct CampaignProgress {
+    pub night: usize,
+    pub unlocked: usize,
+    pub flock: Option<usize>,
+    #[serde(default)]
+    pub lamb_ages: Option<Vec<f32>>,
+    pub score: f32,
+}
+
+impl CampaignProgress {
+    pub fn from_campaign(campaign: &Campaign) -> Self {
+        Self {
+            night: campaign.night,
+            unlocked: campaign.unlocked,
+            flock: campaign.flock,
+            lamb_ages: campaign.lamb_ages.clone(),
+            score: campaign.score,
+        }
+    }
+
+    pub fn apply(&self, campaign: &mut Campaign) {
+        campaign.night = self.night;
+        campaign.unlocked = self.unlocked;
+        campaign.flock = self.flock;
+        campaign.lamb_ages = self.lamb_ages.clone();
+        campaign.score = self.score;
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HighScore {
+    pub score: f32,
+    pub sheep: usize,
+    pub seed: u64,
+    //Seconds since unix epoch
+    pub date: u64,
+}
+
+impl HighScore {
+    /// Date as YYYY-MM-DD (UTC)
+    pub fn date_string(&self) -> String {
+        //Days to civil date, see http://howardhinnant.github.io/date_algorithms.html
+        let z = (self.date / 86400) as i64 + 719468;
+        let era = z.div_euclid(146097);
+        let doe = z - era * 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+impl SaveGame {
+    pub fn from_ron(text: &str) -> Result<Self, String> {
+        let save: Self = ron::from_str(text).map_err(|err| err.to_string())?;
+        //Older versions are migrated here when the format changes
+        if save.version > SAVE_VERSION {
+            return Err(format!("unsupported save version {}", save.version));
+        }
+        Ok(Self {
+            version: SAVE_VERSION,
+            ..save
+        })
+    }
+
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+    }
+
+    /// Load from disk or localStorage. Missing or broken save gives a fresh one
+    pub fn load() -> Self {
+        let Some(text) = storage::read(SAVE_NAME) else {
+            return Self::default();
+        };
+
+        match Self::from_ron(&text) {
+            Ok(save) => save,
+            Err(err) => {
+                warn!("Failed to read save game, starting a new one: {}", err);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        match self.to_ron() {
+            Ok(text) => {
+                if let Err(err) = storage::write(SAVE_NAME, &text) {
+                    error!("Failed to write save game: {}", err);
+                }
+            }
+            Err(err) => error!("Failed to serialize save game: {}", err),
+        }
+    }
+
+    /// Insert result into the level table. Returns true if it made it into the table
+    pub fn add_high_score(&mut self, level: &str, high_score: HighScore) -> bool {
+        let table = self.high_scores.entry(level.to_string()).or_default();
+        let idx = table
+            .iter()
+            .position(|other| other.score < high_score.score)
+            .unwrap_or(table.len());
+        if idx >= HIGH_SCORES_PER_LEVEL {
+            return false;
+        }
+
+        table.insert(idx, high_score);
+        table.truncate(HIGH_SCORES_PER_LEVEL);
+        true
+    }
+}
+
+fn save_night_result(mut save: ResMut<SaveGame>, campaign: Res<Campaign>, rng: Res<GameRng>) {
+    let Some(result) = &campaign.last_result else {
+        return;
+    };
+
+    //Failed nights are retried and do not go to the table
+    if !result.failed {
+        let level = &campaign.levels[result.night].name;
+        let high_score = HighScore {
+            score: result.score,
+            sheep: result.flock,
+            seed: rng.seed(),
+            date: storage::now(),
+        };
+        if save.add_high_score(level, high_score) {
+            info!("New high score on {}: {:.1}", level, result.score);
+        }
+    }
+
+    save.progress = CampaignProgress::from_campaign(&campaign);
+    save.save();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn high_score(score: f32) -> HighScore {
+        HighScore {
+            score,
+            sheep: 100,
+            seed: 1,
+            date: 0,
+        }
+    }
+
+    #[test]
+    fn high_scores_are_sorted_and_truncated() {
+        let mut save = SaveGame::default();
+        for score in [3.0, 1.0, 5.0, 2.0, 4.0, 6.0] {
+            assert!(save.add_high_score("Meadow", high_score(score)));
+        }
+        //Lower than the whole full table
+        assert!(!save.add_high_score("Meadow", high_score(0.5)));
+
+        let scores = save.high_scores["Meadow"]
+            .iter()
+            .map(|s| s.score)
+            .collect::<Vec<_>>();
+        assert_eq!(scores, vec![6.0, 5.0, 4.0, 3.0, 2.0]);
+    }
+
+    #[test]
+    fn date_string_handles_leap_years() {
+        let date = |secs: u64| HighScore { date: secs, ..high_score(0.0) }.date_string();
+        assert_eq!(date(0), "1970-01-01");
+        assert_eq!(date(951782400), "2000-02-29");
+        assert_eq!(date(951868800), "2000-03-01");
+        assert_eq!(date(1703980800 + 86399), "2023-12-31");
+        assert_eq!(date(1709164800), "2024-02-29");
+        //2100 is not a leap year
+        assert_eq!(date(4107456000), "2100-02-28");
+        assert_eq!(date(4107456000 + 86400), "2100-03-01");
+    }
+
+    #[test]
+    fn newer_save_version_is_rejected() {
+        let mut save = SaveGame::default();
+        let text = save.to_ron().unwrap();
+        assert!(SaveGame::from_ron(&text).is_ok());
+
+        save.version = SAVE_VERSION + 1;
+        let text = save.to_ron().unwrap();
+        assert!(SaveGame::from_ron(&text).is_err());
+    }
+}
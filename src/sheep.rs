@@ -1,22 +1,31 @@
-use std::{f32::consts::PI, time::Duration, fmt::format};
+use std::{f32::consts::PI, fmt::format};
 
-use bevy::{prelude::*, utils::HashSet};
-use rand::{rngs::ThreadRng, Rng};
+use bevy::{prelude::*, reflect::TypePath, utils::HashSet};
+use rand::Rng;
+use serde::Deserialize;
 
 use crate::{
     get_sprite_rotation,
     global_task::sheep_escape::ShawshankRedemption,
     physics::{Velocity, WalkController},
     player::{Bark, Dog, DOG_SPEED},
-    safe_area::SafeArea,
+    safe_area::{SafeArea, TargetPen},
     sprite_material::create_plane_mesh,
+    flocking::{Boid, FlockSurroundings, FlockingParams},
+    level::CurrentLevel,
+    pasture::Pasture,
+    panic::{Fear, PanicPlugin},
+    neighbours::{NearestSheep, NeighbourSet, NeighboursUpdated, SheepNeighboursPlugin},
+    sheep_traits::{Mother, SheepTraits, SheepTraitsPlugin, LAMB_PART, LAMB_SCALE, LAMB_SPAWN_RADIUS},
+    sunday::DayState,
     test_level::LevelSize,
-    GameSet, GameStuff, auto_anim::{AnimRange, AnimSet, AutoAnimPlugin, AutoAnim},
+    sheep_behaviour::{
+        entered, exited, BehaviourEnter, BehaviourExit, BehaviourId, Decision, SheepBehaviourAppExt,
+        SheepBehaviourPlugin, SheepBehaviourSet, SheepTransition, SheepTransitions,
+    },
+    GameSet, GameStuff, SimSet, VisualsSet, auto_anim::{AnimRange, AnimSet, AutoAnimPlugin, AutoAnim}, game_rng::GameRng,
 };
 
-use bevy_spatial::{
-    kdtree::KDTree3, AutomaticUpdate, SpatialAccess, SpatialStructure, TransformMode,
-};
 
 const SHEEP_PATH: &str = "test/sheep.png";
 const SHEEP_FOLDER_PATH: &str = "sheep/";
@@ -41,50 +50,59 @@ pub struct SheepPlugin;
 
 impl Plugin for SheepPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<StateChance>();
+        app.init_resource::<StateChance>()
+            .init_resource::<FlockingParams>();
+
+        app.add_plugins((
+            SheepBehaviourPlugin,
+            IdleBehaviourPlugin,
+            FeedBehaviourPlugin,
+            WalkBehaviourPlugin,
+            EscapeBehaviourPlugin,
+            ScaredBehaviourPlugin,
+            SheepNeighboursPlugin,
+            SheepTraitsPlugin,
+            PanicPlugin,
+        ))
+        .configure_sets(Update, SheepBehaviourSet::Update.after(NeighbourSet));
+
+        app.register_type::<StateChance>()
+            .register_type::<StateChanceRow>()
+            .register_type::<NextChance>()
+            .register_type::<FlockingParams>()
+            .register_type::<IsScared>();
 
         app.add_systems(
             Update,
-            (scared_sheeps, update_scared_sheeps).in_set(GameSet::Playing),
+            collect_field
+                .after(SheepBehaviourSet::Enter)
+                .in_set(SimSet::Sheep),
         );
+    }
+}
 
-        app.add_systems(Update, (sheep_state,).in_set(GameSet::Playing));
-
-        //random walk
-        app.add_event::<InitRandomWalk>()
-            .add_systems(Update, (init_random_walk,).in_set(GameSet::Playing));
-
-        app.add_systems(Update, (goto_system,).in_set(GameSet::Playing));
-
-        //idle feeding
-        app.add_systems(Update, idle_feeding_system.in_set(GameSet::Playing));
-
-        // Move to safearea
-        app.add_event::<SafeAreaWalk>()
-            .add_systems(Update, (init_safeareawalk_walk,).in_set(GameSet::Playing));
-
-        app.add_event::<EscapeWalk>()
-            .add_systems(Update, init_escape.in_set(GameSet::Playing));
-
-        app.register_type::<StateChance>()
-            .register_type::<Decision>()
-            .register_type::<IsScared>();
+pub struct SheepVisualsPlugin;
 
-        app.add_plugins(
-            AutomaticUpdate::<Sheep>::new()
-                .with_frequency(Duration::from_millis(250))
-                .with_transform(TransformMode::Transform)
-                .with_spatial_ds(SpatialStructure::KDTree3),
-        )
-        .add_systems(Update, collect_field)
-        .add_plugins(AutoAnimPlugin::<SheepAnim>::default())
-        
-        .add_systems(Update, set_anim_state.in_set(GameSet::Playing))
-        
-        .add_systems(Update, update_nearest);
+impl Plugin for SheepVisualsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_sheep_storage)
+            .add_systems(Update, attach_sheep_visuals.in_set(VisualsSet))
+            .add_plugins(AutoAnimPlugin::<SheepAnim>::default())
+            .add_plugins(AutoAnimPlugin::<LambAnim>::default())
+            .add_systems(
+                Update,
+                (set_anim_state::<SheepAnim>, set_anim_state::<LambAnim>).in_set(GameSet::Playing),
+            );
     }
 }
 
+/// Animations with the same frames for walking, feeding and standing
+pub trait FlockAnim: AnimSet {
+    fn idle() -> Self;
+    fn walk() -> Self;
+    fn feed() -> Self;
+}
+
 #[derive(Default)]
 pub enum SheepAnim {
     #[default]
@@ -111,6 +129,66 @@ impl AnimSet for SheepAnim {
     }
 }
 
+impl FlockAnim for SheepAnim {
+    fn idle() -> Self {
+        SheepAnim::Idle
+    }
+
+    fn walk() -> Self {
+        SheepAnim::Walk
+    }
+
+    fn feed() -> Self {
+        SheepAnim::Feed
+    }
+}
+
+//Lambs use sheep tiles with a lighter wool and a faster walk cycle
+#[derive(Default)]
+pub enum LambAnim {
+    #[default]
+    Idle,
+    Walk,
+    Feed
+}
+
+impl AnimSet for LambAnim {
+    //There is no lamb sprite set yet, lambs reuse the sheep tiles
+    fn get_folder_path() -> String {
+        "sheep".to_string()
+    }
+
+    fn get_index_range(&self) -> AnimRange {
+        match self {
+            LambAnim::Idle => AnimRange::new(28, 39),
+            LambAnim::Walk => AnimRange::new(24, 27),
+            LambAnim::Feed => AnimRange::new(13, 16),
+        }
+    }
+
+    fn get_tile_count() -> usize {
+        40
+    }
+
+    fn get_tint() -> Color {
+        Color::rgb(1.0, 0.95, 0.8)
+    }
+}
+
+impl FlockAnim for LambAnim {
+    fn idle() -> Self {
+        LambAnim::Idle
+    }
+
+    fn walk() -> Self {
+        LambAnim::Walk
+    }
+
+    fn feed() -> Self {
+        LambAnim::Feed
+    }
+}
+
 #[derive(Resource)]
 pub struct StartSheepCount(pub f32);
 
@@ -120,107 +198,345 @@ pub struct Sheep;
 #[derive(Default, PartialEq, Debug, Clone, Component, Reflect)]
 #[reflect(Component, Default)]
 pub struct IsScared {
-    time: f32,
     last_vel: Vec3,
 }
 
 #[derive(Component, Default)]
 pub struct SheepTargetVel(pub Vec3);
 
-#[derive(Default, PartialEq, Eq, Debug, Clone, Component, Reflect, Copy)]
-#[reflect(Component, Default)]
-pub enum Decision {
-    #[default]
-    Idle, //set sheep state to waiting next decision. Not just be idle. For stending we will use Feed or deleted IdleFeeding. We need to have transition states to move to next decision. Addition set of moves will be seen as sheep has plan to move and will be nicely waiting
-    Feed,
-    RandomWalk,
-    MoveToSafeArea,
-    Escape,
-    Scared, //Mark that sheep will not be able to another decision
-}
-
+//Built-in sheep behaviours
+//Idle means waiting for the next decision, not standing. Standing sheep are in Feed
+pub const IDLE: BehaviourId = BehaviourId::IDLE;
+pub const FEED: BehaviourId = BehaviourId("feed");
+pub const RANDOM_WALK: BehaviourId = BehaviourId("random_walk");
+pub const MOVE_TO_SAFE_AREA: BehaviourId = BehaviourId("move_to_safe_area");
+pub const ESCAPE: BehaviourId = BehaviourId("escape");
+//Sheep runs from the dog and takes no other decision until it calms down
+pub const SCARED: BehaviourId = BehaviourId("scared");
+
+/// Markov matrix of idle decisions. Row is the behaviour which the sheep has just finished,
+/// sheep without a row use the IDLE one. Weights are relative and changed by DecisionContext.
+/// Loaded from assets/sheep.behaviour.ron, defaults are the same as in that file
 #[derive(PartialEq, Debug, Clone, Resource, Reflect)]
 #[reflect(Resource, Default)]
 pub struct StateChance {
-    pub next_state: Vec<(f32, Decision)>,
+    pub rows: Vec<StateChanceRow>,
+}
+
+#[derive(PartialEq, Debug, Clone, Reflect)]
+pub struct StateChanceRow {
+    pub from: BehaviourId,
+    pub next: Vec<NextChance>,
+}
+
+#[derive(PartialEq, Debug, Clone, Reflect)]
+pub struct NextChance {
+    pub to: BehaviourId,
+    pub weight: f32,
+    //Weight multiplier at night
+    pub night: f32,
+    //Weight added for each meter between the sheep and the nearest safe area
+    pub far_from_safe_area: f32,
+}
+
+impl NextChance {
+    pub fn new(to: BehaviourId, weight: f32) -> Self {
+        Self {
+            to,
+            weight,
+            night: 1.0,
+            far_from_safe_area: 0.0,
+        }
+    }
+
+    pub fn with_night(mut self, night: f32) -> Self {
+        self.night = night;
+        self
+    }
+
+    pub fn with_far_from_safe_area(mut self, far_from_safe_area: f32) -> Self {
+        self.far_from_safe_area = far_from_safe_area;
+        self
+    }
+
+    fn weight(&self, context: &DecisionContext) -> f32 {
+        let mut weight = self.weight + self.far_from_safe_area * context.safe_area_distance;
+        if context.night {
+            weight *= self.night;
+        }
+        weight.max(0.0)
+    }
+}
+
+/// What an idle sheep takes into account when it chooses the next behaviour
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DecisionContext {
+    pub night: bool,
+    //Zero inside a safe area
+    pub safe_area_distance: f32,
 }
 
 impl Default for StateChance {
     fn default() -> Self {
-        let mut res = Self {
-            //set weights
-            //its weights are relative
-            //must be all between 0 and 1
-            //normalization will be done automatically
-
-            //For testing i made all weights 1 so all next states are equally likely to be chosen
-            next_state: vec![
-                (1.0, Decision::Feed), //zero values for unimplemented things
-                (0.5, Decision::RandomWalk),
+        let back_to_flock = || NextChance::new(MOVE_TO_SAFE_AREA, 0.0).with_far_from_safe_area(0.05);
+        Self {
+            rows: vec![
+                StateChanceRow {
+                    from: IDLE,
+                    next: vec![NextChance::new(FEED, 1.0), NextChance::new(RANDOM_WALK, 0.5)],
+                },
+                StateChanceRow {
+                    from: FEED,
+                    next: vec![
+                        NextChance::new(FEED, 0.6),
+                        NextChance::new(RANDOM_WALK, 0.8).with_night(0.5),
+                        back_to_flock(),
+                    ],
+                },
+                StateChanceRow {
+                    from: RANDOM_WALK,
+                    next: vec![
+                        NextChance::new(FEED, 1.0),
+                        NextChance::new(RANDOM_WALK, 0.3).with_night(0.5),
+                        back_to_flock(),
+                    ],
+                },
+                StateChanceRow {
+                    from: MOVE_TO_SAFE_AREA,
+                    next: vec![NextChance::new(FEED, 1.0), NextChance::new(RANDOM_WALK, 0.2)],
+                },
+                StateChanceRow {
+                    from: SCARED,
+                    next: vec![
+                        NextChance::new(FEED, 0.5),
+                        NextChance::new(RANDOM_WALK, 0.5).with_night(0.5),
+                        back_to_flock().with_night(2.0),
+                    ],
+                },
             ],
-        };
-        res.normalize();
-        res
+        }
     }
 }
 
 impl StateChance {
-    fn normalize(&mut self) {
+    pub fn row(&self, from: BehaviourId) -> Option<&StateChanceRow> {
+        self.rows
+            .iter()
+            .find(|row| row.from == from)
+            .or_else(|| self.rows.iter().find(|row| row.from == IDLE))
+    }
+
+    //I separated next decision selection to function
+    fn select_next(
+        &self,
+        from: BehaviourId,
+        context: &DecisionContext,
+        rng: &mut impl Rng,
+    ) -> BehaviourId {
+        let p = rng.gen_range(0.0..1.0);
+        let Some(row) = self.row(from) else {
+            return IDLE;
+        };
+
+        let total = row.next.iter().map(|next| next.weight(context)).sum::<f32>();
+        let p = p * total;
         let mut sum = 0.0;
-        for (w, _) in &self.next_state {
-            sum += *w;
+        for next in &row.next {
+            sum += next.weight(context);
+            if p < sum {
+                return next.to;
+            }
         }
+        IDLE
+    }
+}
 
-        for (w, _) in &mut self.next_state {
-            *w /= sum;
+/// StateChance as it is written in the asset file, behaviours are referenced by name
+#[derive(Asset, TypePath, Deserialize, Clone, Debug)]
+pub struct StateChanceDescription {
+    pub rows: Vec<StateChanceRowDescription>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct StateChanceRowDescription {
+    pub from: String,
+    pub next: Vec<NextChanceDescription>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct NextChanceDescription {
+    pub to: String,
+    pub weight: f32,
+    #[serde(default = "default_night")]
+    pub night: f32,
+    #[serde(default)]
+    pub far_from_safe_area: f32,
+}
+
+fn default_night() -> f32 {
+    1.0
+}
+
+impl StateChanceDescription {
+    /// Resolve behaviour names. Unknown behaviours are skipped with a warning
+    pub fn to_state_chance(&self, transitions: &SheepTransitions) -> StateChance {
+        let behaviour = |name: &str| {
+            let id = transitions.behaviour(name);
+            if id.is_none() {
+                warn!("Unknown sheep behaviour {:?} in StateChance", name);
+            }
+            id
+        };
+
+        StateChance {
+            rows: self
+                .rows
+                .iter()
+                .filter_map(|row| {
+                    Some(StateChanceRow {
+                        from: behaviour(&row.from)?,
+                        next: row
+                            .next
+                            .iter()
+                            .filter_map(|next| {
+                                Some(NextChance {
+                                    to: behaviour(&next.to)?,
+                                    weight: next.weight,
+                                    night: next.night,
+                                    far_from_safe_area: next.far_from_safe_area,
+                                })
+                            })
+                            .collect(),
+                    })
+                })
+                .collect(),
         }
     }
+}
 
-    //I separated next decision selection to function
-    fn select_next(&self, rng: &mut ThreadRng) -> Decision {
-        let mut sum = 0.0; //This decisicion selection is based on weights, not prop graph. Just for testing and more stable behavior. Dont change please
-        let p = rng.gen_range(0.0..1.0);
-        for (w, d) in &self.next_state {
-            sum += *w;
-            if p < sum {
-                return *d;
+struct IdleBehaviourPlugin;
+
+impl Plugin for IdleBehaviourPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, sheep_state.in_set(SheepBehaviourSet::Update))
+            .add_systems(Update, remember_previous.in_set(SheepBehaviourSet::Enter));
+    }
+}
+
+/// Behaviour which the sheep finished before going idle. Selects the row of StateChance
+#[derive(Component, Clone, Copy, Debug)]
+pub struct PreviousDecision(pub BehaviourId);
+
+impl Default for PreviousDecision {
+    fn default() -> Self {
+        Self(IDLE)
+    }
+}
+
+fn remember_previous(mut commands: Commands, mut events: EventReader<BehaviourEnter>) {
+    for ev in events.read().filter(|ev| ev.to == IDLE) {
+        commands.entity(ev.sheep).insert(PreviousDecision(ev.from));
+    }
+}
+
+pub fn sheep_state(
+    state_matrix: Res<StateChance>,
+    day_state: Res<State<DayState>>,
+    sheeps: Query<(Entity, &Transform, &Decision, &PreviousDecision), With<Sheep>>,
+    //Sheep do not go into the herding pen on their own, the dog has to drive them
+    safe_areas: Query<&SafeArea, Without<TargetPen>>,
+    mut transitions: EventWriter<SheepTransition>,
+    mut rand: ResMut<GameRng>,
+) {
+    let night = *day_state.get() == DayState::Night;
+
+    for (e, t, dec, previous) in sheeps.iter() {
+        if dec.0 == IDLE {
+            let pos = Vec2::new(t.translation.x, t.translation.z);
+            let context = DecisionContext {
+                night,
+                safe_area_distance: safe_areas
+                    .iter()
+                    .map(|sa| sa.distance(pos))
+                    .reduce(f32::min)
+                    .unwrap_or(0.0),
+            };
+
+            let next = state_matrix.select_next(previous.0, &context, &mut *rand);
+            if next != IDLE {
+                transitions.send(SheepTransition { sheep: e, to: next });
             }
         }
-        Decision::Idle
     }
 }
 
-pub fn scared_sheeps(
+struct FeedBehaviourPlugin;
+
+impl Plugin for FeedBehaviourPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_sheep_transition(IDLE, FEED)
+            .add_sheep_transition(FEED, IDLE)
+            .add_systems(Update, idle_feeding_system.in_set(SheepBehaviourSet::Update))
+            .add_systems(Update, stop_feeding.in_set(SheepBehaviourSet::Exit))
+            .add_systems(Update, start_feeding.in_set(SheepBehaviourSet::Enter));
+    }
+}
+
+#[derive(Component)]
+pub struct IdleFeeding {
+    pub time: f32,
+}
+
+fn start_feeding(
     mut commands: Commands,
-    mut event_reader: EventReader<Bark>,
-    mut sheeps: Query<(Entity, &Transform, &mut SheepTargetVel, &mut Decision), (With<Sheep>, Without<IsScared>)>,
+    mut events: EventReader<BehaviourEnter>,
+    decisions: Query<&Decision>,
+    traits: Query<&SheepTraits>,
+    mut rand: ResMut<GameRng>,
 ) {
-    if let Some(bark) = event_reader.read().next() {
-        let bark_origin = bark.position;
-        for mut sheep in &mut sheeps {
-            if sheep.1.translation.distance(bark_origin) <= bark.radius {
-                let scare = IsScared::default();
-                commands
-                    .entity(sheep.0)
-                    .insert(scare)
-                    .remove::<IdleFeeding>()
-                    .remove::<GoTo>()
-                    .remove::<ShawshankRedemption>();
-                *sheep.3 = Decision::Scared;
-            }
+    for e in entered(&mut events, &decisions, FEED) {
+        let hunger = traits.get(e).map_or(1.0, |t| t.feeding_factor());
+        commands.entity(e).insert(IdleFeeding {
+            time: (rand.gen_range(0.0..IDLE_FEEDING_TIME_RANGE) + IDLE_FEEDING_TIME) * hunger,
+        });
+    }
+}
+
+fn idle_feeding_system(
+    mut sheeps: Query<(Entity, &mut IdleFeeding)>,
+    mut transitions: EventWriter<SheepTransition>,
+    time: Res<Time>,
+) {
+    for (e, mut idle) in sheeps.iter_mut() {
+        idle.time -= time.delta_seconds();
+        if idle.time < 0.0 {
+            transitions.send(SheepTransition { sheep: e, to: IDLE });
         }
     }
-    event_reader.clear();
 }
 
-#[derive(Event)]
-pub struct InitRandomWalk {
-    pub e: Entity,
+fn stop_feeding(mut commands: Commands, mut events: EventReader<BehaviourExit>) {
+    for (e, _) in exited(&mut events, &[FEED]) {
+        commands.entity(e).remove::<IdleFeeding>();
+    }
 }
 
-#[derive(Event)]
-pub struct SafeAreaWalk {
-    pub e: Entity,
+//Random walk and move to safe area. Both insert GoTo on enter and return to idle when the target is reached
+struct WalkBehaviourPlugin;
+
+impl Plugin for WalkBehaviourPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_sheep_transition(IDLE, RANDOM_WALK)
+            .add_sheep_transition(RANDOM_WALK, IDLE)
+            .add_sheep_transition(IDLE, MOVE_TO_SAFE_AREA)
+            .add_sheep_transition(MOVE_TO_SAFE_AREA, IDLE)
+            .add_systems(Update, goto_system.in_set(SheepBehaviourSet::Update))
+            .add_systems(Update, stop_walk.in_set(SheepBehaviourSet::Exit))
+            .add_systems(
+                Update,
+                (init_random_walk, init_safeareawalk_walk).in_set(SheepBehaviourSet::Enter),
+            );
+    }
 }
 
 #[derive(Component)]
@@ -230,39 +546,31 @@ pub struct GoTo {
 
 fn init_random_walk(
     mut commands: Commands,
-    mut event_reader: EventReader<InitRandomWalk>,
+    mut events: EventReader<BehaviourEnter>,
+    decisions: Query<&Decision>,
     poses: Query<&Transform, With<Sheep>>,
+    mut rand: ResMut<GameRng>,
 ) {
-    let mut rand = rand::thread_rng();
-    for ev in event_reader.read() {
-        if let Ok(t) = poses.get_component::<Transform>(ev.e) {
-            // info!("init random walk for {:?}", ev.e);
+    for e in entered(&mut events, &decisions, RANDOM_WALK) {
+        if let Ok(t) = poses.get(e) {
             let r = rand.gen_range(0.0..RANDOM_WALK_RANGE);
             let angle = rand.gen_range(0.0..PI * 2.0);
 
-            commands.entity(ev.e).insert(GoTo {
+            commands.entity(e).insert(GoTo {
                 target: t.translation + Vec3::new(angle.cos() * r, 0.0, angle.sin() * r),
             });
         }
     }
-    event_reader.clear();
 }
 
 fn goto_system(
-    mut commands: Commands,
-    mut goto_query: Query<(
-        Entity,
-        &mut Transform,
-        &mut SheepTargetVel,
-        &mut Decision,
-        &GoTo,
-    )>,
+    mut goto_query: Query<(Entity, &Transform, &mut SheepTargetVel, &GoTo)>,
+    mut transitions: EventWriter<SheepTransition>,
 ) {
-    for (e, t, mut v, mut dec, rw) in &mut goto_query.iter_mut() {
+    for (e, t, mut v, rw) in goto_query.iter_mut() {
         if t.translation.distance(rw.target) < RANDOM_WALK_ACCEPT_RADIUS {
             v.0 = Vec3::ZERO;
-            commands.entity(e).remove::<GoTo>();
-            *dec = Decision::Idle;
+            transitions.send(SheepTransition { sheep: e, to: IDLE });
         } else {
             v.0 = (rw.target - t.translation).normalize()
                 * SHEEP_SPEED
@@ -271,378 +579,458 @@ fn goto_system(
     }
 }
 
+//Walk to the center of the nearest safe area. Without safe areas the sheep goes back to idle
 fn init_safeareawalk_walk(
     mut commands: Commands,
-    mut event_reader: EventReader<SafeAreaWalk>,
+    mut events: EventReader<BehaviourEnter>,
+    decisions: Query<&Decision>,
     poses: Query<&Transform, With<Sheep>>,
-    safeareas: Query<&SafeArea>,
-    level_size: Res<LevelSize>,
+    safeareas: Query<&SafeArea, Without<TargetPen>>,
+    mut transitions: EventWriter<SheepTransition>,
 ) {
-    let Ok(safearea) = safeareas.get_single() else {
-        return;
-    };
-
-    for ev in event_reader.read() {
-        if let Ok(t) = poses.get_component::<Transform>(ev.e) {
-            let inside_point = safearea.get_random_point_inside(level_size.0 / 3.0);
-            let dir = (inside_point - t.translation).normalize_or_zero();
-            commands.entity(ev.e).insert(GoTo {
-                target: t.translation + dir * MOVE_IN_DIST, // move to near center, so move will be safe, opposite to RandomWalk or Move out safe zone
+    for e in entered(&mut events, &decisions, MOVE_TO_SAFE_AREA) {
+        let Ok(t) = poses.get(e) else {
+            continue;
+        };
+
+        let pos = Vec2::new(t.translation.x, t.translation.z);
+        let nearest = safeareas
+            .iter()
+            .min_by(|a, b| a.distance(pos).total_cmp(&b.distance(pos)));
+
+        if let Some(safearea) = nearest {
+            let to_center = safearea.get_center() - t.translation;
+            commands.entity(e).insert(GoTo {
+                target: t.translation + to_center.clamp_length_max(MOVE_IN_DIST), // move to near center, so move will be safe, opposite to RandomWalk or Move out safe zone
             });
+        } else {
+            transitions.send(SheepTransition { sheep: e, to: IDLE });
         }
     }
-    event_reader.clear();
 }
 
-pub fn sheep_state(
+fn stop_walk(
     mut commands: Commands,
-    state_matrix: Res<StateChance>,
-    mut sheeps: Query<(Entity, &mut Decision, &Sheep), Without<IsScared>>,
-    mut init_random_walk: EventWriter<InitRandomWalk>,
-    mut init_safe_walk: EventWriter<SafeAreaWalk>,
-    mut init_escape_walk: EventWriter<EscapeWalk>,
+    mut events: EventReader<BehaviourExit>,
+    mut sheeps: Query<&mut SheepTargetVel>,
 ) {
-    let mut rand = rand::thread_rng();
-    for (e, mut dec, _sheep) in &mut sheeps.iter_mut() {
-        if *dec == Decision::Idle {
-            let next_dec = state_matrix.select_next(&mut rand);
-
-            *dec = next_dec;
-
-            match next_dec {
-                Decision::Idle => {
-                    // info!("new idle for {:?}", e);
-                }
-                Decision::Feed => {
-                    commands.entity(e).insert(IdleFeeding {
-                        time: rand.gen_range(0.0..IDLE_FEEDING_TIME_RANGE) + IDLE_FEEDING_TIME,
-                    });
-                }
-                Decision::RandomWalk => {
-                    // info!("new random walk for {:?}", e);
-                    init_random_walk.send(InitRandomWalk { e });
-                }
-                Decision::MoveToSafeArea => {
-                    init_safe_walk.send(SafeAreaWalk { e });
-                }
-                Decision::Escape => {
-                    // For now this seems ok
-                    init_escape_walk.send(EscapeWalk { e });
-                }
-                Decision::Scared => {
-                    *dec = Decision::Idle;
-                }
-            }
+    for (e, _) in exited(&mut events, &[RANDOM_WALK, MOVE_TO_SAFE_AREA, ESCAPE]) {
+        commands.entity(e).remove::<GoTo>();
+        if let Ok(mut v) = sheeps.get_mut(e) {
+            v.0 = Vec3::ZERO;
         }
     }
 }
 
-#[derive(Event)]
-pub struct EscapeWalk {
-    pub e: Entity,
+//Escape from the flock. Sheep of a global task wave get EscapeTarget before the transition,
+//others run away from the nearest safe area
+struct EscapeBehaviourPlugin;
+
+impl Plugin for EscapeBehaviourPlugin {
+    fn build(&self, app: &mut App) {
+        //Waves send sheep away from whatever they were doing
+        app.add_sheep_transition(BehaviourId::ANY, ESCAPE)
+            .add_sheep_transition(ESCAPE, IDLE)
+            .add_systems(Update, end_escape.in_set(SheepBehaviourSet::Exit))
+            .add_systems(Update, init_escape.in_set(SheepBehaviourSet::Enter));
+    }
 }
 
+#[derive(Component)]
+pub struct EscapeTarget(pub Vec3);
+
 pub fn init_escape(
     mut commands: Commands,
-    mut event_reader: EventReader<EscapeWalk>,
-    poses: Query<&Transform, With<Sheep>>,
+    mut events: EventReader<BehaviourEnter>,
+    decisions: Query<&Decision>,
+    poses: Query<(&Transform, Option<&EscapeTarget>), With<Sheep>>,
     safe_zones: Query<&SafeArea>,
 ) {
-    for ev in event_reader.read() {
-        if let Ok(t) = poses.get_component::<Transform>(ev.e) {
-            //find nearest safe zone
-            let mut nearest = None;
-            let mut nearest_dist = f32::MAX;
-
-            for sa in safe_zones.iter() {
-                let dist = t.translation.distance(sa.get_center());
-                if dist < nearest_dist {
-                    nearest = Some(sa);
-                    nearest_dist = dist;
-                }
+    for e in entered(&mut events, &decisions, ESCAPE) {
+        let Ok((t, escape_target)) = poses.get(e) else {
+            continue;
+        };
+
+        if let Some(escape_target) = escape_target {
+            commands.entity(e).insert(GoTo {
+                target: escape_target.0,
+            });
+            continue;
+        }
+
+        //find nearest safe zone
+        let mut nearest = None;
+        let mut nearest_dist = f32::MAX;
+
+        for sa in safe_zones.iter() {
+            let dist = t.translation.distance(sa.get_center());
+            if dist < nearest_dist {
+                nearest = Some(sa);
+                nearest_dist = dist;
             }
+        }
+
+        if let Some(sa) = nearest {
+            let dir = (t.translation - sa.get_center()).normalize_or_zero();
+            info!("escape {:?}", t.translation);
+            commands.entity(e).insert(GoTo {
+                target: t.translation + dir * MOVE_OUT_DIST,
+            });
+        }
+    }
+}
+
+//Sheep stays an escaper for the wave until the dog scares it, even if it reached the target
+fn end_escape(mut commands: Commands, mut events: EventReader<BehaviourExit>) {
+    for (e, to) in exited(&mut events, &[ESCAPE]) {
+        commands.entity(e).remove::<EscapeTarget>();
+        if to == SCARED {
+            commands.entity(e).remove::<ShawshankRedemption>();
+        }
+    }
+}
+
+struct ScaredBehaviourPlugin;
 
-            if let Some(sa) = nearest {
-                let dir = (t.translation - sa.get_center()).normalize_or_zero();
-                info!("escape {:?}", t.translation);
-                commands.entity(ev.e).insert(GoTo {
-                    target: t.translation + dir * MOVE_OUT_DIST,
-                });
+impl Plugin for ScaredBehaviourPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_sheep_transition(BehaviourId::ANY, SCARED)
+            .add_sheep_transition(SCARED, IDLE)
+            .add_systems(
+                Update,
+                (scared_sheeps, update_scared_sheeps).in_set(SheepBehaviourSet::Update),
+            )
+            .add_systems(Update, calm_down.in_set(SheepBehaviourSet::Exit))
+            .add_systems(Update, get_scared.in_set(SheepBehaviourSet::Enter));
+    }
+}
+
+//Bark fills fear of all sheep around, scared ones keep running while the dog barks
+pub fn scared_sheeps(
+    mut event_reader: EventReader<Bark>,
+    mut sheeps: Query<(Entity, &Transform, &SheepTraits, &mut Fear, Option<&IsScared>), With<Sheep>>,
+    mut transitions: EventWriter<SheepTransition>,
+) {
+    if let Some(bark) = event_reader.read().next() {
+        let bark_origin = bark.position;
+        for (e, t, traits, mut fear, scared) in sheeps.iter_mut() {
+            if t.translation.distance(bark_origin) <= bark.radius * traits.scare_factor() {
+                fear.frighten(1.0, bark_origin);
+                if scared.is_none() {
+                    transitions.send(SheepTransition { sheep: e, to: SCARED });
+                }
             }
         }
     }
     event_reader.clear();
 }
 
-pub fn update_scared_sheeps(
+fn get_scared(
     mut commands: Commands,
-    time: Res<Time>,
+    mut events: EventReader<BehaviourEnter>,
+    decisions: Query<&Decision>,
+) {
+    for e in entered(&mut events, &decisions, SCARED) {
+        commands.entity(e).insert(IsScared::default());
+    }
+}
+
+fn calm_down(
+    mut commands: Commands,
+    mut events: EventReader<BehaviourExit>,
+    mut sheeps: Query<&mut SheepTargetVel>,
+) {
+    for (e, _) in exited(&mut events, &[SCARED]) {
+        commands.entity(e).remove::<IsScared>();
+        if let Ok(mut walk) = sheeps.get_mut(e) {
+            walk.0 = Vec3::ZERO;
+        }
+    }
+}
+
+//Scared sheep runs from the dog when it is close and from the source of its fear otherwise.
+//Calming down is done by panic::decay_fear
+pub fn update_scared_sheeps(
     mut sheeps: Query<
         (
-            Entity,
             &Transform,
             &mut SheepTargetVel,
-            &mut Decision,
             &mut IsScared,
+            &Fear,
             &NearestSheep
         ),
         With<Sheep>,
     >,
     dog: Query<&Transform, With<Dog>>,
 ) {
-    let Ok(dog_transform) = dog.get_single() else {
-        return;
-    };
+    let dog = dog.get_single().ok().map(|t| t.translation);
 
-    for (e, t, mut walk, mut dec, mut scare, nearest) in sheeps.iter_mut() {
-        if scare.time > 3. {
-            *dec = Decision::Idle;
-            walk.0 = Vec3::ZERO;
-            commands.entity(e).remove::<IsScared>();
-        } else {
-            scare.time += time.delta_seconds();
-
-            let dog_dpos = t.translation - dog_transform.translation;
-            let dog_distance = dog_dpos.length();
-
-            let dir = dog_dpos.normalize_or_zero();
-
-            let speed_amount = (SHEEP_SPEED * (1.0 - dog_distance / SCARE_MAX_DIST)
-                + SHEEP_SPEED * RANDOM_WALK_SPEED_MULTIPLIER)
-                .max(SHEEP_SPEED * RANDOM_WALK_SPEED_MULTIPLIER);
-
-            let nearest = &nearest.0;
-            let mut mean_nearest_sheep = Vec3::ZERO;
-            let mut count = 0.0;
-            for (pos, _) in nearest.iter().skip(1) {
-                // if (*pos - t.translation).length() < 5.0 {
-                    let ddog = *pos - dog_transform.translation;
-                    if ddog.dot(dog_dpos) >= 0.0 {
-                        mean_nearest_sheep += *pos;
-                        count += 1.0;
-                    }
-                // }
-            }
-            if count > 0.0 {
-                let mean_nearest_sheep = mean_nearest_sheep / (count as f32);
-                if (mean_nearest_sheep - dog_transform.translation).length() < dog_dpos.length() {
-                    walk.0 = (mean_nearest_sheep - dog_transform.translation).normalize_or_zero() * speed_amount;
-                    scare.last_vel = walk.0;
-                } else {
-                    walk.0 = ((mean_nearest_sheep - dog_transform.translation).normalize_or_zero() + (mean_nearest_sheep - t.translation).normalize_or_zero() * 1.5).normalize_or_zero()
-                                 * speed_amount;
-                    scare.last_vel = walk.0;
+    for (t, mut walk, mut scare, fear, nearest) in sheeps.iter_mut() {
+        let threat = match dog {
+            Some(dog) if dog.distance(t.translation) < SCARE_MAX_DIST => dog,
+            _ => fear.source,
+        };
+
+        let threat_dpos = t.translation - threat;
+        let threat_distance = threat_dpos.length();
+
+        let dir = threat_dpos.normalize_or_zero();
+
+        //Panic keeps the sheep running even far from the threat
+        let speed_amount = (SHEEP_SPEED * (1.0 - threat_distance / SCARE_MAX_DIST).max(fear.level)
+            + SHEEP_SPEED * RANDOM_WALK_SPEED_MULTIPLIER)
+            .max(SHEEP_SPEED * RANDOM_WALK_SPEED_MULTIPLIER);
+
+        let nearest = &nearest.0;
+        let mut mean_nearest_sheep = Vec3::ZERO;
+        let mut count = 0.0;
+        for (pos, _) in nearest.iter().skip(1) {
+            // if (*pos - t.translation).length() < 5.0 {
+                let dthreat = *pos - threat;
+                if dthreat.dot(threat_dpos) >= 0.0 {
+                    mean_nearest_sheep += *pos;
+                    count += 1.0;
                 }
+            // }
+        }
+        if count > 0.0 {
+            let mean_nearest_sheep = mean_nearest_sheep / (count as f32);
+            if (mean_nearest_sheep - threat).length() < threat_dpos.length() {
+                walk.0 = (mean_nearest_sheep - threat).normalize_or_zero() * speed_amount;
+                scare.last_vel = walk.0;
             } else {
-                walk.0 = dir * speed_amount;
+                walk.0 = ((mean_nearest_sheep - threat).normalize_or_zero() + (mean_nearest_sheep - t.translation).normalize_or_zero() * 1.5).normalize_or_zero()
+                             * speed_amount;
                 scare.last_vel = walk.0;
             }
-            
+        } else {
+            walk.0 = dir * speed_amount;
+            scare.last_vel = walk.0;
         }
     }
 }
 
 pub fn setup(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    asset_server: Res<AssetServer>,
-    level_size: Res<LevelSize>,
+    level: Res<CurrentLevel>,
+    mut rng: ResMut<GameRng>,
 ) {
-    let square = meshes.add(create_plane_mesh());
-    let sheep_texture: Handle<Image> = asset_server.load(SHEEP_PATH);
-
-    let sheep_material = materials.add(StandardMaterial {
-        base_color_texture: Some(sheep_texture.clone()),
-        alpha_mode: AlphaMode::Blend,
-        ..default()
-    });
-
     //spawn sheeps
-    let r = level_size.0 / 1.5 / 2.0;
-    let mut rng = rand::thread_rng();
-    let sheep_count = 1000;
+    let r = level.0.flock.radius;
+    let sheep_count = level.0.flock.count;
+    //Lambs carried from the previous night are spawned after all adults, otherwise lambs are random
+    let carried_lambs = level
+        .0
+        .flock
+        .lamb_ages
+        .as_ref()
+        .map(|ages| &ages[..ages.len().min(sheep_count)]);
+    let adult_count = sheep_count - carried_lambs.map_or(0, |ages| ages.len());
 
     let mut exact_sheep_count = 0;
+    let mut adults: Vec<(Entity, Vec3)> = Vec::new();
 
     while exact_sheep_count < sheep_count {
         let x = rng.gen_range(-r..r);
         let y = 0.0;
         let z = rng.gen_range(-r..r);
 
-        let pos = Vec3::new(x, y, z);
+        let mut pos = Vec3::new(x, y, z);
         if pos.length() > r {
             continue;
         }
 
-        commands.spawn((
-            MaterialMeshBundle {
-                mesh: square.clone(),
-                material: sheep_material.clone(),
-                transform: Transform::from_xyz(pos.x, pos.y, pos.z)
-                    .with_rotation(get_sprite_rotation())
-                    .with_scale(Vec3::new(1.0, 1.0, 1.0) * 2.0),
-                ..default()
-            },
-            Sheep::default(),
-            Decision::Idle,
-            Velocity::default(),
-            WalkController {
-                target_velocity: Vec3::new(0.0, 0.0, 0.0),
-                acceleration: SHEEP_ACCELERATION,
-                max_speed: SHEEP_SPEED,
-            },
-            SheepTargetVel::default(),
-            GameStuff,
-            AutoAnim {
-                set: SheepAnim::Idle,
-                timer: Timer::from_seconds(0.1 + rng.gen_range(-0.01..=0.01), TimerMode::Repeating),
-                current_frame: 0
-            },
-            NearestSheep::default()
-        ));
+        //Lamb goes next to a random adult
+        let lamb = match carried_lambs {
+            Some(_) => exact_sheep_count >= adult_count,
+            None => rng.gen_range(0.0..1.0) < LAMB_PART,
+        };
+        let mother = if lamb && !adults.is_empty() {
+            let (mother, mother_pos) = adults[rng.gen_range(0..adults.len())];
+            let angle = rng.gen_range(0.0..PI * 2.0);
+            pos = mother_pos + Vec3::new(angle.cos(), 0.0, angle.sin()) * LAMB_SPAWN_RADIUS;
+            Some(mother)
+        } else {
+            None
+        };
+        let traits = match (carried_lambs, mother) {
+            (Some(ages), Some(_)) => SheepTraits::lamb(&mut *rng, ages[exact_sheep_count - adult_count]),
+            _ => SheepTraits::random(&mut *rng, mother.is_some()),
+        };
+
+        let sheep = spawn_sheep(&mut commands, pos, traits, mother);
+        if mother.is_none() {
+            adults.push((sheep, pos));
+        }
         exact_sheep_count += 1;
     }
 
     commands.insert_resource(StartSheepCount(exact_sheep_count as f32));
 }
 
-#[derive(Component)]
-pub struct IdleFeeding {
-    pub time: f32,
-}
-
-fn idle_feeding_system(
-    mut commands: Commands,
-    mut sheeps: Query<(Entity, &mut Decision, &mut IdleFeeding)>,
-    time: Res<Time>,
-) {
-    for (e, mut dec, mut idle) in sheeps.iter_mut() {
-        idle.time -= time.delta_seconds();
-        if idle.time < 0.0 {
-            *dec = Decision::Idle;
-            commands.entity(e).remove::<IdleFeeding>();
-        }
+/// Spawn a sheep with simulation components, visuals are attached by SheepVisualsPlugin
+pub fn spawn_sheep(
+    commands: &mut Commands,
+    pos: Vec3,
+    traits: SheepTraits,
+    mother: Option<Entity>,
+) -> Entity {
+    let scale = if traits.is_lamb() { LAMB_SCALE } else { 1.0 };
+
+    let mut sheep = commands.spawn((
+        SpatialBundle::from_transform(
+            Transform::from_xyz(pos.x, pos.y, pos.z)
+                .with_rotation(get_sprite_rotation())
+                .with_scale(Vec3::new(1.0, 1.0, 1.0) * 2.0 * scale),
+        ),
+        Sheep::default(),
+        Decision::default(),
+        PreviousDecision::default(),
+        Velocity::default(),
+        WalkController {
+            target_velocity: Vec3::new(0.0, 0.0, 0.0),
+            acceleration: SHEEP_ACCELERATION,
+            max_speed: SHEEP_SPEED * traits.speed_factor(),
+        },
+        SheepTargetVel::default(),
+        GameStuff,
+        NearestSheep::default(),
+        NeighboursUpdated::default(),
+        Fear::default(),
+        traits,
+    ));
+
+    if let Some(mother) = mother {
+        sheep.insert(Mother(mother));
     }
+    sheep.id()
 }
 
-type NNTree = KDTree3<Sheep>;
-
-const PREFERED_DISTANCE: f32 = 1.0;
-const PREFERED_DY: f32 = 0.1;
-
-#[derive(Component, Default)]
-pub struct NearestSheep(pub Vec<(Vec3, Option<Entity>)>);
-
-#[derive(Component)]
-pub struct UpdatedSheep;
+#[derive(Resource)]
+pub struct SheepStorage {
+    pub mesh: Handle<Mesh>,
+    pub material: Handle<StandardMaterial>,
+}
 
-fn update_nearest(
+fn setup_sheep_storage(
     mut commands: Commands,
-    mut sheep : Query<(Entity, &Transform, &mut NearestSheep), (With<Sheep>, Without<UpdatedSheep>)>,
-    mut updated_sheep : Query<Entity, With<UpdatedSheep>>,
-    field: ResMut<NNTree>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    asset_server: Res<AssetServer>,
 ) {
-    let max_sheep_count = 100;
-    let mut idx = 0;
-    for (e, t, mut nearest) in sheep.iter_mut() {
-        idx += 1;
-        if idx > max_sheep_count {
-            break;
-        }
+    let sheep_texture: Handle<Image> = asset_server.load(SHEEP_PATH);
 
-        nearest.0 = field.k_nearest_neighbour(t.translation, 7);
-        commands.entity(e).insert(UpdatedSheep);
-    }
+    commands.insert_resource(SheepStorage {
+        mesh: meshes.add(create_plane_mesh()),
+        material: materials.add(StandardMaterial {
+            base_color_texture: Some(sheep_texture),
+            alpha_mode: AlphaMode::Blend,
+            ..default()
+        }),
+    });
+}
 
-    if sheep.is_empty() {
-        for e in updated_sheep.iter() {
-            commands.entity(e).remove::<UpdatedSheep>();
+fn attach_sheep_visuals(
+    mut commands: Commands,
+    sheep: Query<(Entity, &SheepTraits), Added<Sheep>>,
+    storage: Res<SheepStorage>,
+) {
+    let mut rng = rand::thread_rng();
+    for (e, traits) in sheep.iter() {
+        let timer = Timer::from_seconds(0.1 + rng.gen_range(-0.01..=0.01), TimerMode::Repeating);
+        let mut sheep = commands.entity(e);
+        sheep.insert((storage.mesh.clone(), storage.material.clone()));
+        if traits.is_lamb() {
+            sheep.insert(AutoAnim {
+                set: LambAnim::Idle,
+                timer,
+                current_frame: 0,
+            });
+        } else {
+            sheep.insert(AutoAnim {
+                set: SheepAnim::Idle,
+                timer,
+                current_frame: 0,
+            });
         }
     }
 }
 
+//Neighbours are read from a separate query, so the flock is steered without aliasing
 fn collect_field(
+    params: Res<FlockingParams>,
+    level_size: Res<LevelSize>,
+    pasture: Res<Pasture>,
+    dog: Query<&Transform, With<Dog>>,
+    boids: Query<(&Transform, &Velocity), With<Sheep>>,
     mut sheep: Query<
         (
-            &Transform,
+            Entity,
             &SheepTargetVel,
-            &mut WalkController,
-            &Velocity,
             &Decision,
-            &NearestSheep
+            &NearestSheep,
+            Option<&Mother>,
+            &SheepTraits,
+            &mut WalkController,
         ),
         With<Sheep>,
     >,
 ) {
-    unsafe {
-        for (t, vel, mut walk, _, dec, nearest) in sheep.iter_unsafe() {
-            if *dec != Decision::Idle {
-                let neighboors = &nearest.0;
-
-                let mut sum = Vec3::ZERO;
-                let mut distance_force = Vec3::ZERO;
-
-                let mut sum_targets = Vec3::ZERO;
-                let mut target_weight_sum = 0.0;
-
-                let mut sum_dz = 0.0;
-                let mut count = 0;
-                for (_, n_e) in neighboors.iter().skip(1) {
-                    if let Some(n_e) = n_e {
-                        if let Ok((n_t, n_tar, _, n_vel, _, _)) = sheep.get(*n_e) {
-                            sum += n_vel.0;
-                            count += 1;
-
-                            let dp = t.translation - n_t.translation;
-                            let length = dp.length();
-                            if length < PREFERED_DISTANCE {
-                                distance_force += dp * (1.0 - length / PREFERED_DISTANCE);
-                            }
-
-                            if dp.z.abs() < PREFERED_DY {
-                                sum_dz += (1.0 - dp.z.abs() / PREFERED_DY) * dp.z.signum();
-                            }
-
-                            sum_targets += n_vel.0;
-                            target_weight_sum += n_vel.0.length();
-                        }
-                    }
-                }
+    let dog = dog.get_single().ok().map(|t| t.translation);
+    let mut neighbours = Vec::new();
 
-                if count > 0 {
-                    let mean_targets = sum_targets / (target_weight_sum + 0.000001);
-                    let dist_force = 10.0 * distance_force;
-                    let dz = Vec3::new(0.0, 0.0, sum_dz);
-
-                    let wsum = vel.0.length() + dist_force.length() + dz.length();
-                    let max_length = vel
-                        .0
-                        .length()
-                        .max(mean_targets.length())
-                        .max(dist_force.length());
-                    walk.target_velocity =
-                        (vel.0 + dist_force + dz) / (wsum + 0.000001) * max_length;
-                } else {
-                    walk.target_velocity = vel.0;
-                }
-            }
-        }
+    for (e, desired, dec, nearest, mother, traits, mut walk) in sheep.iter_mut() {
+        let Ok((t, vel)) = boids.get(e) else {
+            continue;
+        };
+        let boid = Boid {
+            position: t.translation,
+            velocity: vel.0,
+        };
+
+        neighbours.clear();
+        neighbours.extend(
+            nearest
+                .0
+                .iter()
+                .filter_map(|(_, n_e)| *n_e)
+                .filter(|n_e| *n_e != e)
+                .filter_map(|n_e| boids.get(n_e).ok())
+                .map(|(n_t, n_vel)| Boid {
+                    position: n_t.translation,
+                    velocity: n_vel.0,
+                }),
+        );
+
+        let surroundings = FlockSurroundings {
+            dog,
+            //Escaping sheep have to leave the pasture
+            bounds: (dec.0 != ESCAPE).then_some(level_size.0),
+            //Mother can be eaten by wolves, then the lamb is on its own
+            mother: mother
+                .and_then(|m| boids.get(m.0).ok())
+                .map(|(m_t, _)| m_t.translation),
+            follow: traits.follow_factor(),
+            grass: pasture.richer_direction(t.translation) * traits.hunger,
+        };
+
+        walk.target_velocity =
+            params.steer(&boid, desired.0, &neighbours, &surroundings, walk.max_speed);
     }
 }
 
-fn set_anim_state(
-    mut sheep : Query<(&mut AutoAnim<SheepAnim>, Option<&GoTo>, Option<&IdleFeeding>, Option<&IsScared>), With<Sheep>>
+fn set_anim_state<T: FlockAnim + Send + Sync + 'static>(
+    mut sheep : Query<(&mut AutoAnim<T>, Option<&GoTo>, Option<&IdleFeeding>, Option<&IsScared>), With<Sheep>>
 ) {
     for (mut anim, go_to, idle, scared) in sheep.iter_mut() {
         if go_to.is_some() {
-            anim.set = SheepAnim::Walk;
+            anim.set = T::walk();
         } else if idle.is_some() {
-            anim.set = SheepAnim::Feed;
+            anim.set = T::feed();
         } else if scared.is_some() {
-            anim.set = SheepAnim::Walk;
+            anim.set = T::walk();
         } else {
-            anim.set = SheepAnim::Idle;
+            anim.set = T::idle();
         }
     }
 }
\ No newline at end of file
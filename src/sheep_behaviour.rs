@@ -0,0 +1,192 @@
+//Framework for sheep behaviours. Sheep is always in one behaviour, stored in its Decision component
+//Behaviour is a plugin which:
+//  - adds allowed transitions with app.add_sheep_transition(from, to)
+//  - reacts to BehaviourEnter / BehaviourExit in SheepBehaviourSet::Enter / Exit (insert or remove its components)
+//  - runs its logic in SheepBehaviourSet::Update and asks for a new behaviour with SheepTransition
+//Idle sheep pick the next behaviour by StateChance weights, so a new behaviour is one plugin plus one weight
+
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{GameSet, SimSet};
+
+pub struct SheepBehaviourPlugin;
+
+impl Plugin for SheepBehaviourPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SheepTransitions>()
+            .add_event::<SheepTransition>()
+            .add_event::<BehaviourEnter>()
+            .add_event::<BehaviourExit>()
+            .register_type::<Decision>();
+
+        app.configure_sets(
+            Update,
+            (
+                SheepBehaviourSet::Update,
+                SheepBehaviourSet::Transition,
+                SheepBehaviourSet::Exit,
+                SheepBehaviourSet::Enter,
+            )
+                .chain()
+                .in_set(GameSet::Playing)
+                .in_set(SimSet::Sheep),
+        );
+
+        app.add_systems(
+            Update,
+            apply_transitions.in_set(SheepBehaviourSet::Transition),
+        );
+    }
+}
+
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub enum SheepBehaviourSet {
+    //Behaviour logic, sends SheepTransition
+    Update,
+    Transition,
+    //Remove components of the old behaviour
+    Exit,
+    //Insert components of the new behaviour
+    Enter,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Reflect)]
+#[reflect_value(PartialEq, Hash, Debug)]
+pub struct BehaviourId(pub &'static str);
+
+impl BehaviourId {
+    //Sheep waits for the next decision. New sheep start here
+    pub const IDLE: BehaviourId = BehaviourId("idle");
+    //Wildcard for the transition table: from any behaviour
+    pub const ANY: BehaviourId = BehaviourId("any");
+}
+
+/// Current behaviour of a sheep
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug, Reflect)]
+#[reflect(Component, Default)]
+pub struct Decision(pub BehaviourId);
+
+impl Default for Decision {
+    fn default() -> Self {
+        Self(BehaviourId::IDLE)
+    }
+}
+
+/// Ask to move the sheep to another behaviour. Ignored if the transition table does not allow it
+#[derive(Event, Clone, Copy, Debug)]
+pub struct SheepTransition {
+    pub sheep: Entity,
+    pub to: BehaviourId,
+}
+
+#[derive(Event, Clone, Copy, Debug)]
+pub struct BehaviourEnter {
+    pub sheep: Entity,
+    pub from: BehaviourId,
+    pub to: BehaviourId,
+}
+
+#[derive(Event, Clone, Copy, Debug)]
+pub struct BehaviourExit {
+    pub sheep: Entity,
+    pub from: BehaviourId,
+    pub to: BehaviourId,
+}
+
+#[derive(Resource, Default, Debug)]
+pub struct SheepTransitions {
+    allowed: HashSet<(BehaviourId, BehaviourId)>,
+}
+
+impl SheepTransitions {
+    pub fn allow(&mut self, from: BehaviourId, to: BehaviourId) {
+        self.allowed.insert((from, to));
+    }
+
+    pub fn is_allowed(&self, from: BehaviourId, to: BehaviourId) -> bool {
+        from != to
+            && (self.allowed.contains(&(from, to)) || self.allowed.contains(&(BehaviourId::ANY, to)))
+    }
+
+    /// Behaviour with the given name, if any transition mentions it
+    pub fn behaviour(&self, name: &str) -> Option<BehaviourId> {
+        self.allowed
+            .iter()
+            .flat_map(|(from, to)| [*from, *to])
+            .find(|id| *id != BehaviourId::ANY && id.0 == name)
+    }
+}
+
+pub trait SheepBehaviourAppExt {
+    fn add_sheep_transition(&mut self, from: BehaviourId, to: BehaviourId) -> &mut Self;
+}
+
+impl SheepBehaviourAppExt for App {
+    fn add_sheep_transition(&mut self, from: BehaviourId, to: BehaviourId) -> &mut Self {
+        self.init_resource::<SheepTransitions>();
+        self.world
+            .resource_mut::<SheepTransitions>()
+            .allow(from, to);
+        self
+    }
+}
+
+//Transitions are applied one by one in the order they were sent, so the same sheep can change
+//behaviour twice in one frame. Exit hooks see every step, enter hooks should use `entered` to skip stale ones
+fn apply_transitions(
+    mut transitions: EventReader<SheepTransition>,
+    table: Res<SheepTransitions>,
+    mut sheep: Query<&mut Decision>,
+    mut enter: EventWriter<BehaviourEnter>,
+    mut exit: EventWriter<BehaviourExit>,
+) {
+    for transition in transitions.read() {
+        let Ok(mut decision) = sheep.get_mut(transition.sheep) else {
+            continue;
+        };
+
+        let from = decision.0;
+        if !table.is_allowed(from, transition.to) {
+            debug!("Sheep transition {:?} -> {:?} is not allowed", from, transition.to);
+            continue;
+        }
+
+        decision.0 = transition.to;
+        exit.send(BehaviourExit {
+            sheep: transition.sheep,
+            from,
+            to: transition.to,
+        });
+        enter.send(BehaviourEnter {
+            sheep: transition.sheep,
+            from,
+            to: transition.to,
+        });
+    }
+}
+
+/// Sheep which entered `behaviour` this frame and are still in it
+pub fn entered(
+    events: &mut EventReader<BehaviourEnter>,
+    decisions: &Query<&Decision>,
+    behaviour: BehaviourId,
+) -> Vec<Entity> {
+    events
+        .read()
+        .filter(|ev| ev.to == behaviour)
+        .filter(|ev| decisions.get(ev.sheep).is_ok_and(|d| d.0 == behaviour))
+        .map(|ev| ev.sheep)
+        .collect()
+}
+
+/// Sheep which left one of `behaviours` this frame, with the behaviour they went to
+pub fn exited(
+    events: &mut EventReader<BehaviourExit>,
+    behaviours: &[BehaviourId],
+) -> Vec<(Entity, BehaviourId)> {
+    events
+        .read()
+        .filter(|ev| behaviours.contains(&ev.from))
+        .map(|ev| (ev.sheep, ev.to))
+        .collect()
+}
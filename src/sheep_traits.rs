@@ -0,0 +1,159 @@
+//Individual sheep. Traits are rolled at spawn from GameRng, needs change during the level:
+//  - boldness: bold sheep are scared only by a close bark, timid ones hear it from further away
+//  - hunger: grows while the sheep is not feeding, grass of the pasture removes it. Hungry sheep feed longer
+//  - fatigue: grows while the sheep runs, tired sheep are slower
+//  - lambs are smaller and slower, they follow their mother (see FlockingParams::mother_weight).
+//    The younger the lamb, the closer it keeps to the mother. Newborns come at dawn, see births.rs
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    physics::{Velocity, WalkController},
+    sheep::{IdleFeeding, Sheep, RANDOM_WALK_SPEED_MULTIPLIER, SHEEP_SPEED},
+    sheep_behaviour::SheepBehaviourSet,
+    GameSet, SimSet,
+};
+
+//Part of the flock which are lambs
+pub const LAMB_PART: f32 = 0.15;
+//Lambs are spawned this close to the mother
+pub const LAMB_SPAWN_RADIUS: f32 = 1.5;
+pub const LAMB_SCALE: f32 = 0.7;
+const LAMB_SPEED: f32 = 0.85;
+pub const ADULT_AGE: f32 = 1.0;
+//Lambs grow up by this every night of the campaign, so a newborn is adult on the fifth night
+pub const NIGHT_AGE: f32 = 0.25;
+const MAX_AGE: f32 = 10.0;
+
+//Full hunger in a minute without food
+const HUNGER_RATE: f32 = 1.0 / 60.0;
+//Full fatigue after 10 seconds at full speed, rest removes it in 20 seconds
+const FATIGUE_RATE: f32 = 0.1;
+const REST_RATE: f32 = 0.05;
+//Tired sheep loses up to this part of the speed
+const FATIGUE_SLOWDOWN: f32 = 0.5;
+
+pub struct SheepTraitsPlugin;
+
+impl Plugin for SheepTraitsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<SheepTraits>().add_systems(
+            Update,
+            (update_needs, apply_fatigue)
+                .chain()
+                .before(SheepBehaviourSet::Update)
+                .in_set(GameSet::Playing)
+                .in_set(SimSet::Sheep),
+        );
+    }
+}
+
+#[derive(Component, Reflect, Clone, Debug, Default)]
+#[reflect(Component, Default)]
+pub struct SheepTraits {
+    //Years
+    pub age: f32,
+    //All in [0, 1]
+    pub boldness: f32,
+    pub hunger: f32,
+    pub fatigue: f32,
+}
+
+/// Mother of a lamb
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Mother(pub Entity);
+
+impl SheepTraits {
+    pub fn random(rng: &mut impl Rng, lamb: bool) -> Self {
+        let age = if lamb {
+            rng.gen_range(0.1..ADULT_AGE)
+        } else {
+            rng.gen_range(ADULT_AGE..MAX_AGE)
+        };
+        //Lambs are timid
+        let boldness = if lamb {
+            rng.gen_range(0.0..0.4)
+        } else {
+            rng.gen_range(0.0..1.0)
+        };
+
+        Self {
+            age,
+            boldness,
+            hunger: rng.gen_range(0.0..0.5),
+            fatigue: 0.0,
+        }
+    }
+
+    /// Lamb of the given age, carried from the previous night
+    pub fn lamb(rng: &mut impl Rng, age: f32) -> Self {
+        Self {
+            age,
+            boldness: rng.gen_range(0.0..0.4),
+            hunger: rng.gen_range(0.0..0.5),
+            fatigue: 0.0,
+        }
+    }
+
+    /// Lamb born this night, full and timid
+    pub fn newborn(rng: &mut impl Rng) -> Self {
+        Self {
+            age: 0.0,
+            boldness: rng.gen_range(0.0..0.2),
+            hunger: 0.0,
+            fatigue: 0.0,
+        }
+    }
+
+    pub fn is_lamb(&self) -> bool {
+        self.age < ADULT_AGE
+    }
+
+    /// Bark radius multiplier, from 1.25 for timid sheep to 0.75 for bold ones
+    pub fn scare_factor(&self) -> f32 {
+        1.25 - 0.5 * self.boldness
+    }
+
+    /// Feeding time multiplier, from 0.5 for full sheep to 1.5 for hungry ones
+    pub fn feeding_factor(&self) -> f32 {
+        0.5 + self.hunger
+    }
+
+    /// Mother pull multiplier, from 2 for newborns to 1 for adult age
+    pub fn follow_factor(&self) -> f32 {
+        1.0 + (1.0 - self.age / ADULT_AGE).max(0.0)
+    }
+
+    pub fn speed_factor(&self) -> f32 {
+        let age = if self.is_lamb() { LAMB_SPEED } else { 1.0 };
+        age * (1.0 - FATIGUE_SLOWDOWN * self.fatigue)
+    }
+}
+
+fn update_needs(
+    time: Res<Time>,
+    mut sheep: Query<(&mut SheepTraits, &Velocity, Option<&IdleFeeding>), With<Sheep>>,
+) {
+    let dt = time.delta_seconds();
+    for (mut traits, vel, feeding) in sheep.iter_mut() {
+        if feeding.is_none() {
+            traits.hunger = (traits.hunger + HUNGER_RATE * dt).min(1.0);
+        }
+
+        //Walking is rest, running is not
+        let walk_speed = SHEEP_SPEED * RANDOM_WALK_SPEED_MULTIPLIER;
+        let run = ((vel.0.length() - walk_speed) / (SHEEP_SPEED - walk_speed)).clamp(0.0, 1.0);
+        if run > 0.0 {
+            traits.fatigue = (traits.fatigue + FATIGUE_RATE * run * dt).min(1.0);
+        } else {
+            traits.fatigue = (traits.fatigue - REST_RATE * dt).max(0.0);
+        }
+    }
+}
+
+fn apply_fatigue(mut sheep: Query<(&SheepTraits, &mut WalkController), With<Sheep>>) {
+    for (traits, mut walk) in sheep.iter_mut() {
+        walk.max_speed = SHEEP_SPEED * traits.speed_factor();
+    }
+}
@@ -8,7 +8,7 @@ use crate::{
     player::{Bark, DOG_ACCELERATION, DOG_SPEED},
     sunday::DayState,
     torch::{IgniteTorch, TorchBase},
-    GameSet, GameStuff, auto_anim::{AutoAnimPlugin, AutoAnim, AnimSet, AnimRange},
+    GameSet, SimSet, VisualsSet, GameStuff, auto_anim::{AutoAnimPlugin, AutoAnim, AnimSet, AnimRange},
 };
 
 const SHEPHERD_PATH: &str = "test/Knight.png";
@@ -25,9 +25,21 @@ impl Plugin for ShepherdPlugin {
         app.add_event::<SpawnShepherd>()
             .add_systems(
                 Update,
-                (spawn_shepherd_system, ignite_all_torhes, bark_system, set_anim).in_set(GameSet::Playing),
+                (spawn_shepherd_system, ignite_all_torhes, bark_system)
+                    .chain()
+                    .in_set(GameSet::Playing)
+                    .in_set(SimSet::Shepherd),
             )
-            .add_systems(OnEnter(DayState::Evening), start_ignite_torches)
+            .add_systems(OnEnter(DayState::Evening), start_ignite_torches);
+    }
+}
+
+pub struct ShepherdVisualsPlugin;
+
+impl Plugin for ShepherdVisualsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, attach_shepherd_visuals.in_set(VisualsSet))
+            .add_systems(Update, set_anim.in_set(GameSet::Playing))
             .add_plugins(AutoAnimPlugin::<ShepherdAnim>::default());
     }
 }
@@ -115,24 +127,15 @@ fn ignite_all_torhes(
 fn spawn_shepherd_system(
     mut commands: Commands,
     mut events: EventReader<SpawnShepherd>,
-    asset_server: Res<AssetServer>,
-    common_storage: Res<CommonStorage>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
     for event in events.read() {
         commands.spawn((
             Shepherd::default(),
-            PbrBundle {
-                transform: Transform::from_translation(event.pos)
+            SpatialBundle::from_transform(
+                Transform::from_translation(event.pos)
                     .with_rotation(get_sprite_rotation())
                     .with_scale(Vec3::new(3.0, 3.0, 3.0)),
-                material: materials.add(StandardMaterial {
-                    base_color_texture: Some(asset_server.load(SHEPHERD_PATH)),
-                    ..default()
-                }),
-                mesh: common_storage.plane.clone(),
-                ..default()
-            },
+            ),
             Velocity::default(),
             WalkController {
                 max_speed: SHEPHERD_SPEED,
@@ -140,6 +143,25 @@ fn spawn_shepherd_system(
                 target_velocity: Vec3::ZERO,
             },
             GameStuff,
+        ));
+    }
+    events.clear();
+}
+
+fn attach_shepherd_visuals(
+    mut commands: Commands,
+    shepherds: Query<Entity, Added<Shepherd>>,
+    asset_server: Res<AssetServer>,
+    common_storage: Res<CommonStorage>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for shepherd in shepherds.iter() {
+        commands.entity(shepherd).insert((
+            materials.add(StandardMaterial {
+                base_color_texture: Some(asset_server.load(SHEPHERD_PATH)),
+                ..default()
+            }),
+            common_storage.plane.clone(),
             AutoAnim {
                 set : ShepherdAnim::Sleep,
                 current_frame: 0,
@@ -147,7 +169,6 @@ fn spawn_shepherd_system(
             }
         ));
     }
-    events.clear();
 }
 
 fn bark_system(
@@ -0,0 +1,62 @@
+//Small text files kept between game launches: save game, control bindings, custom difficulty
+//Native: <config dir>/bevy_sheep/<name>.ron. Wasm: localStorage key "bevy_sheep_<name>"
+
+pub use platform::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod platform {
+    use std::path::PathBuf;
+
+    fn path(name: &str) -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("bevy_sheep").join(format!("{}.ron", name)))
+    }
+
+    pub fn read(name: &str) -> Option<String> {
+        std::fs::read_to_string(path(name)?).ok()
+    }
+
+    pub fn write(name: &str, text: &str) -> std::io::Result<()> {
+        let path = path(name).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, "no config directory")
+        })?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, text)
+    }
+
+    /// Seconds since unix epoch
+    pub fn now() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod platform {
+    fn key(name: &str) -> String {
+        format!("bevy_sheep_{}", name)
+    }
+
+    fn local_storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok().flatten()
+    }
+
+    pub fn read(name: &str) -> Option<String> {
+        local_storage()?.get_item(&key(name)).ok().flatten()
+    }
+
+    pub fn write(name: &str, text: &str) -> Result<(), String> {
+        local_storage()
+            .ok_or_else(|| "no localStorage".to_string())?
+            .set_item(&key(name), text)
+            .map_err(|err| format!("{:?}", err))
+    }
+
+    /// Seconds since unix epoch. SystemTime is not available on wasm
+    pub fn now() -> u64 {
+        (js_sys::Date::now() / 1000.0) as u64
+    }
+}
@@ -1,16 +1,12 @@
 //This global AI is responsible for creating problems for player
 //This module will be determine where and how sheep will be try to escape from safe zone
 
-use std::time::Duration;
-
 use bevy::prelude::*;
-use rand::Rng;
 
 use crate::{
-    player::Dog,
-    sheep::{Sheep, StartSheepCount, IsScared, GoTo},
-    sunday::{DayState, EpisodeTime},
-    GameSet, GameState, test_level::LevelSize,
+    sheep::{Sheep, StartSheepCount},
+    GameSet, SimSet, GameState, difficulty::Difficulty,
+    director::{self, DirectorPlugin},
 };
 
 pub struct StorytellerPlugin;
@@ -18,30 +14,41 @@ pub struct StorytellerPlugin;
 impl Plugin for StorytellerPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(Storyteller {
-            level_start_time: 0.0,
             level_duration: 6.0 * 60.0,
             safearea_count: 1,
             change_safe_area_was_spanwed: false
         })
         .init_resource::<Score>()
+        .init_resource::<ScoreBonus>()
+        .init_resource::<LevelClock>()
         .add_systems(
             Update,
-            (storyteller_system, level_timer).in_set(GameSet::Playing),
+            (
+                advance_level_clock,
+                director::update_tension,
+                director::direct_tasks,
+                level_end_system,
+            )
+                .chain()
+                .in_set(GameSet::Playing)
+                .in_set(SimSet::Storyteller),
         )
         .add_systems(OnEnter(GameState::Playing), setup_start_time)
+        .add_systems(OnExit(GameState::Playing), reset_time_scale)
+        .add_systems(Update, sync_time_scale.run_if(in_state(GameState::Playing)))
         .add_systems(
             FixedUpdate,
-            (score_system, fail_system).in_set(GameSet::Playing),
+            (score_system, fail_system)
+                .chain()
+                .in_set(GameSet::Playing)
+                .in_set(SimSet::Storyteller),
         )
-        .init_resource::<NextTaskDelay>()
-        .add_systems(OnEnter(GlobalTask::None), setup_delay)
-        .add_state::<GlobalTask>();
+        .add_plugins(DirectorPlugin);
     }
 }
 
 #[derive(Resource)]
 pub struct Storyteller {
-    pub level_start_time: f32,
     pub level_duration: f32,
     pub safearea_count: u8,
 
@@ -49,124 +56,86 @@ pub struct Storyteller {
 }
 
 impl Storyteller {
-    pub fn get_level_time(&self, time: &Time) -> f32 {
-        time.elapsed_seconds() - self.level_start_time
+    pub fn get_level_time(&self, clock: &LevelClock) -> f32 {
+        clock.elapsed
     }
 
-    pub fn get_level_unfirom_time(&self, time: &Time) -> f32 {
-        self.get_level_time(time) / self.level_duration
+    pub fn get_level_unfirom_time(&self, clock: &LevelClock) -> f32 {
+        self.get_level_time(clock) / self.level_duration
     }
 }
 
-#[derive(Resource, Default)]
-pub struct Score(pub f32);
-
-fn setup_start_time(mut commands: Commands, mut teller: ResMut<Storyteller>, time: Res<Time>) {
-    commands.remove_resource::<FailReason>();
-    teller.level_start_time = time.elapsed_seconds();
-}
-
+/// Time since the level start. Advanced only while the level is played, so pauses and restarts do not shift it
 #[derive(Resource)]
-pub struct NextTaskDelay(pub f32);
+pub struct LevelClock {
+    pub elapsed: f32,
+    //Speed of the whole simulation: 0.5 is slow motion, 2.0 is fast forward
+    pub scale: f32,
+}
 
-impl Default for NextTaskDelay {
+impl Default for LevelClock {
     fn default() -> Self {
-        Self(10.0)
+        Self {
+            elapsed: 0.0,
+            scale: 1.0,
+        }
     }
 }
 
-fn setup_delay(mut delay: ResMut<NextTaskDelay>) {
-    *delay = NextTaskDelay(10.0);
-}
+#[derive(Resource, Default)]
+pub struct Score(pub f32);
+
+/// Score for completed tasks and births, added on top of the survival score
+#[derive(Resource, Default)]
+pub struct ScoreBonus(pub f32);
 
-fn storyteller_system(
+fn setup_start_time(
     mut commands: Commands,
-    sheep: Query<(Entity, &Transform), (With<Sheep>, Without<IsScared>, Without<GoTo>)>,
-    mut teller: ResMut<Storyteller>,
-    time: Res<Time>,
-    level_size: Res<LevelSize>,
-    dog: Query<&Transform, With<Dog>>,
-
-    current_task: Res<State<GlobalTask>>,
-    mut next_task: ResMut<NextState<GlobalTask>>,
-    day_state: Res<State<DayState>>,
-    episode_time: Res<EpisodeTime>,
-    mut delay: ResMut<NextTaskDelay>,
+    mut clock: ResMut<LevelClock>,
+    mut bonus: ResMut<ScoreBonus>,
 ) {
-    if *current_task != GlobalTask::None {
-        return;
-    }
+    commands.remove_resource::<FailReason>();
+    *clock = LevelClock::default();
+    bonus.0 = 0.0;
+}
 
-    let Ok(_dog_transform) = dog.get_single() else {
-        return;
-    };
-    if *current_task == GlobalTask::None {
-        delay.0 -= time.delta_seconds();
-        if delay.0 > 0.0 {
-            return;
-        }
-        let level_time = time.elapsed_seconds() - teller.level_start_time;
-        let unfiorm_time = level_time / teller.level_duration;
-
-        // let episode_time = episode_time.0;
-
-        match &day_state.get() {
-            DayState::Day => {
-                if episode_time.0 > 0.5 && !teller.change_safe_area_was_spanwed {
-                    teller.change_safe_area_was_spanwed = true;
-                    next_task.set(GlobalTask::ChangeSafeArea);
-                } else {
-                    next_task.set(GlobalTask::SheepEscape);
-                }
-            }
-            DayState::Evening => {}
-            DayState::Night => {
-                let mut rng = rand::thread_rng();
-                let rand_choise = rng.gen_range(0..2);
-                if rand_choise == 0 {
-                    next_task.set(GlobalTask::SheepEscape);
-                } else if rand_choise == 1 {
-                    next_task.set(GlobalTask::TorchProblem);
-                }
-            }
-        }
+//Virtual time is already scaled, so the clock follows the same speed as the rest of simulation
+fn advance_level_clock(mut clock: ResMut<LevelClock>, time: Res<Time>) {
+    clock.elapsed += time.delta_seconds();
+}
+
+fn sync_time_scale(clock: Res<LevelClock>, mut time: ResMut<Time<Virtual>>) {
+    if time.relative_speed() != clock.scale {
+        time.set_relative_speed(clock.scale);
     }
 }
 
+fn reset_time_scale(mut time: ResMut<Time<Virtual>>) {
+    time.set_relative_speed(1.0);
+}
+
 #[derive(Component)]
 pub struct LevelTimer;
 
-fn level_timer(
-    mut timers: Query<&mut Text, With<LevelTimer>>,
+fn level_end_system(
     teller: Res<Storyteller>,
-    time: Res<Time>,
+    clock: Res<LevelClock>,
     mut next_state: ResMut<NextState<GameState>>,
-    score: Res<Score>,
 ) {
-    for mut timer in timers.iter_mut() {
-        let level_time = time.elapsed_seconds() - teller.level_start_time;
-        if teller.level_duration - level_time > 0.0 {
-            let dur = Duration::from_secs_f32(teller.level_duration - level_time);
-
-            let time = format!("{:02}:{:02}", dur.as_secs() / 60, dur.as_secs() % 60);
-            let score_text = format!("Score: {:.1}", score.0);
-
-            timer.sections[0].value = format!("{} {}", time, score_text);
-        } else {
-            timer.sections[0].value = format!("{:02}:{:02}", 0, 0);
-            next_state.set(GameState::Finish);
-        }
+    if teller.level_duration - teller.get_level_time(&clock) <= 0.0 {
+        next_state.set(GameState::Finish);
     }
 }
 
 fn score_system(
     mut score: ResMut<Score>,
     alived_sheep: Query<&Sheep>,
-    time: Res<Time>,
+    clock: Res<LevelClock>,
     start_sheep_count: Res<StartSheepCount>,
+    bonus: Res<ScoreBonus>,
 ) {
     let lived_sheep = alived_sheep.iter().count() as f32 / start_sheep_count.0;
-    score.0 = lived_sheep * time.elapsed_seconds();
+    score.0 = lived_sheep * clock.elapsed + bonus.0;
 }
 
 fn fail_system(
@@ -174,8 +143,9 @@ fn fail_system(
     mut next_state: ResMut<NextState<GameState>>,
     alived_sheep: Query<&Sheep>,
     start_sheep_count: Res<StartSheepCount>,
+    difficulty: Res<Difficulty>,
 ) {
-    if (alived_sheep.iter().count() as f32 / start_sheep_count.0) < 0.5 {
+    if (alived_sheep.iter().count() as f32 / start_sheep_count.0) < difficulty.fail_threshold {
         next_state.set(GameState::Finish);
         commands.insert_resource(FailReason::SheepDied);
     }
@@ -186,14 +156,3 @@ pub enum FailReason {
     SheepDied,
     TaskFailed(String),
 }
-
-#[derive(States, Debug, Clone, Eq, PartialEq, Hash, Default)]
-pub enum GlobalTask {
-    #[default]
-    None,
-    SheepEscape,
-    WolfAttack,
-    CollectSheepInArea,
-    TorchProblem,
-    ChangeSafeArea
-}
@@ -5,8 +5,8 @@ use rand::Rng;
 
 use crate::{
     safe_area::{LandSafeArea, SafeArea},
-    storyteller::Storyteller,
-    GameSet,
+    storyteller::{LevelClock, Storyteller},
+    GameSet, SimSet,
 };
 
 pub struct SundayPlugin;
@@ -35,25 +35,32 @@ const NIGHT_TIME: f32 = 0.8;
 
 impl Plugin for SundayPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, sunday_system.in_set(GameSet::Playing));
         app.add_systems(
             Update,
-            (set_day_state, set_episode_time).in_set(GameSet::Playing),
-        );
-        app.add_state::<DayState>();
-
-        app.add_systems(
-            Update,
-            safe_area_evening_decrease
+            (
+                set_day_state,
+                set_episode_time,
+                safe_area_evening_decrease.run_if(in_state(DayState::Evening)),
+            )
+                .chain()
                 .in_set(GameSet::Playing)
-                .run_if(in_state(DayState::Evening)),
+                .in_set(SimSet::Sunday),
         );
+        app.add_state::<DayState>();
         app.add_systems(OnEnter(DayState::Night), delete_land_area_at_night);
 
         app.init_resource::<EpisodeTime>();
     }
 }
 
+pub struct SundayVisualsPlugin;
+
+impl Plugin for SundayVisualsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, sunday_system.in_set(GameSet::Playing));
+    }
+}
+
 #[derive(Resource, Default)]
 pub struct EpisodeTime(pub f32);
 
@@ -69,9 +76,9 @@ fn set_day_state(
     mut state: ResMut<NextState<DayState>>,
     current_state: Res<State<DayState>>,
     teller: Res<Storyteller>,
-    time: Res<Time>,
+    clock: Res<LevelClock>,
 ) {
-    let uniform_time = teller.get_level_unfirom_time(&time);
+    let uniform_time = teller.get_level_unfirom_time(&clock);
     if uniform_time < DAY_TIME {
         if *current_state != DayState::Day {
             state.set(DayState::Day);
@@ -89,11 +96,11 @@ fn set_day_state(
 
 fn set_episode_time(
     mut episode: ResMut<EpisodeTime>,
-    time: Res<Time>,
+    clock: Res<LevelClock>,
     teller: Res<Storyteller>,
     day_state: Res<State<DayState>>,
 ) {
-    let uniform_time = teller.get_level_unfirom_time(&time);
+    let uniform_time = teller.get_level_unfirom_time(&clock);
     match *day_state.get() {
         DayState::Day => {
             episode.0 = uniform_time / DAY_TIME;
@@ -108,7 +115,7 @@ fn set_episode_time(
 }
 
 fn sunday_system(
-    time: Res<Time>,
+    clock: Res<LevelClock>,
     teller: Res<Storyteller>,
     mut sun: Query<(&mut Transform, &mut DirectionalLight)>,
     mut ambient_light: ResMut<AmbientLight>,
@@ -118,7 +125,7 @@ fn sunday_system(
         return;
     };
 
-    let uniform_time = teller.get_level_unfirom_time(&time);
+    let uniform_time = teller.get_level_unfirom_time(&clock);
 
     if uniform_time < DAY_TIME {
         let sun_falloff = 1.0;
@@ -169,10 +176,10 @@ fn sunday_system(
 
 fn safe_area_evening_decrease(
     mut areas: Query<(&mut SafeArea, &LandSafeArea)>,
-    time: Res<Time>,
+    clock: Res<LevelClock>,
     teller: Res<Storyteller>,
 ) {
-    let uniform_time = teller.get_level_unfirom_time(&time);
+    let uniform_time = teller.get_level_unfirom_time(&clock);
     let evening_time = (uniform_time - DAY_TIME) / (EVENING_TIME - DAY_TIME);
     let scale = 1.0 - evening_time;
     for (mut area, land_area) in areas.iter_mut() {
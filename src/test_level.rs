@@ -4,17 +4,19 @@ use std::f32::consts::PI;
 
 use crate::{
     get_sprite_rotation,
+    level::{CurrentLevel, TorchPlacement},
     level_ui::CreateLevelUi,
     player::SpawnPlayer,
-    safe_area::{LandSafeArea, SafeArea},
+    safe_area::LandSafeArea,
     shepherd::SpawnShepherd,
-    sprite_material::{create_plane_mesh, SpriteMaterial},
+    sprite_material::create_plane_mesh,
     sunday::{AMBIENT_BASE_ILLUMINANCE, DAY_SUN_COLOR, SUN_BASE_ILLUMINANCE},
     torch::{SpawnTorch, TORCH_BASE_RADIUS},
-    GameStuff,
+    GameStuff, game_rng::GameRng,
 };
 
 const TREE_PATH: &str = "test/pine.png";
+const TREE_SEED_SALT: u64 = 0x7ee5;
 
 #[derive(Clone, Resource)]
 pub struct LevelSize(pub f32);
@@ -26,16 +28,81 @@ impl Default for LevelSize {
 }
 
 pub fn setup(
+    mut commands: Commands,
+    mut spawn_player_event: EventWriter<SpawnPlayer>,
+    mut spawn_torch: EventWriter<SpawnTorch>,
+    level: Res<CurrentLevel>,
+    mut level_size: ResMut<LevelSize>,
+    mut spawn_shepherd: EventWriter<SpawnShepherd>,
+    mut rng: ResMut<GameRng>,
+) {
+    let level = &level.0;
+    *level_size = LevelSize(level.size);
+
+    spawn_player_event.send(SpawnPlayer {
+        position: ground_pos(level.player_spawn),
+    });
+
+    match &level.torches {
+        TorchPlacement::Random { count, range } => {
+            let torch_r = *range;
+            let mut torch_poses = vec![];
+            for _ in 0..*count {
+                let pos = Vec3::new(
+                    rng.gen_range(-torch_r..torch_r),
+                    0.0,
+                    rng.gen_range(-torch_r..torch_r),
+                );
+
+                let mut neared_dist_to_another_torch = f32::MAX;
+                for torch_pos in &torch_poses {
+                    let dist = pos.distance(*torch_pos);
+                    neared_dist_to_another_torch = neared_dist_to_another_torch.min(dist);
+                }
+                if neared_dist_to_another_torch < TORCH_BASE_RADIUS {
+                    continue;
+                }
+
+                spawn_torch.send(SpawnTorch { position: pos });
+                torch_poses.push(pos);
+            }
+        }
+        TorchPlacement::Fixed(positions) => {
+            for pos in positions {
+                spawn_torch.send(SpawnTorch {
+                    position: ground_pos(*pos),
+                });
+            }
+        }
+    }
+
+    for safe_area in &level.safe_areas {
+        commands
+            .spawn(safe_area.clone())
+            .insert(LandSafeArea {
+                start_area: safe_area.clone(),
+            })
+            .insert(GameStuff);
+    }
+
+    spawn_shepherd.send(SpawnShepherd {
+        pos: ground_pos(level.shepherd_spawn),
+    });
+}
+
+fn ground_pos(pos: Vec2) -> Vec3 {
+    Vec3::new(pos.x, 0.0, pos.y)
+}
+
+//Sun, trees, ground and level ui. Only visual stuff, so it is not needed for headless simulation
+pub fn setup_decorations(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     asset_server: Res<AssetServer>,
-    mut spawn_player_event: EventWriter<SpawnPlayer>,
-    mut spawn_torch: EventWriter<SpawnTorch>,
-    level_size: Res<LevelSize>,
+    level: Res<CurrentLevel>,
     mut create_level_ui: EventWriter<CreateLevelUi>,
-    mut spawn_shepherd: EventWriter<SpawnShepherd>,
-    mut sprite_materials : ResMut<Assets<SpriteMaterial>>,
+    game_rng: Res<GameRng>,
 ) {
     //spawn sun
     let mut cascades = CascadeShadowConfigBuilder::default();
@@ -62,8 +129,9 @@ pub fn setup(
     let square = meshes.add(create_plane_mesh());
     let tree_texture: Handle<Image> = asset_server.load(TREE_PATH);
 
-    let r = level_size.0;
-    let mut rng = rand::thread_rng();
+    let r = level.0.size;
+    //trees have own generator, so presentation does not change simulation random sequence
+    let mut rng = game_rng.fork(TREE_SEED_SALT);
 
     //spawn trees
     let tree_material = materials.add(StandardMaterial {
@@ -77,7 +145,7 @@ pub fn setup(
     let cut_r = r + 5.0;
 
     let tree_area_size = PI * tree_r * tree_r - PI * cut_r * cut_r;
-    let tree_per_meter = 0.3;
+    let tree_per_meter = level.0.tree_density;
     let tree_count = (tree_area_size * tree_per_meter) as usize;
 
     for _ in 0..tree_count {
@@ -119,47 +187,5 @@ pub fn setup(
         })
         .insert(GameStuff);
 
-    spawn_player_event.send(SpawnPlayer {
-        position: Vec3::new(-r - 2.0, 0.0, 0.0),
-    });
-
-    let num_of_torchs = 20;
-    let torch_r = r / 2.0;
-    let mut torch_poses = vec![];
-    for _ in 0..num_of_torchs {
-        let pos = Vec3::new(
-            rng.gen_range(-torch_r..torch_r),
-            0.0,
-            rng.gen_range(-torch_r..torch_r),
-        );
-
-        let mut neared_dist_to_another_torch = f32::MAX;
-        for torch_pos in &torch_poses {
-            let dist = pos.distance(*torch_pos);
-            neared_dist_to_another_torch = neared_dist_to_another_torch.min(dist);
-        }
-        if neared_dist_to_another_torch < TORCH_BASE_RADIUS {
-            continue;
-        }
-
-        spawn_torch.send(SpawnTorch { position: pos });
-        torch_poses.push(pos);
-    }
-
-    let safe_area = SafeArea::Rect {
-        pos: Vec2::ZERO,
-        size: Vec2::new(r * 1.5, r * 1.5),
-    };
-    commands
-        .spawn(safe_area.clone())
-        .insert(LandSafeArea {
-            start_area: safe_area,
-        })
-        .insert(GameStuff);
-
-    spawn_shepherd.send(SpawnShepherd {
-        pos: Vec3::new(0.0, 0.0, -level_size.0),
-    });
-
     create_level_ui.send(CreateLevelUi);
 }
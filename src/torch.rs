@@ -5,7 +5,7 @@ use crate::{
     get_sprite_rotation,
     global_task::torch_blinking::TorchDelight,
     safe_area::{HiddenSafeArea, SafeArea},
-    GameSet, GameStuff,
+    GameSet, GameStuff, SimSet, VisualsSet,
 };
 
 const TORCH_PATH: &str = "test/torch.png";
@@ -18,10 +18,22 @@ pub struct TorchPlugin;
 impl Plugin for TorchPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<SpawnTorch>()
-            .add_systems(Startup, setup_material)
-            .add_systems(Update, spawn_torch)
             .add_event::<IgniteTorch>()
-            .add_systems(Update, ignite_torch.in_set(GameSet::Playing))
+            .add_systems(
+                Update,
+                (spawn_torch, ignite_torch.in_set(GameSet::Playing))
+                    .chain()
+                    .in_set(SimSet::Torch),
+            );
+    }
+}
+
+pub struct TorchVisualsPlugin;
+
+impl Plugin for TorchVisualsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_material)
+            .add_systems(Update, attach_torch_visuals.in_set(VisualsSet))
             .add_systems(FixedUpdate, torch_audio.in_set(GameSet::Playing));
     }
 }
@@ -103,8 +115,6 @@ fn setup_material(
 fn spawn_torch(
     mut commands: Commands,
     mut events: EventReader<SpawnTorch>,
-    common_storage: Res<CommonStorage>,
-    torch_material: Res<TorchMaterial>,
 ) {
     for event in events.read() {
         let torch_radius = TORCH_BASE_RADIUS;
@@ -145,20 +155,30 @@ fn spawn_torch(
 
         commands.spawn((
             torch,
-            PbrBundle {
-                transform: Transform::from_translation(event.position)
+            SpatialBundle::from_transform(
+                Transform::from_translation(event.position)
                     .with_rotation(get_sprite_rotation())
                     .with_scale(Vec3::new(2.0 / 7.0, 2.0 / 7.0, 2.0)),
-                material: torch_material.0.clone(),
-                mesh: common_storage.plane.clone(),
-                ..default()
-            },
+            ),
             GameStuff,
         ));
     }
     events.clear();
 }
 
+fn attach_torch_visuals(
+    mut commands: Commands,
+    torches: Query<Entity, Added<TorchBase>>,
+    common_storage: Res<CommonStorage>,
+    torch_material: Res<TorchMaterial>,
+) {
+    for torch in torches.iter() {
+        commands
+            .entity(torch)
+            .insert((common_storage.plane.clone(), torch_material.0.clone()));
+    }
+}
+
 #[derive(Event)]
 pub struct IgniteTorch {
     pub position: Vec3,
@@ -176,17 +196,17 @@ fn ignite_torch(
             if (transform.translation - event.position).length() < event.radius {
                 torch.lit = true;
                 torch.fuel = torch.max_fuel;
+                commands
+                    .entity(torch_e)
+                    .insert(SafeArea::Circle {
+                        pos: Vec2::new(transform.translation.x, transform.translation.z),
+                        radius: torch.radius,
+                    })
+                    .insert(HiddenSafeArea)
+                    .remove::<TorchDelight>();
+
                 if let Ok(mut light) = lights.get_mut(torch.light) {
                     light.intensity = TORCH_ILLUMINATION;
-                    commands
-                        .entity(torch_e)
-                        .insert(SafeArea::Circle {
-                            pos: Vec2::new(transform.translation.x, transform.translation.z),
-                            radius: torch.radius,
-                        })
-                        .insert(HiddenSafeArea)
-                        .remove::<TorchDelight>();
-
                     light.outer_angle = 2.0_f32.atan();
                     light.inner_angle = light.outer_angle * 0.95;
                     light.color = torch.color;
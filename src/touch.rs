@@ -0,0 +1,318 @@
+//On-screen controls for touch screens. Added by the mobile crate on top of GamePlugin
+//Left half of the screen is a virtual joystick, bottom right corner has bark / big bark / sprint buttons,
+//two fingers elsewhere zoom the camera
+
+use bevy::{
+    input::{touch::Touch, InputSystem},
+    prelude::*,
+};
+
+use crate::{
+    controls::{ControlAction, VirtualInput},
+    pause::PauseState,
+    player::{CameraZoom, PlayerInputSet},
+    GameSet, GameState, GameStuff,
+};
+
+//Finger offset from the joystick center which gives full speed
+const JOYSTICK_RADIUS: f32 = 60.0;
+const KNOB_SIZE: f32 = 40.0;
+const BUTTON_SIZE: f32 = 80.0;
+//Touches which start in this corner belong to the buttons and are not used for pinch
+const BUTTON_PANEL_WIDTH: f32 = BUTTON_SIZE * 2.0 + 40.0;
+const BUTTON_PANEL_HEIGHT: f32 = BUTTON_SIZE * 2.0 + 40.0;
+
+pub struct TouchControlsPlugin;
+
+impl Plugin for TouchControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Joystick>()
+            .add_systems(
+                PreUpdate,
+                (joystick_input, touch_buttons_input, pinch_zoom)
+                    .after(InputSystem)
+                    .before(PlayerInputSet)
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(in_state(PauseState::Running)),
+            )
+            .add_systems(OnEnter(GameState::Playing), setup_touch_controls)
+            .add_systems(OnExit(GameState::Playing), reset_touch_input)
+            .add_systems(OnEnter(PauseState::Paused), reset_touch_input)
+            .add_systems(Update, (update_joystick_ui, pause_button).in_set(GameSet::Playing));
+    }
+}
+
+#[derive(Resource, Default)]
+struct Joystick {
+    //Finger which holds the joystick and position where it touched the screen
+    touch: Option<(u64, Vec2)>,
+    position: Vec2,
+}
+
+#[derive(Component)]
+struct JoystickBase;
+
+#[derive(Component)]
+struct JoystickKnob;
+
+#[derive(Component)]
+struct TouchButton {
+    action: ControlAction,
+    //Finger which has pressed the button
+    touch: Option<u64>,
+}
+
+#[derive(Component)]
+struct TouchPauseButton;
+
+fn in_button_panel(position: Vec2, window: &Window) -> bool {
+    position.x > window.width() - BUTTON_PANEL_WIDTH
+        && position.y > window.height() - BUTTON_PANEL_HEIGHT
+}
+
+fn joystick_input(
+    touches: Res<Touches>,
+    windows: Query<&Window>,
+    mut joystick: ResMut<Joystick>,
+    mut virtual_input: ResMut<VirtualInput>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+
+    if let Some((id, _)) = joystick.touch {
+        if touches.get_pressed(id).is_none() {
+            joystick.touch = None;
+        }
+    }
+
+    if joystick.touch.is_none() {
+        joystick.touch = touches
+            .iter_just_pressed()
+            .find(|touch| touch.position().x < window.width() / 2.0)
+            .map(|touch| (touch.id(), touch.position()));
+    }
+
+    virtual_input.move_axis = match joystick.touch {
+        Some((id, start)) => {
+            let position = touches.get_pressed(id).map(Touch::position).unwrap_or(start);
+            joystick.position = position;
+            //Screen y goes down, move axis y goes forward
+            let offset = (position - start) / JOYSTICK_RADIUS;
+            Vec2::new(offset.x, -offset.y).clamp_length_max(1.0)
+        }
+        None => Vec2::ZERO,
+    };
+}
+
+//Button is held while the finger which has pressed it stays on the button. Fingers which slide onto a button
+//do not press it. Interaction is not used, UI focus follows only the first touch,
+//so a button would not work while the other finger holds the joystick
+fn touch_buttons_input(
+    touches: Res<Touches>,
+    mut buttons: Query<(&Node, &GlobalTransform, &mut TouchButton)>,
+    mut virtual_input: ResMut<VirtualInput>,
+) {
+    for (node, transform, mut button) in buttons.iter_mut() {
+        let rect = node.logical_rect(transform);
+        if let Some(id) = button.touch {
+            if !touches.get_pressed(id).is_some_and(|touch| rect.contains(touch.position())) {
+                button.touch = None;
+            }
+        }
+        if button.touch.is_none() {
+            button.touch = touches
+                .iter_just_pressed()
+                .find(|touch| rect.contains(touch.position()))
+                .map(Touch::id);
+        }
+    }
+
+    virtual_input.pressed = buttons
+        .iter()
+        .filter(|(_, _, button)| button.touch.is_some())
+        .map(|(_, _, button)| button.action)
+        .collect();
+}
+
+fn pinch_zoom(
+    touches: Res<Touches>,
+    windows: Query<&Window>,
+    joystick: Res<Joystick>,
+    pause_button: Query<(&Node, &GlobalTransform), With<TouchPauseButton>>,
+    mut zoom: EventWriter<CameraZoom>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+
+    let joystick_id = joystick.touch.map(|(id, _)| id);
+    let pause_rect = pause_button
+        .get_single()
+        .ok()
+        .map(|(node, transform)| node.logical_rect(transform));
+    let pinch = touches
+        .iter()
+        .filter(|touch| Some(touch.id()) != joystick_id)
+        .filter(|touch| !in_button_panel(touch.start_position(), window))
+        .filter(|touch| !pause_rect.is_some_and(|rect| rect.contains(touch.start_position())))
+        .collect::<Vec<_>>();
+
+    let [a, b] = pinch.as_slice() else {
+        return;
+    };
+
+    let distance = a.position().distance(b.position());
+    let previous_distance = a.previous_position().distance(b.previous_position());
+    if distance > 1.0 && previous_distance > 1.0 && distance != previous_distance {
+        //Fingers apart bring the camera closer
+        zoom.send(CameraZoom(previous_distance / distance));
+    }
+}
+
+fn reset_touch_input(mut joystick: ResMut<Joystick>, mut virtual_input: ResMut<VirtualInput>) {
+    *joystick = Joystick::default();
+    *virtual_input = VirtualInput::default();
+}
+
+fn setup_touch_controls(mut commands: Commands) {
+    let mut text_style = TextStyle::default();
+    text_style.font_size = 18.0;
+
+    commands.spawn((
+        JoystickBase,
+        GameStuff,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Px(JOYSTICK_RADIUS * 2.0),
+                height: Val::Px(JOYSTICK_RADIUS * 2.0),
+                ..default()
+            },
+            background_color: Color::rgba(1.0, 1.0, 1.0, 0.15).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    ));
+
+    commands.spawn((
+        JoystickKnob,
+        GameStuff,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Px(KNOB_SIZE),
+                height: Val::Px(KNOB_SIZE),
+                ..default()
+            },
+            background_color: Color::rgba(1.0, 1.0, 1.0, 0.4).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    ));
+
+    commands
+        .spawn((
+            GameStuff,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(20.0),
+                    bottom: Val::Px(20.0),
+                    width: Val::Px(BUTTON_PANEL_WIDTH - 40.0),
+                    flex_wrap: FlexWrap::WrapReverse,
+                    justify_content: JustifyContent::FlexEnd,
+                    ..default()
+                },
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            for (action, text) in [
+                (ControlAction::Bark, "Bark"),
+                (ControlAction::BigBark, "Big bark"),
+                (ControlAction::Sprint, "Sprint"),
+            ] {
+                parent
+                    .spawn((TouchButton { action, touch: None }, touch_button_bundle()))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(text, text_style.clone()));
+                    });
+            }
+        });
+
+    commands
+        .spawn((
+            TouchPauseButton,
+            GameStuff,
+            ButtonBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(20.0),
+                    top: Val::Px(20.0),
+                    width: Val::Px(BUTTON_SIZE / 2.0),
+                    height: Val::Px(BUTTON_SIZE / 2.0),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.4).into(),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section("II", text_style.clone()));
+        });
+}
+
+fn touch_button_bundle() -> ButtonBundle {
+    ButtonBundle {
+        style: Style {
+            width: Val::Px(BUTTON_SIZE),
+            height: Val::Px(BUTTON_SIZE),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        },
+        background_color: Color::rgba(0.0, 0.0, 0.0, 0.4).into(),
+        ..default()
+    }
+}
+
+fn update_joystick_ui(
+    joystick: Res<Joystick>,
+    mut base: Query<(&mut Style, &mut Visibility), (With<JoystickBase>, Without<JoystickKnob>)>,
+    mut knob: Query<(&mut Style, &mut Visibility), (With<JoystickKnob>, Without<JoystickBase>)>,
+) {
+    let (Ok((mut base_style, mut base_visibility)), Ok((mut knob_style, mut knob_visibility))) =
+        (base.get_single_mut(), knob.get_single_mut())
+    else {
+        return;
+    };
+
+    let Some((_, start)) = joystick.touch else {
+        *base_visibility = Visibility::Hidden;
+        *knob_visibility = Visibility::Hidden;
+        return;
+    };
+
+    let knob_position = start + (joystick.position - start).clamp_length_max(JOYSTICK_RADIUS);
+
+    base_style.left = Val::Px(start.x - JOYSTICK_RADIUS);
+    base_style.top = Val::Px(start.y - JOYSTICK_RADIUS);
+    knob_style.left = Val::Px(knob_position.x - KNOB_SIZE / 2.0);
+    knob_style.top = Val::Px(knob_position.y - KNOB_SIZE / 2.0);
+    *base_visibility = Visibility::Visible;
+    *knob_visibility = Visibility::Visible;
+}
+
+fn pause_button(
+    buttons: Query<&Interaction, (Changed<Interaction>, With<TouchPauseButton>)>,
+    mut next_state: ResMut<NextState<PauseState>>,
+) {
+    for interaction in buttons.iter() {
+        if *interaction == Interaction::Pressed {
+            next_state.set(PauseState::Paused);
+        }
+    }
+}
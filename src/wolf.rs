@@ -5,24 +5,27 @@ use crate::{
     common_storage::CommonStorage,
     get_sprite_rotation,
     physics::{Velocity, WalkController},
-    player::{Bark, DOG_SPEED},
-    safe_area::{OutOfSafeArea, SafeArea},
+    player::DOG_SPEED,
+    safe_area::{OutOfSafeArea, SafeArea, TargetPen},
     test_level::LevelSize,
-    GameStuff, auto_anim::{AnimSet, AnimRange, AutoAnimPlugin, AutoAnim}, corpse::SpawnCorpse,
+    wolf_pack::{pack_bark, pack_spawner, pack_tactics},
+    GameStuff, SimSet, VisualsSet, auto_anim::{AnimSet, AnimRange, AutoAnimPlugin, AutoAnim}, corpse::SpawnCorpse,
 };
 
-const WOLF_SPEED: f32 = DOG_SPEED * 1.3;
+pub const WOLF_SPEED: f32 = DOG_SPEED * 1.3;
 const WOLF_ACCEL: f32 = WOLF_SPEED * 2.0;
 
 pub struct WolfPlugin;
 
 impl Plugin for WolfPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup_wolf_material).add_systems(
+        app.add_systems(
             Update,
             (
                 apply_deferred,
-                wolf_spawner,
+                pack_spawner,
+                apply_deferred,
+                pack_tactics,
                 catch_system,
                 apply_deferred,
                 eating_system,
@@ -30,11 +33,22 @@ impl Plugin for WolfPlugin {
                 go_out_system,
                 apply_deferred,
                 run_out_system,
-                bark,
+                pack_bark,
                 apply_deferred,
             )
-                .chain(),
-        ).add_plugins(AutoAnimPlugin::<WolfAnim>::default());
+                .chain()
+                .in_set(SimSet::Wolf),
+        );
+    }
+}
+
+pub struct WolfVisualsPlugin;
+
+impl Plugin for WolfVisualsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_wolf_material)
+            .add_systems(Update, (attach_wolf_visuals.in_set(VisualsSet), set_anim_state, kill_sound))
+            .add_plugins(AutoAnimPlugin::<WolfAnim>::default());
     }
 }
 
@@ -101,56 +115,16 @@ fn setup_wolf_material(
     })
 }
 
-fn wolf_spawner(
-    mut commands: Commands,
-    sheep: Query<(Entity, &Transform), (With<OutOfSafeArea>, Without<UnderHunting>)>,
-    level_size: Res<LevelSize>,
-    common_storage: Res<CommonStorage>,
-    wolf_storage: Res<WolfStorage>,
-    wolfs : Query<(), With<Wolf>>,
-    safe_areas : Query<&SafeArea>,
-) {
-    let num_wolfs = wolfs.iter().count();
-    if num_wolfs > 20 {
-        return;
-    }
-    for (sheep_entity, sheep_transform) in sheep.iter() {
-
-        let start_pos = sheep_transform.translation.normalize() * level_size.0 * 2.0;
-        let dir = (sheep_transform.translation - start_pos).normalize();
-        let t_to_ship = (sheep_transform.translation - start_pos).length();
-        let mut has_cross_to_safe = false;
-        for safe_area in safe_areas.iter() {
-            if let SafeArea::Circle { pos, radius } = safe_area {
-                let t = (-start_pos.x * dir.x - start_pos.y * dir.y + pos.x * dir.x + pos.y * dir.y) / (dir.x * dir.x + dir.y * dir.y);
-                let nearest_pos = start_pos + dir * t;
-                if (Vec2::new(nearest_pos.x, nearest_pos.z) - *pos).length() < *radius && t > 0.0 && t < t_to_ship {
-                    has_cross_to_safe = true;
-                    break;
-                }
-            }
-        }
-
-        if has_cross_to_safe {
-            continue;
-        }
-
-        commands.spawn((
+/// Wolf which runs nowhere yet, pack gives it a job
+pub fn spawn_wolf(commands: &mut Commands, pos: Vec3) -> Entity {
+    commands
+        .spawn((
             Wolf,
-            PbrBundle {
-                mesh: common_storage.plane.clone(),
-                material: wolf_storage.material.clone(),
-                transform: Transform::from_translation(
-                    sheep_transform.translation.normalize() * level_size.0 * 2.0,
-                )
-                .with_rotation(get_sprite_rotation())
-                .with_scale(Vec3::new(1.0, 1.0, 1.0) * 2.0),
-                ..default()
-            },
-            TryToCatchSheep {
-                target: sheep_entity,
-                ignore_safe : false
-            },
+            SpatialBundle::from_transform(
+                Transform::from_translation(pos)
+                    .with_rotation(get_sprite_rotation())
+                    .with_scale(Vec3::new(1.0, 1.0, 1.0) * 2.0),
+            ),
             Velocity::default(),
             WalkController {
                 max_speed: WOLF_SPEED,
@@ -158,68 +132,49 @@ fn wolf_spawner(
                 target_velocity: Vec3::ZERO,
             },
             GameStuff,
-            AutoAnim {
-                set: WolfAnim::Run,
-                current_frame: 0,
-                timer: Timer::from_seconds(0.1 + rand::thread_rng().gen_range(-0.01..=0.01), TimerMode::Repeating),
-            }
-        ));
-
-        commands.entity(sheep_entity).insert(UnderHunting);
-    }
+        ))
+        .id()
 }
 
-#[derive(Component)]
-pub struct SheepDying;
-
 fn catch_system(
     mut commands: Commands,
     sheep: Query<&Transform>,
     mut wolfs: Query<(Entity, &Transform, &mut WalkController, &TryToCatchSheep)>,
-    asset_server : Res<AssetServer>,
     mut spawn_corpse : EventWriter<SpawnCorpse>,
-    sheep_dying : Query<(), With<SheepDying>>
 ) {
-    let mut sheep_dying_count = sheep_dying.iter().count();
+    //Several wolves of a pack can hunt the same sheep, only the first one gets it
+    let mut killed = Vec::new();
     for (wolf, wolf_transform, mut walk_controller, try_to_catch_sheep) in wolfs.iter_mut() {
         let wolf_translation = wolf_transform.translation;
-        if let Ok(sheep) = sheep.get(try_to_catch_sheep.target) {
-            if wolf_translation.distance(sheep.translation) < 1.0 {
-                commands
-                    .entity(wolf)
-                    .insert(Eating { time: 2.0 })
-                    .remove::<TryToCatchSheep>()
-                    .insert(AutoAnim {
-                        set: WolfAnim::Eat,
-                        current_frame: 0,
-                        timer: Timer::from_seconds(0.1, TimerMode::Repeating),
-                    });
-
-                commands
-                    .entity(try_to_catch_sheep.target)
-                    .despawn_recursive();
-
-                spawn_corpse.send(SpawnCorpse { position: sheep.translation });
-
-                if sheep_dying_count < 3 {
-                    commands.spawn(AudioBundle {
-                        source: asset_server.load("audio/kill_sound.ogg"),
-                        settings: PlaybackSettings {
-                            mode: bevy::audio::PlaybackMode::Despawn,
-                            volume: Volume::new_relative(0.7),
-                            spatial: true,
-                            ..default()
-                        },
-                    }).insert(SheepDying);
-                    sheep_dying_count += 3;
-                }
-            } else {
-                walk_controller.target_velocity =
-                    (sheep.translation - wolf_translation).normalize() * WOLF_SPEED;
-                walk_controller.target_velocity = walk_controller
-                    .target_velocity
-                    .clamp_length_max((sheep.translation - wolf_translation).length() * 2.0);
-            }
+        let target = try_to_catch_sheep.target;
+        let Some(sheep) = sheep.get(target).ok().filter(|_| !killed.contains(&target)) else {
+            //Sheep is eaten by another wolf, look for the next one as after eating
+            commands
+                .entity(wolf)
+                .insert(Eating { time: 0.0 })
+                .remove::<TryToCatchSheep>();
+            continue;
+        };
+
+        if wolf_translation.distance(sheep.translation) < 1.0 {
+            commands
+                .entity(wolf)
+                .insert(Eating { time: 2.0 })
+                .remove::<TryToCatchSheep>();
+
+            commands.entity(target).despawn_recursive();
+            killed.push(target);
+
+            spawn_corpse.send(SpawnCorpse {
+                position: sheep.translation,
+                killer: wolf,
+            });
+        } else {
+            walk_controller.target_velocity =
+                (sheep.translation - wolf_translation).normalize() * WOLF_SPEED;
+            walk_controller.target_velocity = walk_controller
+                .target_velocity
+                .clamp_length_max((sheep.translation - wolf_translation).length() * 2.0);
         }
     }
 }
@@ -250,12 +205,7 @@ fn eating_system(
 
                 commands.entity(e).insert(UnderHunting);
             } else {
-                commands.entity(wolf).remove::<Eating>().insert(GoOut)
-                    .insert(AutoAnim {
-                        set: WolfAnim::Run,
-                        current_frame: 0,
-                        timer: Timer::from_seconds(0.1, TimerMode::Repeating),
-                    });
+                commands.entity(wolf).remove::<Eating>().insert(GoOut);
             }
         } else {
             walk_controller.target_velocity = Vec3::ZERO;
@@ -282,12 +232,7 @@ fn go_out_system(
                 y: wolf_transform.translation.z,
             })
         }) {
-            commands.entity(wolf).insert(GoOut).remove::<Eating>()
-                .insert(AutoAnim {
-                    set: WolfAnim::Run,
-                    current_frame: 0,
-                    timer: Timer::from_seconds(0.1, TimerMode::Repeating),
-                });
+            commands.entity(wolf).insert(GoOut).remove::<Eating>();
         }
     }
 }
@@ -303,7 +248,7 @@ fn run_out_system(
         ),
         (With<Wolf>, Without<GoOut>),
     >,
-    safearea: Query<&SafeArea>,
+    safearea: Query<&SafeArea, Without<TargetPen>>,
 ) {
     for (wolf, wolf_transform, mut walk_controller, catch) in wolfs.iter_mut() {
         if let Some(catch) = catch {
@@ -325,45 +270,71 @@ fn run_out_system(
                 .entity(wolf)
                 .insert(GoOut)
                 .remove::<TryToCatchSheep>()
-                .remove::<Eating>()
-                .insert(AutoAnim {
-                    set: WolfAnim::Run,
-                    current_frame: 0,
-                    timer: Timer::from_seconds(0.1, TimerMode::Repeating),
-                });
+                .remove::<Eating>();
 
             if let Some(catch) = catch {
-                commands.entity(catch.target).remove::<UnderHunting>();
+                if let Some(mut sheep) = commands.get_entity(catch.target) {
+                    sheep.remove::<UnderHunting>();
+                }
             }
         }
     }
 }
 
-fn bark(
+fn attach_wolf_visuals(
     mut commands: Commands,
-    mut wolfs: Query<(Entity, &Transform, Option<&TryToCatchSheep>), With<Wolf>>,
-    mut barks: EventReader<Bark>,
+    wolfs: Query<Entity, Added<Wolf>>,
+    common_storage: Res<CommonStorage>,
+    wolf_storage: Res<WolfStorage>,
 ) {
-    let Some(bark) = barks.read().next() else {
-        return;
-    };
+    for wolf in wolfs.iter() {
+        commands.entity(wolf).insert((
+            common_storage.plane.clone(),
+            wolf_storage.material.clone(),
+            AutoAnim {
+                set: WolfAnim::Run,
+                current_frame: 0,
+                timer: Timer::from_seconds(0.1 + rand::thread_rng().gen_range(-0.01..=0.01), TimerMode::Repeating),
+            },
+        ));
+    }
+}
 
-    for (wolf, wolf_transform, catch) in wolfs.iter_mut() {
-        if wolf_transform.translation.distance(bark.position) < bark.radius {
-            commands
-                .entity(wolf)
-                .insert(GoOut)
-                .remove::<Eating>()
-                .remove::<TryToCatchSheep>()
-                .insert(AutoAnim {
-                    set: WolfAnim::Run,
-                    current_frame: 0,
-                    timer: Timer::from_seconds(0.1, TimerMode::Repeating),
-                });
+fn set_anim_state(mut wolfs: Query<(&mut AutoAnim<WolfAnim>, Option<&Eating>), With<Wolf>>) {
+    for (mut anim, eating) in wolfs.iter_mut() {
+        if eating.is_some() {
+            anim.set = WolfAnim::Eat;
+        } else {
+            anim.set = WolfAnim::Run;
+        }
+    }
+}
 
-            if let Some(catch) = catch {
-                commands.entity(catch.target).remove::<UnderHunting>();
-            }
+#[derive(Component)]
+pub struct SheepDying;
+
+fn kill_sound(
+    mut commands: Commands,
+    mut corpses: EventReader<SpawnCorpse>,
+    asset_server : Res<AssetServer>,
+    sheep_dying : Query<(), With<SheepDying>>
+) {
+    let mut sheep_dying_count = sheep_dying.iter().count();
+    for corpse in corpses.read() {
+        if sheep_dying_count < 3 {
+            commands.spawn(AudioBundle {
+                source: asset_server.load("audio/kill_sound.ogg"),
+                settings: PlaybackSettings {
+                    mode: bevy::audio::PlaybackMode::Despawn,
+                    volume: Volume::new_relative(0.7),
+                    spatial: true,
+                    ..default()
+                },
+            }).insert((
+                SheepDying,
+                SpatialBundle::from_transform(Transform::from_translation(corpse.position)),
+            ));
+            sheep_dying_count += 3;
         }
     }
 }
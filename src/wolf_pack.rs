@@ -0,0 +1,339 @@
+//Wolves hunt in packs. Pack is a separate entity with a leader and members:
+//  - pack comes for a group of sheep outside of safe areas, all of them are marked UnderHunting
+//  - stalk: leader waits in front of the group, flankers go around it from both sides,
+//    cutter stands between the group and the nearest safe area, so sheep have nowhere to run
+//  - attack: when everybody is in place (or stalking takes too long) every wolf catches
+//    its own sheep of the group, see wolf::catch_system
+//  - retreat: bark at any wolf of the pack makes the whole pack run away
+//Dog can see the pack spreading around the flock and break it up before the attack
+
+use std::f32::consts::PI;
+
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::{
+    difficulty::Difficulty,
+    physics::WalkController,
+    player::Bark,
+    safe_area::{OutOfSafeArea, SafeArea},
+    test_level::LevelSize,
+    wolf::{spawn_wolf, Eating, GoOut, TryToCatchSheep, UnderHunting, Wolf, WOLF_SPEED},
+    GameStuff,
+};
+
+const PACK_SIZE: usize = 4;
+//Even a single sheep is hunted by a pair
+const MIN_PACK_SIZE: usize = 2;
+//Sheep closer than this to the first one are hunted by the same pack
+const GROUP_RADIUS: f32 = 8.0;
+//Wolves wait this far from the group center while stalking
+const STALK_RADIUS: f32 = 8.0;
+//Angle between the leader and a flanker around the group
+const FLANK_ANGLE: f32 = PI * 0.6;
+//Cutter stands at most this far from the group towards the safe area
+const CUT_DISTANCE: f32 = 6.0;
+//Wolf is in place when it is this close to its slot
+const SLOT_TOLERANCE: f32 = 1.5;
+//Pack attacks after this time even if not everybody is in place
+const MAX_STALK_TIME: f32 = 10.0;
+//Distance between wolves of a new pack
+const PACK_SPREAD: f32 = 2.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PackRole {
+    Leader,
+    //Side of the group: 1 or -1
+    Flank(f32),
+    Cutter,
+}
+
+impl PackRole {
+    //Leader, flankers from both sides, cutter, then more flankers
+    fn for_member(index: usize) -> Self {
+        match index {
+            0 => PackRole::Leader,
+            3 => PackRole::Cutter,
+            i if i % 2 == 1 => PackRole::Flank(1.0),
+            _ => PackRole::Flank(-1.0),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PackState {
+    Stalk,
+    Attack,
+    Retreat,
+}
+
+#[derive(Component, Debug)]
+pub struct WolfPack {
+    //Leader is the first one
+    pub members: Vec<Entity>,
+    pub targets: Vec<Entity>,
+    pub state: PackState,
+    pub stalk_time: f32,
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+pub struct PackMember {
+    pub pack: Entity,
+    pub role: PackRole,
+}
+
+//Pack does not come through a round safe area. Safe areas are on the ground plane, SafeArea pos is (x, z)
+fn crosses_safe_area<'a>(
+    start_pos: Vec3,
+    target: Vec3,
+    mut safe_areas: impl Iterator<Item = &'a SafeArea>,
+) -> bool {
+    let dir = (target - start_pos).normalize();
+    let t_to_ship = (target - start_pos).length();
+    safe_areas.any(|safe_area| {
+        if let SafeArea::Circle { pos, radius } = safe_area {
+            let t = (-start_pos.x * dir.x - start_pos.z * dir.z + pos.x * dir.x + pos.y * dir.z)
+                / (dir.x * dir.x + dir.z * dir.z);
+            let nearest_pos = start_pos + dir * t;
+            (Vec2::new(nearest_pos.x, nearest_pos.z) - *pos).length() < *radius
+                && t > 0.0
+                && t < t_to_ship
+        } else {
+            false
+        }
+    })
+}
+
+pub(crate) fn pack_spawner(
+    mut commands: Commands,
+    sheep: Query<(Entity, &Transform), (With<OutOfSafeArea>, Without<UnderHunting>)>,
+    level_size: Res<LevelSize>,
+    wolfs: Query<(), With<Wolf>>,
+    safe_areas: Query<&SafeArea>,
+    difficulty: Res<Difficulty>,
+) {
+    let mut num_wolfs = wolfs.iter().count();
+    let mut taken = HashSet::new();
+
+    for (sheep_entity, sheep_transform) in sheep.iter() {
+        if num_wolfs >= difficulty.max_wolves {
+            return;
+        }
+        if taken.contains(&sheep_entity) {
+            continue;
+        }
+
+        let start_pos = sheep_transform.translation.normalize() * level_size.0 * 2.0;
+        if crosses_safe_area(start_pos, sheep_transform.translation, safe_areas.iter()) {
+            continue;
+        }
+
+        let targets: Vec<Entity> = sheep
+            .iter()
+            .filter(|(e, t)| {
+                !taken.contains(e)
+                    && t.translation.distance(sheep_transform.translation) < GROUP_RADIUS
+            })
+            .map(|(e, _)| e)
+            .collect();
+        let size = targets
+            .len()
+            .clamp(MIN_PACK_SIZE, PACK_SIZE)
+            .min(difficulty.max_wolves - num_wolfs);
+
+        let (pack, _) = spawn_pack(&mut commands, start_pos, size, targets.clone(), PackState::Stalk);
+        num_wolfs += size;
+        for target in targets.iter() {
+            taken.insert(*target);
+        }
+        debug!("Wolf pack {:?} hunts {} sheep", pack, targets.len());
+    }
+}
+
+/// Pack of `size` wolves side by side at `start_pos`. Targets are marked UnderHunting.
+/// Returns the pack and its wolves, leader first
+pub fn spawn_pack(
+    commands: &mut Commands,
+    start_pos: Vec3,
+    size: usize,
+    targets: Vec<Entity>,
+    state: PackState,
+) -> (Entity, Vec<Entity>) {
+    let pack = commands.spawn(GameStuff).id();
+    let side = Vec3::Y.cross(start_pos).normalize_or_zero();
+    let mut members = Vec::with_capacity(size);
+    for i in 0..size {
+        let offset = side * PACK_SPREAD * (i as f32 - (size as f32 - 1.0) / 2.0);
+        let wolf = spawn_wolf(commands, start_pos + offset);
+        commands.entity(wolf).insert(PackMember {
+            pack,
+            role: PackRole::for_member(i),
+        });
+        members.push(wolf);
+    }
+
+    for target in targets.iter() {
+        commands.entity(*target).insert(UnderHunting);
+    }
+    commands.entity(pack).insert(WolfPack {
+        members: members.clone(),
+        targets,
+        state,
+        stalk_time: 0.0,
+    });
+    (pack, members)
+}
+
+pub(crate) fn pack_tactics(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut packs: Query<(Entity, &mut WolfPack)>,
+    mut wolfs: Query<(&Transform, &mut PackMember, &mut WalkController), (With<Wolf>, Without<GoOut>)>,
+    sheep: Query<&Transform, (With<OutOfSafeArea>, Without<Wolf>)>,
+    safe_areas: Query<&SafeArea>,
+) {
+    for (pack_entity, mut pack) in packs.iter_mut() {
+        //Wolves which are going out are not in the pack anymore, next one becomes the leader
+        pack.members.retain(|e| wolfs.contains(*e));
+        if pack.members.is_empty() {
+            commands.entity(pack_entity).despawn_recursive();
+            continue;
+        }
+        if pack.state != PackState::Stalk {
+            continue;
+        }
+
+        //Sheep which got into a safe area are lost for the pack
+        let (targets, lost): (Vec<Entity>, Vec<Entity>) =
+            pack.targets.iter().partition(|e| sheep.contains(**e));
+        for e in lost {
+            if let Some(mut lost_sheep) = commands.get_entity(e) {
+                lost_sheep.remove::<UnderHunting>();
+            }
+        }
+        pack.targets = targets;
+        if pack.targets.is_empty() {
+            retreat(&mut commands, &mut pack);
+            continue;
+        }
+
+        let center = pack
+            .targets
+            .iter()
+            .filter_map(|e| sheep.get(*e).ok())
+            .map(|t| t.translation)
+            .sum::<Vec3>()
+            / pack.targets.len() as f32;
+
+        let leader = pack.members[0];
+        let Ok((leader_transform, _, _)) = wolfs.get(leader) else {
+            continue;
+        };
+        let approach = (leader_transform.translation - center).normalize_or_zero();
+
+        let center_2d = Vec2::new(center.x, center.z);
+        let safe = safe_areas
+            .iter()
+            .map(|area| area.nearest_point(center_2d))
+            .min_by(|a, b| a.distance(center_2d).total_cmp(&b.distance(center_2d)));
+
+        let mut in_place = true;
+        for (i, member) in pack.members.iter().enumerate() {
+            let Ok((t, mut pack_member, mut walk)) = wolfs.get_mut(*member) else {
+                continue;
+            };
+            pack_member.role = PackRole::for_member(i);
+            let slot = match pack_member.role {
+                PackRole::Leader => center + approach * STALK_RADIUS,
+                PackRole::Flank(side) => {
+                    center + Quat::from_rotation_y(FLANK_ANGLE * side) * approach * STALK_RADIUS
+                }
+                PackRole::Cutter => match safe {
+                    Some(safe) => {
+                        let to_safe = safe - center_2d;
+                        let cut = to_safe.clamp_length_max(CUT_DISTANCE.min(to_safe.length() * 0.5));
+                        center + Vec3::new(cut.x, 0.0, cut.y)
+                    }
+                    None => center - approach * STALK_RADIUS,
+                },
+            };
+
+            let to_slot = slot - t.translation;
+            in_place &= to_slot.length() < SLOT_TOLERANCE;
+            walk.target_velocity = (to_slot * 2.0).clamp_length_max(WOLF_SPEED);
+        }
+
+        pack.stalk_time += time.delta_seconds();
+        if in_place || pack.stalk_time > MAX_STALK_TIME {
+            pack.state = PackState::Attack;
+            //Sheep without a wolf are free, wolves without a sheep share one
+            if pack.targets.len() > pack.members.len() {
+                let count = pack.members.len();
+                for target in pack.targets.drain(count..) {
+                    commands.entity(target).remove::<UnderHunting>();
+                }
+            }
+            for (i, member) in pack.members.iter().enumerate() {
+                commands.entity(*member).insert(TryToCatchSheep {
+                    target: pack.targets[i % pack.targets.len()],
+                    ignore_safe: false,
+                });
+            }
+        }
+    }
+}
+
+/// All wolves of the pack run away and free their sheep
+pub fn retreat(commands: &mut Commands, pack: &mut WolfPack) {
+    pack.state = PackState::Retreat;
+    for member in pack.members.iter() {
+        commands
+            .entity(*member)
+            .insert(GoOut)
+            .remove::<Eating>()
+            .remove::<TryToCatchSheep>();
+    }
+    for target in pack.targets.drain(..) {
+        if let Some(mut sheep) = commands.get_entity(target) {
+            sheep.remove::<UnderHunting>();
+        }
+    }
+}
+
+pub(crate) fn pack_bark(
+    mut commands: Commands,
+    wolfs: Query<(&Transform, &PackMember), With<Wolf>>,
+    mut packs: Query<&mut WolfPack>,
+    mut barks: EventReader<Bark>,
+) {
+    for bark in barks.read() {
+        for (wolf_transform, member) in wolfs.iter() {
+            if wolf_transform.translation.distance(bark.position) >= bark.radius {
+                continue;
+            }
+            if let Ok(mut pack) = packs.get_mut(member.pack) {
+                if pack.state != PackState::Retreat {
+                    retreat(&mut commands, &mut pack);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pen_between_spawn_and_sheep_blocks_the_pack() {
+        let pen = [SafeArea::Circle {
+            pos: Vec2::new(0.0, 10.0),
+            radius: 3.0,
+        }];
+        let start = Vec3::new(0.0, 0.0, 30.0);
+
+        assert!(crosses_safe_area(start, Vec3::ZERO, pen.iter()));
+        //Pen behind the sheep or aside of the path does not block
+        assert!(!crosses_safe_area(start, Vec3::new(0.0, 0.0, 20.0), pen.iter()));
+        assert!(!crosses_safe_area(Vec3::new(30.0, 0.0, 0.0), Vec3::ZERO, pen.iter()));
+    }
+}
@@ -0,0 +1,101 @@
+//Whole simulation without window, audio or renderer, the same way as examples/headless_level.rs runs it
+
+use std::time::Duration;
+
+use bevy::{
+    ecs::schedule::{ExecutorKind, Schedules},
+    input::InputPlugin,
+    prelude::*,
+    time::TimeUpdateStrategy,
+};
+use bevy_game::{
+    campaign::Campaign,
+    difficulty::{Difficulty, DifficultyPreset},
+    game_rng::GameRng,
+    level::LevelDescription,
+    safe_area::SheepCounter,
+    sheep::Sheep,
+    storyteller::{FailReason, Score},
+    GameState, SimulationPlugin,
+};
+
+const FRAME_TIME: f32 = 1.0 / 60.0;
+const SEED: u64 = 7;
+//40 seconds of the level, the director starts its first tasks in this time
+const FRAMES: usize = 60 * 40;
+
+#[derive(Debug, PartialEq)]
+struct Outcome {
+    state: GameState,
+    score: f32,
+    sheep: usize,
+    in_safe_area: u32,
+    fail: Option<String>,
+}
+
+fn run_level(seed: u64, frames: usize) -> Outcome {
+    let mut level = LevelDescription::default();
+    level.flock.count = 300;
+
+    let mut app = App::new();
+    //Inserted before the plugins, otherwise GameRngPlugin picks a random seed
+    app.insert_resource(GameRng::new(seed))
+        .add_plugins((MinimalPlugins, InputPlugin, SimulationPlugin))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+            FRAME_TIME,
+        )))
+        .insert_resource(Campaign::new(vec![level]))
+        .insert_resource(Difficulty::preset(DifficultyPreset::Normal));
+
+    //Systems without explicit order may run in any order on the shared task pool
+    for (_, schedule) in app.world.resource_mut::<Schedules>().iter_mut() {
+        schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+    }
+
+    app.update();
+    app.world
+        .resource_mut::<NextState<GameState>>()
+        .set(GameState::Playing);
+
+    for _ in 0..frames {
+        app.update();
+        if *app.world.resource::<State<GameState>>().get() == GameState::Finish {
+            break;
+        }
+    }
+
+    let sheep = app
+        .world
+        .query_filtered::<(), With<Sheep>>()
+        .iter(&app.world)
+        .count();
+    let fail = app.world.get_resource::<FailReason>().map(|fail| match fail {
+        FailReason::SheepDied => "sheep died".to_string(),
+        FailReason::TaskFailed(reason) => reason.clone(),
+    });
+
+    Outcome {
+        state: app.world.resource::<State<GameState>>().get().clone(),
+        score: app.world.resource::<Score>().0,
+        sheep,
+        in_safe_area: app.world.resource::<SheepCounter>().count,
+        fail,
+    }
+}
+
+#[test]
+fn level_with_fixed_seed() {
+    let outcome = run_level(SEED, FRAMES);
+
+    assert_eq!(outcome.state, GameState::Playing);
+    assert_eq!(outcome.fail, None);
+    assert_eq!(outcome.sheep, 300);
+    assert_eq!(outcome.in_safe_area, 300);
+    //float math may differ slightly between platforms
+    assert!((outcome.score - 39.983).abs() < 0.01, "{:?}", outcome);
+}
+
+#[test]
+fn same_seed_gives_same_level() {
+    assert_eq!(run_level(SEED, FRAMES), run_level(SEED, FRAMES));
+}